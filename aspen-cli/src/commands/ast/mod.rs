@@ -0,0 +1,85 @@
+use crate::reporter::report;
+use aspen::syntax::{Node, Parser};
+use aspen::Source;
+use clap::{App, Arg, ArgMatches};
+use std::sync::Arc;
+
+const FILE: &str = "FILE";
+const DEPTH: &str = "DEPTH";
+
+pub fn app() -> App<'static, 'static> {
+    App::new("ast")
+        .about("Prints the syntax tree of a file, for debugging the parser")
+        .arg(
+            Arg::with_name(FILE)
+                .help("The file to parse")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name(DEPTH)
+                .long("depth")
+                .help("How deep into the tree to print")
+                .takes_value(true),
+        )
+}
+
+pub async fn main(matches: &ArgMatches<'_>) -> clap::Result<()> {
+    let source = Source::file(matches.value_of(FILE).unwrap()).await?;
+    let depth = matches.value_of(DEPTH).and_then(|d| d.parse().ok());
+
+    let context = aspen::Context::infer().await?;
+
+    let (root, diagnostics) = Parser::new(source).parse().await;
+
+    print!("{}", dump(root, depth));
+    report(&context, diagnostics);
+
+    Ok(())
+}
+
+fn dump(root: Arc<dyn Node>, depth: Option<usize>) -> String {
+    let mut output = String::new();
+    dump_node(&mut output, root, 0, depth);
+    output
+}
+
+fn dump_node(output: &mut String, node: Arc<dyn Node>, level: usize, depth: Option<usize>) {
+    output.push_str(
+        format!(
+            "{}{} {:?}\n",
+            "  ".repeat(level),
+            label(node.as_ref()),
+            node.range()
+        )
+        .as_str(),
+    );
+
+    if depth.map(|d| level < d).unwrap_or(true) {
+        for child in node.children() {
+            dump_node(output, child, level + 1, depth);
+        }
+    }
+}
+
+/// The name of the node, taken from the beginning of its `Debug`
+/// representation.
+fn label(node: &dyn Node) -> String {
+    format!("{:?}", node)
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == ':' || *c == '_')
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn object_declaration() {
+        let source = Source::new("test:x", "object X.");
+        let (root, _) = Parser::new(source).parse().await;
+
+        assert!(dump(root.clone(), None).contains("ObjectDeclaration"));
+        assert!(!dump(root, Some(1)).contains("ObjectDeclaration"));
+    }
+}
@@ -1,18 +1,26 @@
+use crate::output;
 use crate::platform::*;
+use aspen::Context;
 use clap::{App, Arg, ArgMatches};
 use rustyline::Editor;
+use std::future::Future;
 use std::io::{stdin, Read};
 use std::process::exit;
+use url::Url;
 
 const PLATFORM_URL: &str = "PLATFORM_URL";
 const USERNAME: &str = "USERNAME";
 const EMAIL: &str = "EMAIL";
 const USERNAME_OR_EMAIL: &str = "USERNAME_OR_EMAIL";
 const PASSWORD_STDIN: &str = "PASSWORD_STDIN";
+const YES: &str = "YES";
+const JSON: &str = "JSON";
+
+const DEFAULT_PLATFORM_URL: &str = "https://platform.aspen-lang.com";
+const PLATFORM_URL_ENV: &str = "ASPEN_PLATFORM_URL";
 
 pub fn app() -> App<'static, 'static> {
-    let platform_url =
-        Arg::with_name(PLATFORM_URL).default_value("https://platform.aspen-lang.com");
+    let platform_url = Arg::with_name(PLATFORM_URL).env(PLATFORM_URL_ENV);
 
     let username = Arg::with_name(USERNAME)
         .long("username")
@@ -27,6 +35,10 @@ pub fn app() -> App<'static, 'static> {
         .short("u")
         .takes_value(true);
     let password_stdin = Arg::with_name(PASSWORD_STDIN).long("password-stdin");
+    let yes = Arg::with_name(YES)
+        .long("yes")
+        .short("y")
+        .help("Skips asking for the username of the account to confirm");
 
     App::new("auth")
         .about("Runs commands related to the authentication to any hosted Aspen Platform(s)")
@@ -41,7 +53,12 @@ pub fn app() -> App<'static, 'static> {
         .subcommand(
             App::new("whoami")
                 .about("Displays the currently signed in user on the platform")
-                .arg(platform_url.clone()),
+                .arg(platform_url.clone())
+                .arg(
+                    Arg::with_name(JSON)
+                        .long("json")
+                        .help("Prints the user as JSON, with null if no one is signed in"),
+                ),
         )
         .subcommand(
             App::new("sign-out")
@@ -61,7 +78,8 @@ pub fn app() -> App<'static, 'static> {
                     "Deletes the account that is currently signed in completely from the platform",
                 )
                 .arg(platform_url.clone())
-                .arg(password_stdin.clone()),
+                .arg(password_stdin.clone().requires(YES))
+                .arg(yes),
         )
 }
 
@@ -90,9 +108,7 @@ pub async fn main(matches: &ArgMatches<'_>) -> clap::Result<()> {
 }
 
 async fn sign_up(matches: &ArgMatches<'_>) -> clap::Result<()> {
-    let platform_url = matches.value_of(PLATFORM_URL).unwrap();
-    let platform_url = platform_url.parse().unwrap();
-    let client = PlatformClient::new(platform_url).unwrap();
+    let client = PlatformClient::new(platform_url(matches).await).unwrap();
 
     let read_password_from_stdin = matches.is_present(PASSWORD_STDIN);
     if read_password_from_stdin && !matches.is_present(USERNAME) {
@@ -117,21 +133,47 @@ async fn sign_up(matches: &ArgMatches<'_>) -> clap::Result<()> {
 }
 
 async fn whoami(matches: &ArgMatches<'_>) -> clap::Result<()> {
-    let platform_url = matches.value_of(PLATFORM_URL).unwrap();
-    let platform_url = platform_url.parse().unwrap();
-    let client = PlatformClient::new(platform_url).unwrap();
+    let platform_url = platform_url(matches).await;
+    let client = PlatformClient::new(platform_url.clone()).unwrap();
 
     let data = client.query::<MeQuery>(me_query::Variables).await.unwrap();
 
-    println!("{:?}", data);
+    if matches.is_present(JSON) {
+        println!("{}", whoami_json(data.me.as_ref(), &platform_url));
+    } else {
+        println!("{}", whoami_summary(data.me.as_ref(), &platform_url));
+    }
 
     Ok(())
 }
 
+fn whoami_summary(me: Option<&me_query::MeQueryMe>, platform_url: &Url) -> String {
+    let me = match me {
+        None => return format!("Not signed in on {}", platform_url),
+        Some(me) => me,
+    };
+
+    let mut summary = format!("Username  {}\n", me.username);
+    if let Some(email) = me.email.as_ref() {
+        summary.push_str(format!("Email     {}\n", email).as_str());
+    }
+    summary.push_str(format!("Platform  {}", platform_url).as_str());
+    summary
+}
+
+fn whoami_json(me: Option<&me_query::MeQueryMe>, platform_url: &Url) -> serde_json::Value {
+    serde_json::json!({
+        "platform_url": platform_url.as_str(),
+        "user": me.map(|me| serde_json::json!({
+            "id": me.id.to_string(),
+            "username": me.username,
+            "email": me.email,
+        })),
+    })
+}
+
 async fn sign_out(matches: &ArgMatches<'_>) -> clap::Result<()> {
-    let platform_url = matches.value_of(PLATFORM_URL).unwrap();
-    let platform_url = platform_url.parse().unwrap();
-    let client = PlatformClient::new(platform_url).unwrap();
+    let client = PlatformClient::new(platform_url(matches).await).unwrap();
 
     let data = client
         .query::<SignOutMutation>(sign_out_mutation::Variables)
@@ -144,9 +186,7 @@ async fn sign_out(matches: &ArgMatches<'_>) -> clap::Result<()> {
 }
 
 async fn sign_in(matches: &ArgMatches<'_>) -> clap::Result<()> {
-    let platform_url = matches.value_of(PLATFORM_URL).unwrap();
-    let platform_url = platform_url.parse().unwrap();
-    let client = PlatformClient::new(platform_url).unwrap();
+    let client = PlatformClient::new(platform_url(matches).await).unwrap();
 
     let read_password_from_stdin = matches.is_present(PASSWORD_STDIN);
     if read_password_from_stdin && !matches.is_present(USERNAME_OR_EMAIL) {
@@ -170,22 +210,106 @@ async fn sign_in(matches: &ArgMatches<'_>) -> clap::Result<()> {
 }
 
 async fn remove_account(matches: &ArgMatches<'_>) -> clap::Result<()> {
-    let platform_url = matches.value_of(PLATFORM_URL).unwrap();
-    let platform_url = platform_url.parse().unwrap();
-    let client = PlatformClient::new(platform_url).unwrap();
-
-    let data = client
-        .query::<RemoveAccountMutation>(remove_account_mutation::Variables {
-            password: stdin_or_ask_hidden("Password", matches.is_present(PASSWORD_STDIN)),
-        })
-        .await
-        .unwrap();
+    let client = PlatformClient::new(platform_url(matches).await).unwrap();
+    let client = &client;
+    let read_password_from_stdin = matches.is_present(PASSWORD_STDIN);
 
-    println!("{:?}", data);
+    let result = remove_confirmed_account(
+        matches.is_present(YES),
+        move || async move {
+            client
+                .query::<CurrentUsernameQuery>(current_username_query::Variables)
+                .await
+                .unwrap()
+                .me
+                .map(|me| me.username)
+        },
+        ask,
+        move || async move {
+            client
+                .query::<RemoveAccountMutation>(remove_account_mutation::Variables {
+                    password: stdin_or_ask_hidden("Password", read_password_from_stdin),
+                })
+                .await
+                .unwrap()
+        },
+    )
+    .await;
+
+    match result {
+        Ok(data) => println!("{:?}", data),
+        Err(error) => {
+            let e = output::paint(error, |e| {
+                e.red();
+            });
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
 
     Ok(())
 }
 
+/// Removes the account, unless the confirmation is skipped, only after
+/// the user has confirmed the username of the account currently signed in.
+async fn remove_confirmed_account<U, UF, A, R, RF>(
+    skip_confirmation: bool,
+    current_username: U,
+    ask: A,
+    remove: R,
+) -> Result<RF::Output, &'static str>
+where
+    U: FnOnce() -> UF,
+    UF: Future<Output = Option<String>>,
+    A: FnOnce(&str) -> String,
+    R: FnOnce() -> RF,
+    RF: Future,
+{
+    if !skip_confirmation {
+        match current_username().await {
+            None => return Err("No user is signed in on the platform"),
+            Some(username) if !confirms_removal(&username, ask) => {
+                return Err("The username didn't match, so the account was not removed")
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(remove().await)
+}
+
+/// The platform URL given as an argument or in ASPEN_PLATFORM_URL,
+/// falling back to the one in the workspace config, and then to the
+/// default platform.
+async fn platform_url(matches: &ArgMatches<'_>) -> Url {
+    let context = Context::infer().await.ok();
+    resolve_platform_url(matches.value_of(PLATFORM_URL), context.as_deref()).await
+}
+
+async fn resolve_platform_url(given: Option<&str>, context: Option<&Context>) -> Url {
+    let configured = match (given, context) {
+        (None, Some(context)) => configured_platform_url(context).await,
+        _ => None,
+    };
+    given
+        .or(configured.as_deref())
+        .unwrap_or(DEFAULT_PLATFORM_URL)
+        .parse()
+        .unwrap()
+}
+
+async fn configured_platform_url(context: &Context) -> Option<String> {
+    let config = tokio::fs::read(context.config_path()).await.ok()?;
+    let config: serde_json::Value = serde_json::from_slice(config.as_slice()).ok()?;
+    config["platform_url"].as_str().map(Into::into)
+}
+
+/// Asks the user to type the username of the account that is about to
+/// be removed, to make sure it isn't removed by accident.
+fn confirms_removal<A: FnOnce(&str) -> String>(username: &str, ask: A) -> bool {
+    ask(format!("Type {} to remove the account for good", username).as_str()) == username
+}
+
 fn value_or_ask(name: &str, value: Option<&str>) -> String {
     match value {
         None => ask(name),
@@ -223,3 +347,151 @@ fn ask_hidden(prompt: &str) -> String {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn platform_url_precedence() {
+        std::env::remove_var(PLATFORM_URL_ENV);
+        let matches = app().get_matches_from(vec!["auth", "whoami"]);
+        assert_eq!(
+            matches
+                .subcommand_matches("whoami")
+                .unwrap()
+                .value_of(PLATFORM_URL),
+            None
+        );
+
+        std::env::set_var(PLATFORM_URL_ENV, "http://localhost:8080");
+        let matches = app().get_matches_from(vec!["auth", "whoami"]);
+        assert_eq!(
+            matches
+                .subcommand_matches("whoami")
+                .unwrap()
+                .value_of(PLATFORM_URL),
+            Some("http://localhost:8080")
+        );
+
+        let matches = app().get_matches_from(vec!["auth", "whoami", "http://example.com"]);
+        assert_eq!(
+            matches
+                .subcommand_matches("whoami")
+                .unwrap()
+                .value_of(PLATFORM_URL),
+            Some("http://example.com")
+        );
+        std::env::remove_var(PLATFORM_URL_ENV);
+    }
+
+    #[tokio::test]
+    async fn platform_url_from_workspace_config() {
+        let context = Context::temporary(None).unwrap();
+        context.ensure_workspace_dir(None).await.unwrap();
+        tokio::fs::write(
+            context.config_path(),
+            r#"{ "platform_url": "http://platform.example.com" }"#,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            resolve_platform_url(None, Some(&context)).await.as_str(),
+            "http://platform.example.com/"
+        );
+        assert_eq!(
+            resolve_platform_url(Some("http://example.com"), Some(&context))
+                .await
+                .as_str(),
+            "http://example.com/"
+        );
+        assert_eq!(
+            resolve_platform_url(None, None).await.as_str(),
+            "https://platform.aspen-lang.com/"
+        );
+    }
+
+    #[test]
+    fn whoami_json_contains_the_username() {
+        let me = me_query::MeQueryMe {
+            id: uuid::Uuid::new_v4(),
+            username: "alice".into(),
+            email: None,
+        };
+        let platform_url: Url = DEFAULT_PLATFORM_URL.parse().unwrap();
+
+        let json = whoami_json(Some(&me), &platform_url).to_string();
+        let parsed: serde_json::Value = serde_json::from_str(json.as_str()).unwrap();
+        assert_eq!(parsed["user"]["username"], "alice");
+        assert_eq!(parsed["user"]["email"], serde_json::Value::Null);
+
+        let json = whoami_json(None, &platform_url).to_string();
+        let parsed: serde_json::Value = serde_json::from_str(json.as_str()).unwrap();
+        assert_eq!(parsed["user"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn account_removal_requires_confirmation() {
+        let matches = app().get_matches_from(vec!["auth", "remove-account"]);
+        let matches = matches.subcommand_matches("remove-account").unwrap();
+        assert!(!matches.is_present(YES));
+
+        assert!(!confirms_removal("alice", |_| "bob".into()));
+        assert!(!confirms_removal("alice", |_| "alice ".into()));
+        assert!(confirms_removal("alice", |_| "alice".into()));
+
+        let mut removed = false;
+        let result = remove_confirmed_account(
+            false,
+            || async { Some("alice".into()) },
+            |_| "bob".into(),
+            || {
+                removed = true;
+                async {}
+            },
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(!removed);
+
+        let result = remove_confirmed_account(
+            false,
+            || async { None },
+            |_| "alice".into(),
+            || {
+                removed = true;
+                async {}
+            },
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(!removed);
+
+        let result = remove_confirmed_account(
+            false,
+            || async { Some("alice".into()) },
+            |_| "alice".into(),
+            || {
+                removed = true;
+                async {}
+            },
+        )
+        .await;
+        assert!(result.is_ok());
+        assert!(removed);
+    }
+
+    #[test]
+    fn password_stdin_requires_yes_when_removing_accounts() {
+        assert!(app()
+            .get_matches_from_safe(vec!["auth", "remove-account", "--password-stdin"])
+            .is_err());
+
+        let matches =
+            app().get_matches_from(vec!["auth", "remove-account", "--password-stdin", "--yes"]);
+        let matches = matches.subcommand_matches("remove-account").unwrap();
+        assert!(matches.is_present(PASSWORD_STDIN));
+        assert!(matches.is_present(YES));
+    }
+}
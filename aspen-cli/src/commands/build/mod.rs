@@ -1,13 +1,24 @@
+use crate::output;
 use crate::reporter::report;
-use ansi_colors::ColouredStr;
-use aspen::generation::Executable;
+use aspen::generation::{Executable, TargetSpec};
 use aspen::semantics::Host;
-use aspen::Source;
+use aspen::{Severity, URI};
 use clap::{App, Arg, ArgMatches};
+use std::time::Duration;
 
 const MAIN: &str = "MAIN";
 const STATIC: &str = "STATIC";
+const PIE: &str = "PIE";
+const NO_PIE: &str = "NO_PIE";
 const LIBRARY: &str = "LIBRARY";
+const DENY_WARNINGS: &str = "DENY_WARNINGS";
+const TARGET: &str = "TARGET";
+const TARGET_CPU: &str = "TARGET_CPU";
+const TARGET_FEATURE: &str = "TARGET_FEATURE";
+const EMIT: &str = "EMIT";
+const OUT_DIR: &str = "OUT_DIR";
+const VERBOSE: &str = "VERBOSE";
+const ENTRY: &str = "ENTRY";
 
 pub fn app() -> App<'static, 'static> {
     App::new("build")
@@ -17,30 +28,107 @@ pub fn app() -> App<'static, 'static> {
                 .help("The name of the entrypoint object")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name(ENTRY)
+                .long("entry")
+                .takes_value(true)
+                .value_name("ATOM")
+                .conflicts_with(LIBRARY)
+                .help(
+                    "The message told to the main object to start the program (defaults to run!)",
+                ),
+        )
         .arg(
             Arg::with_name(STATIC)
                 .long("static")
                 .help("Link the binary statically"),
         )
+        .arg(
+            Arg::with_name(PIE)
+                .long("pie")
+                .conflicts_with(NO_PIE)
+                .help("Link a position-independent executable"),
+        )
+        .arg(
+            Arg::with_name(NO_PIE)
+                .long("no-pie")
+                .help("Link an executable that is not position-independent"),
+        )
         .arg(
             Arg::with_name(LIBRARY)
                 .long("lib")
                 .short("l")
                 .help("Output a library instead of an executable"),
         )
+        .arg(
+            Arg::with_name(TARGET)
+                .long("target")
+                .takes_value(true)
+                .value_name("TRIPLE")
+                .help("The target triple to build for, e.g. wasm32-unknown-unknown"),
+        )
+        .arg(
+            Arg::with_name(TARGET_CPU)
+                .long("target-cpu")
+                .takes_value(true)
+                .value_name("CPU")
+                .help("The CPU to generate code for, or `native` for this machine's CPU"),
+        )
+        .arg(
+            Arg::with_name(TARGET_FEATURE)
+                .long("target-feature")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .allow_hyphen_values(true)
+                .value_name("FEATURE")
+                .help("Enable (+) or disable (-) a CPU feature, e.g. +avx2"),
+        )
+        .arg(
+            Arg::with_name(EMIT)
+                .long("emit")
+                .takes_value(true)
+                .possible_values(&["obj"])
+                .help("Write object files instead of linking them"),
+        )
+        .arg(
+            Arg::with_name(OUT_DIR)
+                .long("out-dir")
+                .takes_value(true)
+                .value_name("DIR")
+                .requires(EMIT)
+                .help("Where emitted object files are written (defaults to the current directory)"),
+        )
+        .arg(
+            Arg::with_name(VERBOSE)
+                .long("verbose")
+                .short("v")
+                .help("Print the number and sizes of the objects that were built"),
+        )
+        .arg(
+            Arg::with_name(DENY_WARNINGS)
+                .long("deny-warnings")
+                .help("Fail the build if there are any warnings"),
+        )
 }
 
 pub async fn main(matches: &ArgMatches<'_>) -> clap::Result<()> {
     let context = aspen::Context::infer().await?;
 
-    let host = Host::from(context.clone(), Source::files("**/*.aspen").await).await;
+    let host = Host::from(context.clone(), context.sources().await?).await;
+
+    let threshold = if matches.is_present(DENY_WARNINGS) {
+        Severity::Warning
+    } else {
+        Severity::Error
+    };
 
     let diagnostics = host.diagnostics().await;
-    if !diagnostics.is_ok() {
-        report(diagnostics);
-        return Ok(());
+    let is_ok = diagnostics.is_ok_at(threshold);
+    report(&context, diagnostics);
+    if !is_ok {
+        std::process::exit(1);
     }
-    report(diagnostics);
 
     let mut executable = Executable::build(host);
     if !matches.is_present(LIBRARY) {
@@ -50,17 +138,165 @@ pub async fn main(matches: &ArgMatches<'_>) -> clap::Result<()> {
             .or(context.name())
             .expect("Couldn't infer main object name");
         executable.main(main);
+        if let Some(entry) = matches.value_of(ENTRY) {
+            if let Err(error) = executable.entry_selector(entry) {
+                let s = format!("{}", error);
+                eprintln!(
+                    "{}",
+                    output::paint(s.as_str(), |e| {
+                        e.red();
+                    })
+                );
+                std::process::exit(1);
+            }
+        }
     }
     if matches.is_present(STATIC) {
         executable.link_statically();
     }
-    let executable = executable.write().await.unwrap();
+    let mut target = match matches.value_of(TARGET) {
+        Some(triple) => TargetSpec::new(triple),
+        None => TargetSpec::host(),
+    };
+    if let Some(cpu) = matches.value_of(TARGET_CPU) {
+        target = target.with_cpu(cpu);
+    }
+    for feature in matches.values_of(TARGET_FEATURE).into_iter().flatten() {
+        target = match target.with_feature(feature) {
+            Ok(target) => target,
+            Err(error) => {
+                let s = format!("{}", error);
+                eprintln!(
+                    "{}",
+                    output::paint(s.as_str(), |e| {
+                        e.red();
+                    })
+                );
+                std::process::exit(1);
+            }
+        };
+    }
+    executable.target(target);
+    if matches.is_present(PIE) {
+        executable.position_independent(true);
+    } else if matches.is_present(NO_PIE) {
+        executable.position_independent(false);
+    }
+    if !output::quiet() {
+        executable.on_progress(|uri, elapsed| println!("{}", progress_line(uri, elapsed)));
+    }
+    if matches.value_of(EMIT) == Some("obj") {
+        let dir = matches.value_of(OUT_DIR).unwrap_or(".");
+        let objects = match executable.write_objects(dir).await {
+            Ok(objects) => objects,
+            Err(error) => {
+                let s = format!("{}", error);
+                let e = output::paint(s.as_str(), |e| {
+                    e.red();
+                });
+
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+
+        if !output::quiet() {
+            for object in objects {
+                let s = format!("{}", object.path.display());
+                let e = output::paint(s.as_str(), |e| {
+                    e.yellow();
+                });
+
+                println!("Emitted {}", e);
+            }
+        }
 
-    let s = format!("{}", executable);
-    let mut e = ColouredStr::new(s.as_str());
-    e.yellow();
+        return Ok(());
+    }
+
+    let executable = match executable.write().await {
+        Ok(executable) => executable,
+        Err(error) => {
+            let s = format!("{}", error);
+            let e = output::paint(s.as_str(), |e| {
+                e.red();
+            });
 
-    println!("Compiled {}", e);
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if !output::quiet() {
+        let s = format!("{}", executable);
+        let e = output::paint(s.as_str(), |e| {
+            e.yellow();
+        });
+
+        println!("Compiled {}", e);
+
+        if matches.is_present(VERBOSE) {
+            match executable.summary().await {
+                Ok(summary) => println!("{}", summary),
+                Err(error) => eprintln!("Couldn't summarize the build: {}", error),
+            }
+        }
+    }
 
     Ok(())
 }
+
+/// The line printed as soon as a module is generated, rather than once
+/// the whole build is done. Modules are generated concurrently, but
+/// `println!` holds the lock on stdout for the whole line and flushes it
+/// at the newline, so lines from different modules never interleave.
+fn progress_line(uri: &URI, elapsed: Duration) -> String {
+    let s = format!("Generated {} in {:.2?}", uri, elapsed);
+    output::paint(s.as_str(), |e| {
+        e.dark_gray();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspen::{Context, Source};
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn progress_is_printed_per_module() {
+        let dir = std::env::current_dir().unwrap();
+        let context = Arc::new(Context::temporary_in(None, dir.clone()).unwrap());
+        let host = Host::from(
+            context,
+            (0..3).map(|i| {
+                Source::new(
+                    URI::file(dir.join(format!("streamed_{}.aspen", i))),
+                    format!("object S{}.", i),
+                )
+            }),
+        )
+        .await;
+
+        let lines = Arc::new(Mutex::new(vec![]));
+        let mut executable = Executable::build(host);
+        {
+            let lines = lines.clone();
+            executable.on_progress(move |uri, elapsed| {
+                lines.lock().unwrap().push(progress_line(uri, elapsed));
+            });
+        }
+
+        // Linking may fail without the runtime library, but every module
+        // has been generated by then.
+        let _ = executable.write().await;
+
+        let lines = lines.lock().unwrap().clone();
+        assert_eq!(lines.len(), 4, "{:?}", lines);
+        assert!(lines.iter().all(|l| l.starts_with("Generated ")));
+        for i in 0..3 {
+            let name = format!("streamed_{}.aspen", i);
+            assert_eq!(lines.iter().filter(|l| l.contains(&name)).count(), 1);
+        }
+    }
+}
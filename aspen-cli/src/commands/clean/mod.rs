@@ -0,0 +1,53 @@
+use crate::output;
+use clap::{App, Arg, ArgMatches};
+
+const ALL: &str = "ALL";
+const DRY_RUN: &str = "DRY_RUN";
+
+pub fn app() -> App<'static, 'static> {
+    App::new("clean")
+        .about("Removes cached object files and build output from the workspace")
+        .arg(
+            Arg::with_name(ALL)
+                .long("all")
+                .help("Remove the entire workspace directory"),
+        )
+        .arg(
+            Arg::with_name(DRY_RUN)
+                .long("dry-run")
+                .help("List what would be removed without removing anything"),
+        )
+}
+
+pub async fn main(matches: &ArgMatches<'_>) -> clap::Result<()> {
+    let context = aspen::Context::infer().await?;
+    let dry_run = matches.is_present(DRY_RUN);
+
+    let removed = match context.clean(matches.is_present(ALL), dry_run).await {
+        Ok(removed) => removed,
+        Err(error) => {
+            let s = format!("{}", error);
+            let e = output::paint(s.as_str(), |e| {
+                e.red();
+            });
+
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    for path in removed {
+        let s = format!("{}", path.display());
+        let e = output::paint(s.as_str(), |e| {
+            e.dark_gray();
+        });
+
+        if dry_run {
+            println!("Would remove {}", e);
+        } else {
+            println!("Removed {}", e);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use crate::output;
+use aspen::semantics::Host;
+use aspen::URI;
+use clap::{App, Arg, ArgMatches};
+use std::path::Path;
+
+const FILE: &str = "FILE";
+const LINE: &str = "LINE";
+const COLUMN: &str = "COLUMN";
+
+pub fn app() -> App<'static, 'static> {
+    App::new("explain")
+        .about("Explains how the type of the expression at a position was inferred")
+        .arg(
+            Arg::with_name(FILE)
+                .help("The file or URI of the module")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name(LINE)
+                .help("The line of the expression, starting at 1")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name(COLUMN)
+                .help("The column of the expression, starting at 1")
+                .required(true),
+        )
+}
+
+pub async fn main(matches: &ArgMatches<'_>) -> clap::Result<()> {
+    let context = aspen::Context::infer().await?;
+    let line: usize = matches.value_of(LINE).unwrap().parse().unwrap_or(1);
+    let column: usize = matches.value_of(COLUMN).unwrap().parse().unwrap_or(1);
+
+    let file = matches.value_of(FILE).unwrap();
+    let uri = if file.contains(':') {
+        URI::from(file)
+    } else {
+        URI::file(Path::new(file).canonicalize()?)
+    };
+
+    let sources = context.sources().await?;
+    let host = Host::from(context, sources).await;
+
+    let module = match host.get(&uri).await {
+        Some(module) => module,
+        None => fail(format!("{} is not part of the current context", uri)),
+    };
+
+    let location = module.source.location_at_coords(line.max(1), column.max(1));
+    match module.explain_type_at(location).await {
+        Some(explanation) => print!("{}", explanation),
+        None => fail(format!("There is no expression at {}:{}", line, column)),
+    }
+
+    Ok(())
+}
+
+fn fail(message: String) -> ! {
+    let e = output::paint(message.as_str(), |e| {
+        e.red();
+    });
+
+    eprintln!("{}", e);
+    std::process::exit(1)
+}
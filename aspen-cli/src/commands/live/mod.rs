@@ -1,9 +1,16 @@
+use crate::output;
 use crate::reporter::report;
-use aspen::generation::JIT;
+use aspen::generation::{CapturedPrints, JIT};
+use aspen::syntax::is_incomplete;
 use aspen::{Source, URI};
 use clap::{App, ArgMatches};
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
+use std::time::Duration;
+
+/// How long the value of an expression is waited for before the prompt
+/// returns without it.
+const VALUE_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub fn app() -> App<'static, 'static> {
     App::new("live").about("Starts a live programming environment in the terminal")
@@ -11,37 +18,78 @@ pub fn app() -> App<'static, 'static> {
 
 pub async fn main(_matches: &ArgMatches<'_>) -> clap::Result<()> {
     let context = aspen::Context::infer().await?;
-    let host = context.host();
-    let jit = JIT::new(context);
+    let host = context.host().await;
+    // The values of evaluated expressions are captured, so that they
+    // can be printed along with their types.
+    let values = CapturedPrints::start();
+    let jit = JIT::new(context)
+        .with_workers(super::run::workers())
+        .with_print(values.print_fn());
     jit.init_live_env(host.clone()).unwrap();
+    if let Some(prelude) = host.prelude().await {
+        jit.evaluate(prelude).unwrap();
+    }
 
     let mut rl = Editor::<()>::new();
     let mut line_number: usize = 0;
+    let mut input = String::new();
     loop {
-        match rl.readline(">> ") {
+        let prompt = if input.is_empty() { ">> " } else { ".. " };
+        match rl.readline(prompt) {
             Ok(line) => {
                 rl.add_history_entry(&line);
+
+                if !input.is_empty() {
+                    input.push('\n');
+                }
+                input.push_str(line.as_str());
+
+                let source = Source::inline(
+                    URI::new("repl", (line_number + 1).to_string()),
+                    input.clone(),
+                );
+
+                if is_incomplete(&source) {
+                    continue;
+                }
+
+                input.clear();
                 line_number += 1;
 
-                let module = host
-                    .set(Source::inline(
-                        URI::new("repl", line_number.to_string()),
-                        line,
-                    ))
-                    .await;
+                let module = host.set(source).await;
 
                 let diagnostics = module.diagnostics().await;
 
                 if !diagnostics.is_ok() {
-                    report(diagnostics);
+                    report(&host.context, diagnostics);
                     host.remove(module.uri()).await;
                 } else {
+                    // Values of earlier expressions that took too long.
+                    for value in values.try_iter() {
+                        println!("{}", value);
+                    }
+
+                    let type_ = module.inline_result_type().await;
                     if let Err(error) = jit.evaluate(module) {
                         eprintln!("{:?}", error);
+                    } else if let Some(type_) = type_ {
+                        match values.recv_timeout(VALUE_TIMEOUT) {
+                            Ok(value) => println!("{} : {}", value, type_),
+                            Err(_) => eprintln!(
+                                "{}",
+                                output::paint(
+                                    "The expression didn't evaluate to a value yet",
+                                    |e| {
+                                        e.red();
+                                    }
+                                )
+                            ),
+                        }
                     }
                 }
             }
             Err(ReadlineError::Interrupted) => {
+                input.clear();
                 continue;
             }
             Err(ReadlineError::Eof) => {
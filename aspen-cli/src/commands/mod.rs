@@ -1,31 +1,44 @@
 use clap::{App, ArgMatches};
 
+pub mod ast;
 pub mod auth;
 pub mod build;
+pub mod clean;
 pub mod context;
+pub mod explain;
 pub mod live;
 pub mod run;
 pub mod server;
+pub mod tokens;
 
 pub fn app() -> App<'static, 'static> {
-    App::new("aspen")
-        .version(aspen::version())
+    crate::output::args(App::new("aspen").version(aspen::version()))
         .subcommand(live::app())
         .subcommand(build::app())
+        .subcommand(clean::app())
         .subcommand(context::app())
+        .subcommand(explain::app())
         .subcommand(run::app())
         .subcommand(server::app())
+        .subcommand(tokens::app())
         .subcommand(auth::app())
+        .subcommand(ast::app())
 }
 
 pub async fn main(matches: &ArgMatches<'_>) -> clap::Result<()> {
+    crate::output::configure(matches);
+
     match matches.subcommand() {
         ("live", Some(matches)) => live::main(matches).await,
         ("build", Some(matches)) => build::main(matches).await,
+        ("clean", Some(matches)) => clean::main(matches).await,
         ("context", Some(matches)) => context::main(matches).await,
+        ("explain", Some(matches)) => explain::main(matches).await,
         ("run", Some(matches)) => run::main(matches).await,
         ("server", Some(matches)) => server::main(matches).await,
+        ("tokens", Some(matches)) => tokens::main(matches).await,
         ("auth", Some(matches)) => auth::main(matches).await,
+        ("ast", Some(matches)) => ast::main(matches).await,
 
         _ => {
             app().print_help()?;
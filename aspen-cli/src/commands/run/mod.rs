@@ -1,13 +1,29 @@
+use crate::output;
 use crate::reporter::report;
 use aspen::generation::JIT;
 use aspen::semantics::Host;
-use aspen::Source;
 use clap::{App, Arg, ArgMatches};
 
+/// Sets the number of worker threads that programs run with, besides
+/// the thread that starts them. Zero runs programs on that thread only,
+/// except in live sessions, which always have at least one worker.
+const WORKERS_VAR: &str = "ASPEN_WORKERS";
+
 pub fn app() -> App<'static, 'static> {
     App::new("run")
         .about("Runs the application directly, compiling Just-In-Time (JIT)")
         .arg(Arg::with_name("MAIN").takes_value(true))
+        .arg(
+            Arg::with_name("stats")
+                .long("stats")
+                .help("Prints runtime metrics once the program has exited"),
+        )
+        .arg(
+            Arg::with_name("ARGS")
+                .help("Arguments passed on to the program")
+                .multiple(true)
+                .last(true),
+        )
 }
 
 pub async fn main(matches: &ArgMatches<'_>) -> clap::Result<()> {
@@ -17,22 +33,62 @@ pub async fn main(matches: &ArgMatches<'_>) -> clap::Result<()> {
         .map(ToString::to_string)
         .or(context.name())
         .expect("Couldn't infer main object name");
+    let args: Vec<String> = matches
+        .values_of("ARGS")
+        .map(|values| values.map(ToString::to_string).collect())
+        .unwrap_or_default();
 
-    let jit = JIT::new(context.clone());
-    let host = Host::from(context, Source::files("**/*.aspen").await).await;
+    let jit = JIT::new(context.clone()).with_workers(workers());
+    let sources = context.sources().await?;
+    let host = Host::from(context.clone(), sources).await;
 
     let diagnostics = host.diagnostics().await;
-    if !diagnostics.is_ok() {
-        report(diagnostics);
-        return Ok(());
+    let is_ok = diagnostics.is_ok();
+    report(&context, diagnostics);
+    if !is_ok {
+        std::process::exit(1);
     }
-    report(diagnostics);
 
     for module in host.modules().await {
         jit.evaluate(module).unwrap();
     }
 
-    jit.evaluate_main(host, main).unwrap();
+    jit.evaluate_main(host, main, args.as_slice()).unwrap();
+
+    if matches.is_present("stats") {
+        print_stats(aspenrt::AspenLastRuntimeStats());
+    }
 
     Ok(())
 }
+
+/// Prints the runtime metrics to stderr, so that they don't mix with
+/// the output of the program.
+fn print_stats(stats: aspenrt::RuntimeStats) {
+    eprintln!("workers:           {}", stats.workers);
+    eprintln!("actors:            {}", stats.actors);
+    eprintln!("pending messages:  {}", stats.pending_messages);
+    eprintln!("received messages: {}", stats.received_messages);
+}
+
+/// The number of workers requested with `ASPEN_WORKERS`, if any. Exits
+/// if the variable is set to something other than a number.
+pub fn workers() -> Option<usize> {
+    let value = std::env::var(WORKERS_VAR).ok()?;
+    match value.trim().parse() {
+        Ok(workers) => Some(workers),
+        Err(_) => {
+            let message = format!(
+                "{} must be a number of workers, not `{}`",
+                WORKERS_VAR, value
+            );
+            eprintln!(
+                "{}",
+                output::paint(message.as_str(), |e| {
+                    e.red();
+                })
+            );
+            std::process::exit(1);
+        }
+    }
+}
@@ -1,20 +1,32 @@
-use aspen::semantics::Host;
-use aspen::syntax::Node;
-use aspen::{Context, Location, Range, Source, URI};
+use aspen::semantics::types::{Behaviour, Type};
+use aspen::semantics::{capitalize_object_name, Host, Module};
+use aspen::syntax::{Declaration, Node};
+use aspen::{Context, Location, Range, Severity, Source, URI};
 use clap::{App, ArgMatches};
 use futures::future::{AbortHandle, Abortable};
-use log::info;
+use log::{error, info, warn};
 use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
 use lsp_types::notification::{
-    Cancel, DidChangeTextDocument, DidOpenTextDocument, PublishDiagnostics,
+    Cancel, DidChangeTextDocument, DidChangeWatchedFiles, DidCloseTextDocument,
+    DidOpenTextDocument, PublishDiagnostics,
 };
 use lsp_types::{
-    request::GotoDefinition, DidChangeTextDocumentParams, DidOpenTextDocumentParams,
-    GotoDefinitionResponse, InitializeParams, NumberOrString, PublishDiagnosticsParams,
-    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
-    Url, WorkspaceCapability, WorkspaceFolderCapability,
+    request::{
+        CodeActionRequest, Completion, GotoDefinition, RegisterCapability, SignatureHelpRequest,
+    },
+    ClientCapabilities, CodeAction, CodeActionKind, CodeActionOrCommand,
+    CodeActionProviderCapability, CompletionItem, CompletionItemKind, CompletionOptions,
+    CompletionResponse, DiagnosticSeverity, DidChangeTextDocumentParams,
+    DidChangeWatchedFilesParams, DidChangeWatchedFilesRegistrationOptions,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, FileChangeType, FileEvent,
+    FileSystemWatcher, GotoDefinitionResponse, InitializeParams, InsertTextFormat, NumberOrString,
+    PublishDiagnosticsParams, Registration, RegistrationParams, ServerCapabilities, SignatureHelp,
+    SignatureHelpOptions, SignatureInformation, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextEdit, Url, WorkspaceCapability, WorkspaceEdit,
+    WorkspaceFolderCapability,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -34,6 +46,13 @@ pub async fn main(_matches: &ArgMatches<'_>) -> clap::Result<()> {
 
     let mut capabilities = ServerCapabilities::default();
     capabilities.definition_provider = Some(true);
+    capabilities.completion_provider = Some(CompletionOptions::default());
+    capabilities.code_action_provider = Some(CodeActionProviderCapability::Simple(true));
+    capabilities.signature_help_provider = Some(SignatureHelpOptions {
+        trigger_characters: Some(vec![" ".into()]),
+        retrigger_characters: None,
+        work_done_progress_options: Default::default(),
+    });
     capabilities.text_document_sync = Some(TextDocumentSyncCapability::Options(text_document_sync));
     capabilities.workspace = Some(WorkspaceCapability {
         workspace_folders: Some(WorkspaceFolderCapability {
@@ -49,22 +68,19 @@ pub async fn main(_matches: &ArgMatches<'_>) -> clap::Result<()> {
     )
     .unwrap();
 
-    let context = match initialization_params.root_uri {
-        Some(url) if url.scheme() == "file" => {
-            Context::infer_from(url.path().into()).await.unwrap()
+    let host = match initial_host(initialization_params.root_uri).await {
+        Ok(host) => host,
+        Err(error) => {
+            error!("The language server could not start: {}", error);
+            std::process::exit(1);
         }
-        _ => Context::infer().await.unwrap(),
     };
-
-    let root_dir = context.root_dir().unwrap();
-
-    info!("Starting Aspen Language Server in {}", root_dir.display());
-
-    let host = Host::from(
-        context,
-        Source::files(format!("{}/**/*.aspen", root_dir.display())).await,
-    )
-    .await;
+    if can_watch_files(&initialization_params.capabilities) {
+        connection
+            .sender
+            .send(Message::Request(watch_sources_request(&host.context)))
+            .unwrap();
+    }
     let state = ServerState::new(host, connection.clone());
 
     for module in state.host.modules().await {
@@ -85,11 +101,78 @@ pub async fn main(_matches: &ArgMatches<'_>) -> clap::Result<()> {
     Ok(())
 }
 
+/// Whether the client lets the server register for changes to files on
+/// disk.
+fn can_watch_files(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .workspace
+        .as_ref()
+        .and_then(|workspace| workspace.did_change_watched_files.as_ref())
+        .and_then(|watched_files| watched_files.dynamic_registration)
+        .unwrap_or(false)
+}
+
+/// Asks the client to tell the server about source files that are
+/// changed on disk, rather than in the editor.
+fn watch_sources_request(context: &Context) -> Request {
+    let options = DidChangeWatchedFilesRegistrationOptions {
+        watchers: context
+            .source_extensions()
+            .iter()
+            .map(|extension| FileSystemWatcher {
+                glob_pattern: format!("**/*.{}", extension),
+                kind: None,
+            })
+            .collect(),
+    };
+
+    Request::new(
+        RequestId::from("watch-sources".to_string()),
+        <RegisterCapability as lsp_types::request::Request>::METHOD.into(),
+        RegistrationParams {
+            registrations: vec![Registration {
+                id: "watch-sources".into(),
+                method: <DidChangeWatchedFiles as lsp_types::notification::Notification>::METHOD
+                    .into(),
+                register_options: Some(serde_json::to_value(options).unwrap()),
+            }],
+        },
+    )
+}
+
+/// Loads the workspace the server was started in. If there is no
+/// usable context or root directory, the server still starts, but only
+/// knows about the documents that are opened in the editor.
+async fn initial_host(root_uri: Option<Url>) -> io::Result<Host> {
+    let context = match root_uri {
+        Some(url) if url.scheme() == "file" => Context::infer_from(url.path().into()).await,
+        _ => Context::infer().await,
+    };
+
+    match context.and_then(|c| c.root_dir().map(|d| (c, d))) {
+        Ok((context, root_dir)) => {
+            info!("Starting Aspen Language Server in {}", root_dir.display());
+
+            let sources = context.sources().await.unwrap_or_default();
+            Ok(Host::from(context, sources).await)
+        }
+        Err(error) => {
+            warn!(
+                "No workspace could be loaded ({}), only opened documents will be checked",
+                error
+            );
+
+            Ok(Host::from(Arc::new(Context::temporary(None)?), vec![]).await)
+        }
+    }
+}
+
 struct ServerState {
     host: Host,
     connection: Arc<Connection>,
     tasks: Mutex<HashMap<RequestId, AbortHandle>>,
     scheduled_check: Mutex<HashMap<URI, AbortHandle>>,
+    open_documents: Mutex<HashSet<URI>>,
 }
 
 impl ServerState {
@@ -99,6 +182,7 @@ impl ServerState {
             connection,
             tasks: Mutex::new(HashMap::new()),
             scheduled_check: Mutex::new(HashMap::new()),
+            open_documents: Mutex::new(HashSet::new()),
         })
     }
 
@@ -122,7 +206,11 @@ impl ServerState {
                         .into_iter()
                         .map(|d| lsp_types::Diagnostic {
                             range: range_to_lsp_range(d.range()),
-                            severity: None,
+                            severity: Some(match d.severity() {
+                                Severity::Error => DiagnosticSeverity::Error,
+                                Severity::Warning => DiagnosticSeverity::Warning,
+                                Severity::Info => DiagnosticSeverity::Information,
+                            }),
                             code: None,
                             source: None,
                             message: d.message().into(),
@@ -233,6 +321,96 @@ impl ServerState {
             }
         };
 
+        let req = match cast_request::<Completion>(req) {
+            Err(req) => req,
+            Ok((id, params)) => {
+                let uri = params
+                    .text_document_position
+                    .text_document
+                    .uri
+                    .as_str()
+                    .into();
+                let mut items = vec![];
+                if let Some(module) = self.host.get(&uri).await {
+                    let location = lsp_position_to_location(
+                        &module.source,
+                        params.text_document_position.position,
+                    );
+
+                    if module.receiver_before(&location).is_some() {
+                        for behaviour in module.behaviours_at(location).await {
+                            items.push(behaviour_completion_item(behaviour));
+                        }
+                    } else {
+                        for module in self.host.modules().await {
+                            for (name, _) in module.exported_declarations().await {
+                                let mut item = CompletionItem::new_simple(name, "object".into());
+                                item.kind = Some(CompletionItemKind::Class);
+                                items.push(item);
+                            }
+                        }
+                    }
+                }
+                return self
+                    .connection
+                    .sender
+                    .send(Message::Response(Response::new_ok(
+                        id,
+                        CompletionResponse::Array(items),
+                    )))
+                    .unwrap();
+            }
+        };
+
+        let req = match cast_request::<SignatureHelpRequest>(req) {
+            Err(req) => req,
+            Ok((id, params)) => {
+                let uri = params
+                    .text_document_position_params
+                    .text_document
+                    .uri
+                    .as_str()
+                    .into();
+                let mut help = None;
+                if let Some(module) = self.host.get(&uri).await {
+                    let location = lsp_position_to_location(
+                        &module.source,
+                        params.text_document_position_params.position,
+                    );
+                    help = Some(signature_help(&module, &location).await);
+                }
+                return self
+                    .connection
+                    .sender
+                    .send(Message::Response(Response::new_ok(id, help)))
+                    .unwrap();
+            }
+        };
+
+        let req = match cast_request::<CodeActionRequest>(req) {
+            Err(req) => req,
+            Ok((id, params)) => {
+                let uri = params.text_document.uri.as_str().into();
+                let mut actions = vec![];
+                if let Some(module) = self.host.get(&uri).await {
+                    let range = lsp_range_to_range(&module.source, params.range);
+                    actions =
+                        missing_declaration_actions(&module, &params.text_document.uri, range)
+                            .await;
+                    actions.extend(capitalize_object_actions(
+                        &module,
+                        &params.text_document.uri,
+                        range,
+                    ));
+                }
+                return self
+                    .connection
+                    .sender
+                    .send(Message::Response(Response::new_ok(id, actions)))
+                    .unwrap();
+            }
+        };
+
         info!("Unknown request: {:?}", req);
 
         self.connection
@@ -245,6 +423,49 @@ impl ServerState {
             .unwrap();
     }
 
+    /// Brings the host up to date with a source file that was created,
+    /// changed or deleted on disk, and checks the modules affected by it.
+    /// Documents that are open in the editor are left alone, since the
+    /// editor's version of them is the one being checked.
+    async fn reload_watched_file(&self, event: FileEvent) {
+        let path = match event.uri.to_file_path() {
+            Ok(path) => path,
+            Err(()) => return warn!("Ignoring change to non-file {}", event.uri),
+        };
+        if self.open_documents.lock().await.contains(&URI::file(&path)) {
+            return;
+        }
+        let is_source = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| self.host.context.source_extensions().iter().any(|s| s == e))
+            .unwrap_or(false);
+        if !is_source {
+            return;
+        }
+
+        let uri = match event.typ {
+            FileChangeType::Deleted => {
+                let uri = URI::file(&path);
+                self.host.remove(&uri).await;
+                uri
+            }
+            _ => match Source::file(&path).await {
+                Ok(source) => {
+                    let uri = source.uri().clone();
+                    self.host.set(source).await;
+                    uri
+                }
+                Err(error) => return warn!("Couldn't read {}: {}", path.display(), error),
+            },
+        };
+
+        for dependent in self.host.dependents(&uri).await {
+            self.schedule_check(dependent).await;
+        }
+        self.schedule_check(uri).await;
+    }
+
     async fn handle_notification(&self, not: Notification) {
         let not = match cast_notification::<Cancel>(not) {
             Err(not) => not,
@@ -278,35 +499,80 @@ impl ServerState {
                 content_changes,
             }) => {
                 let uri: URI = text_document.uri.as_str().into();
-                let module = self.host.get(&uri).await;
-                if let Some(module) = module {
-                    self.host
-                        .apply_edits(
-                            &uri,
-                            content_changes.into_iter().map(|c| {
-                                let range = c.range.map(|r| lsp_range_to_range(&module.source, r));
-
-                                (range, c.text)
-                            }),
-                        )
-                        .await;
+                let module = match self.host.get(&uri).await {
+                    Some(module) => module,
+                    None => {
+                        warn!("Ignoring changes to untracked document {}", uri);
+                        return;
+                    }
+                };
+                let dependents = self
+                    .host
+                    .apply_edits(
+                        &uri,
+                        content_changes.into_iter().map(|c| {
+                            let range = c.range.map(|r| lsp_range_to_range(&module.source, r));
+
+                            (range, c.text)
+                        }),
+                    )
+                    .await;
+                match dependents {
+                    Some(dependents) => {
+                        for dependent in dependents {
+                            self.schedule_check(dependent).await;
+                        }
+                    }
+                    None => warn!("Document {} was closed while it was being edited", uri),
                 }
                 self.schedule_check(uri).await;
                 return;
             }
         };
 
+        let not = match cast_notification::<DidChangeWatchedFiles>(not) {
+            Err(not) => not,
+            Ok(DidChangeWatchedFilesParams { changes }) => {
+                for event in changes {
+                    self.reload_watched_file(event).await;
+                }
+                return;
+            }
+        };
+
         let not = match cast_notification::<DidOpenTextDocument>(not) {
             Err(not) => not,
             Ok(DidOpenTextDocumentParams { text_document }) => {
                 let source = Source::new(text_document.uri.as_str(), text_document.text);
                 let uri = source.uri().clone();
+                self.open_documents.lock().await.insert(uri.clone());
                 self.host.set(source).await;
                 self.schedule_check(uri).await;
                 return;
             }
         };
 
+        let not = match cast_notification::<DidCloseTextDocument>(not) {
+            Err(not) => not,
+            Ok(DidCloseTextDocumentParams { text_document }) => {
+                let uri: URI = text_document.uri.as_str().into();
+                self.open_documents.lock().await.remove(&uri);
+
+                // Changes to the file on disk were ignored while it was
+                // open, so it's reloaded in case the editor didn't save.
+                if let Ok(path) = text_document.uri.to_file_path() {
+                    let typ = if path.exists() {
+                        FileChangeType::Changed
+                    } else {
+                        FileChangeType::Deleted
+                    };
+                    self.reload_watched_file(FileEvent::new(text_document.uri, typ))
+                        .await;
+                }
+                return;
+            }
+        };
+
         info!("Unknown notification: {:?}", not);
     }
 }
@@ -327,6 +593,159 @@ where
     req.extract(R::METHOD)
 }
 
+fn behaviour_completion_item(behaviour: Behaviour) -> CompletionItem {
+    let selector = behaviour.selector.to_string();
+    let mut item = CompletionItem::new_simple(selector.clone(), behaviour.reply.to_string());
+    match behaviour.selector {
+        Type::Atom(Some(_)) => {
+            item.kind = Some(CompletionItemKind::Method);
+        }
+        _ => {
+            item.kind = Some(CompletionItemKind::Value);
+            item.insert_text = Some(format!("${{1:{}}}", selector));
+            item.insert_text_format = Some(InsertTextFormat::Snippet);
+        }
+    }
+    item
+}
+
+/// The behaviours of the receiver written right before the location,
+/// as one signature per selector. There are no signatures if there is
+/// no receiver to send a message to.
+async fn signature_help(module: &Arc<Module>, location: &Location) -> SignatureHelp {
+    let behaviours = module.behaviours_at(location.clone()).await;
+
+    SignatureHelp {
+        signatures: behaviours
+            .into_iter()
+            .map(|behaviour| SignatureInformation {
+                label: behaviour.to_string(),
+                documentation: None,
+                parameters: None,
+            })
+            .collect(),
+        active_signature: None,
+        active_parameter: None,
+    }
+}
+
+/// Quick fixes that declare a stub object at the top of the file for
+/// each undefined reference within the range.
+async fn missing_declaration_actions(
+    module: &Arc<Module>,
+    uri: &Url,
+    range: Range,
+) -> Vec<CodeActionOrCommand> {
+    let mut names: Vec<String> = vec![];
+    for reference in module
+        .navigate()
+        .traverse()
+        .filter_map(|n| n.node.clone().as_reference_expression())
+    {
+        let reference_range = reference.range();
+        if !range.contains(&reference_range) && !reference_range.contains(&range) {
+            continue;
+        }
+        if module.binding_referenced_by(&reference).is_some() {
+            continue;
+        }
+        if module
+            .declaration_referenced_by(reference.clone())
+            .await
+            .is_some()
+        {
+            continue;
+        }
+        let name = reference.symbol.identifier.lexeme().to_string();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    names
+        .into_iter()
+        .map(|name| {
+            let top = lsp_types::Position {
+                line: 0,
+                character: 0,
+            };
+            let mut changes = HashMap::new();
+            changes.insert(
+                uri.clone(),
+                vec![TextEdit::new(
+                    lsp_types::Range {
+                        start: top,
+                        end: top,
+                    },
+                    format!("object {}.\n", name),
+                )],
+            );
+
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Declare object `{}`", name),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: None,
+                edit: Some(WorkspaceEdit::new(changes)),
+                command: None,
+                is_preferred: Some(true),
+            })
+        })
+        .collect()
+}
+
+/// Quick fixes that capitalize the names of objects declared within
+/// the range, along with the references to them in the same file.
+fn capitalize_object_actions(
+    module: &Arc<Module>,
+    uri: &Url,
+    range: Range,
+) -> Vec<CodeActionOrCommand> {
+    let mut actions = vec![];
+    for declaration in module.navigate().all_declarations() {
+        let object = match declaration.as_ref() {
+            Declaration::Object(o) => o,
+        };
+        let symbol_range = object.symbol.range();
+        if !range.contains(&symbol_range) && !symbol_range.contains(&range) {
+            continue;
+        }
+        let name = object.symbol();
+        let capitalized = capitalize_object_name(name);
+        if capitalized == name {
+            continue;
+        }
+
+        let mut edits = vec![TextEdit::new(
+            range_to_lsp_range(symbol_range),
+            capitalized.clone(),
+        )];
+        for reference in module
+            .navigate()
+            .traverse()
+            .filter_map(|n| n.node.clone().as_reference_expression())
+            .filter(|r| r.symbol.identifier.lexeme() == name)
+            .filter(|r| module.binding_referenced_by(r).is_none())
+        {
+            edits.push(TextEdit::new(
+                range_to_lsp_range(reference.range()),
+                capitalized.clone(),
+            ));
+        }
+
+        let mut changes = HashMap::new();
+        changes.insert(uri.clone(), edits);
+        actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+            title: format!("Rename `{}` to `{}`", name, capitalized),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: None,
+            edit: Some(WorkspaceEdit::new(changes)),
+            command: None,
+            is_preferred: Some(true),
+        }));
+    }
+    actions
+}
+
 fn range_to_lsp_range(range: Range) -> lsp_types::Range {
     lsp_types::Range {
         start: location_to_lsp_position(range.start),
@@ -351,3 +770,225 @@ fn lsp_range_to_range(source: &Arc<Source>, range: lsp_types::Range) -> Range {
 fn lsp_position_to_location(source: &Arc<Source>, position: lsp_types::Position) -> Location {
     source.location_at_coords(position.line as usize + 1, position.character as usize + 1)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::TextDocumentItem;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn signatures_for_integer_receiver() {
+        let host = Host::new(Arc::new(Context::temporary(None).unwrap()));
+        let module = host
+            .set(Source::new("test:x", "object X { 1 -> 2 3. }"))
+            .await;
+
+        let help = signature_help(&module, &module.source.location_at(18)).await;
+        let labels: Vec<_> = help.signatures.iter().map(|s| s.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            vec!["increment! -> Integer (3)", "Integer -> Integer"]
+        );
+
+        let help = signature_help(&module, &module.source.location_at(0)).await;
+        assert!(help.signatures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn code_action_declares_missing_object() {
+        let host = Host::new(Arc::new(Context::temporary(None).unwrap()));
+        let module = host
+            .set(Source::new("test:x", "object X { 1 -> Foo. }"))
+            .await;
+        let uri = Url::parse("test:x").unwrap();
+
+        let actions = missing_declaration_actions(&module, &uri, module.source.range_all()).await;
+        assert_eq!(actions.len(), 1);
+        match &actions[0] {
+            CodeActionOrCommand::CodeAction(action) => {
+                let changes = action.edit.as_ref().unwrap().changes.as_ref().unwrap();
+                let edits = &changes[&uri];
+                assert_eq!(edits.len(), 1);
+                assert_eq!(edits[0].new_text, "object Foo.\n");
+                assert_eq!(edits[0].range.start, lsp_types::Position::new(0, 0));
+            }
+            other => panic!("expected a code action, got {:?}", other),
+        }
+
+        let module = host
+            .set(Source::new("test:x", "object Foo. object X { 1 -> Foo. }"))
+            .await;
+        assert!(
+            missing_declaration_actions(&module, &uri, module.source.range_all())
+                .await
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn code_action_capitalizes_object_names() {
+        let host = Host::new(Arc::new(Context::temporary(None).unwrap()));
+        let module = host
+            .set(Source::new("test:x", "object foo. object X { 1 -> foo. }"))
+            .await;
+        let uri = Url::parse("test:x").unwrap();
+
+        let actions = capitalize_object_actions(&module, &uri, module.source.range_all());
+        assert_eq!(actions.len(), 1);
+        match &actions[0] {
+            CodeActionOrCommand::CodeAction(action) => {
+                let changes = action.edit.as_ref().unwrap().changes.as_ref().unwrap();
+                let edits = &changes[&uri];
+                assert_eq!(edits.len(), 2);
+                assert!(edits.iter().all(|e| e.new_text == "Foo"));
+            }
+            other => panic!("expected a code action, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn unrooted_server_handles_opened_documents() {
+        let host = initial_host(Some(Url::parse("file:///does/not/exist").unwrap()))
+            .await
+            .unwrap();
+        assert!(host.modules().await.is_empty());
+        assert!(host.context.sources().await.is_err());
+
+        let (server, client) = Connection::memory();
+        let state = ServerState::new(host, Arc::new(server));
+
+        state
+            .handle_notification(Notification::new(
+                <DidOpenTextDocument as lsp_types::notification::Notification>::METHOD.into(),
+                DidOpenTextDocumentParams {
+                    text_document: TextDocumentItem {
+                        uri: Url::parse("file:///x.aspen").unwrap(),
+                        language_id: "aspen".into(),
+                        version: 1,
+                        text: "object X.".into(),
+                    },
+                },
+            ))
+            .await;
+
+        assert!(state.host.get(&"file:///x.aspen".into()).await.is_some());
+
+        let receiver = client.receiver;
+        let published =
+            tokio::task::spawn_blocking(move || receiver.recv_timeout(Duration::from_secs(5)))
+                .await
+                .unwrap();
+        match published {
+            Ok(Message::Notification(n)) => assert_eq!(
+                n.method,
+                <PublishDiagnostics as lsp_types::notification::Notification>::METHOD
+            ),
+            other => panic!("expected diagnostics to be published, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn watched_file_changes_update_the_host() {
+        let dir = std::env::temp_dir().join(format!("aspen-watched-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("x.aspen");
+        std::fs::write(&path, "object X.").unwrap();
+        let path = path.canonicalize().unwrap();
+        let uri = URI::file(&path);
+
+        let host = Host::new(Arc::new(Context::temporary(None).unwrap()));
+        let (server, _client) = Connection::memory();
+        let state = ServerState::new(host, Arc::new(server));
+
+        let notify = |typ| {
+            Notification::new(
+                <DidChangeWatchedFiles as lsp_types::notification::Notification>::METHOD.into(),
+                DidChangeWatchedFilesParams {
+                    changes: vec![FileEvent::new(Url::from_file_path(&path).unwrap(), typ)],
+                },
+            )
+        };
+
+        state
+            .handle_notification(notify(FileChangeType::Created))
+            .await;
+        let module = state.host.get(&uri).await.unwrap();
+        assert_eq!(module.source.slice(0..module.source.len()), "object X.");
+
+        std::fs::write(&path, "object Y.").unwrap();
+        state
+            .handle_notification(notify(FileChangeType::Changed))
+            .await;
+        let module = state.host.get(&uri).await.unwrap();
+        assert_eq!(module.source.slice(0..module.source.len()), "object Y.");
+
+        std::fs::remove_file(&path).unwrap();
+        state
+            .handle_notification(notify(FileChangeType::Deleted))
+            .await;
+        assert!(state.host.get(&uri).await.is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn watched_file_changes_leave_open_documents_alone() {
+        let dir = std::env::temp_dir().join(format!("aspen-open-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("x.aspen");
+        std::fs::write(&path, "object X.").unwrap();
+        let path = path.canonicalize().unwrap();
+        let url = Url::from_file_path(&path).unwrap();
+        let uri = URI::file(&path);
+
+        let host = Host::new(Arc::new(Context::temporary(None).unwrap()));
+        let (server, _client) = Connection::memory();
+        let state = ServerState::new(host, Arc::new(server));
+        let code = |state: &Arc<ServerState>| {
+            let state = state.clone();
+            let uri = uri.clone();
+            async move {
+                let module = state.host.get(&uri).await.unwrap();
+                module.source.slice(0..module.source.len()).to_string()
+            }
+        };
+
+        state
+            .handle_notification(Notification::new(
+                <DidOpenTextDocument as lsp_types::notification::Notification>::METHOD.into(),
+                DidOpenTextDocumentParams {
+                    text_document: TextDocumentItem {
+                        uri: url.clone(),
+                        language_id: "aspen".into(),
+                        version: 1,
+                        text: "object Unsaved.".into(),
+                    },
+                },
+            ))
+            .await;
+
+        std::fs::write(&path, "object Y.").unwrap();
+        state
+            .handle_notification(Notification::new(
+                <DidChangeWatchedFiles as lsp_types::notification::Notification>::METHOD.into(),
+                DidChangeWatchedFilesParams {
+                    changes: vec![FileEvent::new(url.clone(), FileChangeType::Changed)],
+                },
+            ))
+            .await;
+        assert_eq!(code(&state).await, "object Unsaved.");
+
+        state
+            .handle_notification(Notification::new(
+                <DidCloseTextDocument as lsp_types::notification::Notification>::METHOD.into(),
+                DidCloseTextDocumentParams {
+                    text_document: lsp_types::TextDocumentIdentifier { uri: url },
+                },
+            ))
+            .await;
+        assert_eq!(code(&state).await, "object Y.");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+use aspen::syntax::{Lexer, TokenKind};
+use aspen::Source;
+use clap::{App, Arg, ArgMatches};
+use std::sync::Arc;
+
+const FILE: &str = "FILE";
+const SIGNIFICANT: &str = "SIGNIFICANT";
+
+pub fn app() -> App<'static, 'static> {
+    App::new("tokens")
+        .about("Prints the tokens of a file, for debugging the grammar")
+        .arg(
+            Arg::with_name(FILE)
+                .help("The file to tokenize")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name(SIGNIFICANT)
+                .long("significant")
+                .help("Hide whitespace tokens"),
+        )
+}
+
+pub async fn main(matches: &ArgMatches<'_>) -> clap::Result<()> {
+    let source = Source::file(matches.value_of(FILE).unwrap()).await?;
+
+    print!("{}", dump(&source, matches.is_present(SIGNIFICANT)));
+
+    Ok(())
+}
+
+fn dump(source: &Arc<Source>, significant: bool) -> String {
+    let mut output = String::new();
+    for token in Lexer::tokenize(source).iter() {
+        if significant && token.kind.is_trivia() {
+            continue;
+        }
+        output.push_str(
+            format!("{:?} {:?} {:?}\n", token.range, token.kind, token.lexeme()).as_str(),
+        );
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn object_declaration() {
+        let source = Source::new("test:x", "object X.");
+
+        assert_eq!(
+            dump(&source, true),
+            "1:1->1:7 ObjectKeyword \"object\"\n\
+             1:8->1:9 Identifier \"X\"\n\
+             1:9->1:10 Period \".\"\n\
+             1:10->1:10 EOF \"\"\n"
+        );
+        assert!(dump(&source, false).contains("Whitespace \" \""));
+    }
+}
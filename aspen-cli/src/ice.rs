@@ -0,0 +1,59 @@
+use aspen::generation::TargetSpec;
+use aspen::InternalCompilerError;
+use std::panic::{self, PanicInfo};
+
+const BUG_REPORT_URL: &str = "https://github.com/aspen-lang/aspen/issues";
+
+/// Replaces the default panic output with a report asking the user to
+/// file a bug. The default output, with its backtrace, is still printed
+/// when `RUST_BACKTRACE` is set.
+///
+/// Panics that are caught and reported as diagnostics are reported here
+/// too, since they're bugs all the same, but aren't printed again when
+/// they're caught.
+pub fn install() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info: &PanicInfo| {
+        let message = InternalCompilerError::describe_panic(info.payload());
+        let location = info.location().map(ToString::to_string);
+        eprintln!("{}", report(message.as_str(), location.as_deref()));
+
+        if std::env::var_os("RUST_BACKTRACE").is_some() {
+            default_hook(info);
+        }
+    }));
+}
+
+fn report(message: &str, location: Option<&str>) -> String {
+    let mut report = format!("internal compiler error: {}\n", message);
+    if let Some(location) = location {
+        report.push_str(&format!("  at {}\n", location));
+    }
+    report.push_str(&format!(
+        "\nThis is a bug in Aspen. Please report it at {}\n\
+         and include the following:\n\
+         \n  aspen {} ({})\n",
+        BUG_REPORT_URL,
+        aspen::version(),
+        TargetSpec::host().triple,
+    ));
+    if std::env::var_os("RUST_BACKTRACE").is_none() {
+        report.push_str("\nRun with RUST_BACKTRACE=1 to include a backtrace.\n");
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_includes_the_version_and_target() {
+        let report = report("oh no", Some("src/x.rs:1:2"));
+
+        assert!(report.starts_with("internal compiler error: oh no\n  at src/x.rs:1:2\n"));
+        assert!(report.contains(&format!("aspen {}", aspen::version())));
+        assert!(report.contains(&TargetSpec::host().triple));
+        assert!(report.contains(BUG_REPORT_URL));
+    }
+}
@@ -1,10 +1,13 @@
 #![feature(async_closure)]
 
 mod commands;
+mod ice;
+mod output;
 mod platform;
 mod reporter;
 
 #[tokio::main]
 async fn main() -> clap::Result<()> {
+    ice::install();
     commands::main(&commands::app().get_matches()).await
 }
@@ -0,0 +1,79 @@
+use ansi_colors::ColouredStr;
+use clap::{App, Arg, ArgMatches};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const NO_COLOR: &str = "NO_COLOR";
+const QUIET: &str = "QUIET";
+const ERROR_FORMAT: &str = "ERROR_FORMAT";
+
+static COLOUR: AtomicBool = AtomicBool::new(false);
+static QUIET_OUTPUT: AtomicBool = AtomicBool::new(false);
+static RICH_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Adds the flags that control the output of every command.
+pub fn args(app: App<'static, 'static>) -> App<'static, 'static> {
+    app.arg(
+        Arg::with_name(NO_COLOR)
+            .long("no-color")
+            .global(true)
+            .help("Don't colour the output"),
+    )
+    .arg(
+        Arg::with_name(QUIET)
+            .long("quiet")
+            .short("q")
+            .global(true)
+            .help("Only print diagnostics and errors"),
+    )
+    .arg(
+        Arg::with_name(ERROR_FORMAT)
+            .long("error-format")
+            .global(true)
+            .takes_value(true)
+            .possible_values(&["short", "rich"])
+            .help("How diagnostics are printed. `rich` shows each one under its source line"),
+    )
+}
+
+/// Applies the output flags. Colours are only used when stdout is a
+/// terminal, and never when the `NO_COLOR` environment variable is set.
+pub fn configure(matches: &ArgMatches) {
+    let colour = !matches.is_present(NO_COLOR)
+        && std::env::var_os("NO_COLOR").is_none()
+        && atty::is(atty::Stream::Stdout);
+
+    COLOUR.store(colour, Ordering::Relaxed);
+    QUIET_OUTPUT.store(matches.is_present(QUIET), Ordering::Relaxed);
+    RICH_ERRORS.store(
+        matches.value_of(ERROR_FORMAT) == Some("rich"),
+        Ordering::Relaxed,
+    );
+}
+
+pub fn colour() -> bool {
+    COLOUR.load(Ordering::Relaxed)
+}
+
+/// Whether progress and success messages should be left out.
+pub fn quiet() -> bool {
+    QUIET_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Whether diagnostics should be printed with `--error-format rich`.
+pub fn rich_errors() -> bool {
+    RICH_ERRORS.load(Ordering::Relaxed)
+}
+
+/// Styles the text if colours are enabled.
+pub fn paint<F: FnOnce(&mut ColouredStr)>(text: &str, style: F) -> String {
+    paint_if(colour(), text, style)
+}
+
+pub fn paint_if<F: FnOnce(&mut ColouredStr)>(colour: bool, text: &str, style: F) -> String {
+    if !colour {
+        return text.into();
+    }
+    let mut s = ColouredStr::new(text);
+    style(&mut s);
+    s.to_string()
+}
@@ -11,6 +11,14 @@ use uuid::Uuid;
 )]
 pub struct MeQuery;
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/platform/schema.graphql",
+    query_path = "src/platform/queries.graphql",
+    response_derives = "Debug"
+)]
+pub struct CurrentUsernameQuery;
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "src/platform/schema.graphql",
@@ -1,40 +1,57 @@
+use crate::output;
 use ansi_colors::ColouredStr;
 use aspen::syntax::{Lexer, Token, TokenKind};
-use aspen::{Diagnostic, Diagnostics};
+use aspen::{Context, Diagnostic, Diagnostics, Severity};
 use std::collections::HashMap;
+use std::fmt::Write;
 use std::sync::Arc;
 
-pub fn report(diagnostics: Diagnostics) {
+pub fn report(context: &Context, diagnostics: Diagnostics) {
+    if output::rich_errors() {
+        print!("{}", render_rich(context, diagnostics, output::colour()));
+    } else {
+        print!("{}", render(context, diagnostics, output::colour()));
+    }
+}
+
+fn render(context: &Context, diagnostics: Diagnostics, colour: bool) -> String {
+    let mut out = String::new();
     if diagnostics.is_empty() {
-        return;
+        return out;
     }
 
-    let mut heading = ColouredStr::new(" DIAGNOSIS ");
-    heading.back_light_red();
-    heading.black();
-    heading.bold();
+    let heading = output::paint_if(colour, " DIAGNOSIS ", |s| {
+        s.back_light_red();
+        s.black();
+        s.bold();
+    });
 
-    print!("{}\n\n", heading);
+    write!(out, "{} {}\n\n", heading, summary(&diagnostics)).unwrap();
 
     let mut groups: Vec<_> = diagnostics.group_by_source().into_iter().collect();
 
     groups.sort_by(|(a, _), (b, _)| a.uri().cmp(&b.uri()));
 
     for (source, diagnostics) in groups {
-        let uri = format!("{:?}", source.uri());
-        let mut uri = ColouredStr::new(uri.as_str());
-        uri.dark_gray();
-        println!("{}", uri);
+        let uri = context.display_uri(source.uri());
+        let uri = output::paint_if(colour, uri.as_str(), |s| {
+            s.dark_gray();
+        });
+        writeln!(out, "{}", uri).unwrap();
 
         let diagnostics: Vec<_> = diagnostics.into_iter().collect();
 
         let tokens = Lexer::tokenize(&source);
-        let pairs: Vec<(&Arc<Token>, bool, Vec<&Arc<dyn Diagnostic>>)> = tokens
+        let pairs: Vec<(&Arc<Token>, Option<Severity>, Vec<&Arc<dyn Diagnostic>>)> = tokens
             .iter()
             .map(|token| {
                 (
                     token,
-                    diagnostics.iter().any(|d| d.range().contains(&token.range)),
+                    diagnostics
+                        .iter()
+                        .filter(|d| d.range().contains(&token.range))
+                        .map(|d| d.severity())
+                        .max(),
                     diagnostics
                         .iter()
                         .filter(|d| d.range().start == token.range.start)
@@ -43,17 +60,19 @@ pub fn report(diagnostics: Diagnostics) {
             })
             .collect();
 
-        let mut lines: HashMap<usize, Vec<(&Arc<Token>, bool, Vec<&Arc<dyn Diagnostic>>)>> =
-            HashMap::new();
+        let mut lines: HashMap<
+            usize,
+            Vec<(&Arc<Token>, Option<Severity>, Vec<&Arc<dyn Diagnostic>>)>,
+        > = HashMap::new();
 
-        for (token, has_error, diagnostics) in pairs {
+        for (token, severity, diagnostics) in pairs {
             if !lines.contains_key(&token.range.start.line) {
                 lines.insert(token.range.start.line, vec![]);
             }
             lines
                 .get_mut(&token.range.start.line)
                 .unwrap()
-                .push((token, has_error, diagnostics))
+                .push((token, severity, diagnostics))
         }
 
         let mut lines: Vec<_> = lines.into_iter().collect();
@@ -63,48 +82,303 @@ pub fn report(diagnostics: Diagnostics) {
         let gutter_width = lines.len().to_string().len();
 
         for (line_number, tokens) in lines {
-            print!(
+            write!(
+                out,
                 "{:gutter_width$} | ",
                 line_number,
                 gutter_width = gutter_width
-            );
-            for (token, has_error, _) in tokens.iter() {
+            )
+            .unwrap();
+            for (token, severity, _) in tokens.iter() {
                 let mut lexeme = token.lexeme();
                 if lexeme == "\n" {
                     lexeme = " ";
                 }
-                let mut lexeme = ColouredStr::new(lexeme);
-
-                if *has_error {
-                    lexeme.red();
-                    lexeme.underline();
-                } else {
-                    use TokenKind::*;
-                    match token.kind {
-                        ObjectKeyword => {
-                            lexeme.blue();
+                let lexeme = output::paint_if(colour, lexeme, |lexeme| {
+                    if let Some(severity) = severity {
+                        Style::of(*severity).paint(lexeme);
+                        lexeme.underline();
+                    } else {
+                        use TokenKind::*;
+                        match token.kind {
+                            ObjectKeyword | SelfKeyword | SenderKeyword => {
+                                lexeme.blue();
+                            }
+                            _ => {}
                         }
-                        _ => {}
                     }
-                }
+                });
 
-                print!("{}", lexeme);
+                write!(out, "{}", lexeme).unwrap();
             }
-            print!("\n");
+            writeln!(out).unwrap();
             for (token, _, diagnostics) in tokens {
                 for diagnostic in diagnostics {
+                    let style = Style::of(diagnostic.severity());
                     let mut message = diagnostic.message();
-                    message.insert(0, '^');
+                    message.insert(0, style.caret);
                     message.insert(1, ' ');
-                    let mut message = ColouredStr::new(message.as_str());
-                    message.red();
-                    print!(
+                    let message = output::paint_if(colour, message.as_str(), |s| style.paint(s));
+                    write!(
+                        out,
                         "  | {}{}\n",
                         " ".repeat(token.range.start.character - 1),
                         message
                     )
+                    .unwrap();
                 }
             }
         }
     }
+    out
+}
+
+/// Renders each diagnostic under the line of source code it's found
+/// on, with carets underlining exactly its range and the message to the
+/// side of them.
+fn render_rich(context: &Context, diagnostics: Diagnostics, colour: bool) -> String {
+    let mut out = String::new();
+    if diagnostics.is_empty() {
+        return out;
+    }
+
+    let totals = summary(&diagnostics);
+    let mut diagnostics: Vec<_> = diagnostics.iter().collect();
+    diagnostics.sort_by(|a, b| {
+        a.source()
+            .uri()
+            .cmp(b.source().uri())
+            .then(a.range().start.offset.cmp(&b.range().start.offset))
+    });
+
+    for diagnostic in diagnostics {
+        let style = Style::of(diagnostic.severity());
+        let source = diagnostic.source();
+        let range = diagnostic.range();
+        let line = source.line(range.start.line).unwrap_or("");
+
+        // Ranges spanning several lines are underlined to the end of
+        // their first line.
+        let end = if range.end.line == range.start.line {
+            range.end.character
+        } else {
+            line.chars().count() + 1
+        };
+        let width = end.saturating_sub(range.start.character).max(1);
+
+        let gutter = " ".repeat(range.start.line.to_string().len());
+        let label = match diagnostic.severity() {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "note",
+        };
+
+        writeln!(
+            out,
+            "{} --> {}:{}:{}",
+            output::paint_if(colour, label, |s| {
+                style.paint(s);
+                s.bold();
+            }),
+            context.display_uri(source.uri()),
+            range.start.line,
+            range.start.character
+        )
+        .unwrap();
+        writeln!(out, "{} |", gutter).unwrap();
+        writeln!(out, "{} | {}", range.start.line, line).unwrap();
+
+        let carets = style.caret.to_string().repeat(width);
+        let underline = format!("{} {}", carets, diagnostic.message());
+        writeln!(
+            out,
+            "{} | {}{}",
+            gutter,
+            " ".repeat(range.start.character - 1),
+            output::paint_if(colour, underline.as_str(), |s| style.paint(s))
+        )
+        .unwrap();
+        writeln!(out).unwrap();
+    }
+
+    writeln!(out, "{}", totals).unwrap();
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Colour {
+    Red,
+    Yellow,
+    Blue,
+}
+
+/// How diagnostics of a particular severity are rendered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Style {
+    colour: Colour,
+    caret: char,
+}
+
+impl Style {
+    fn of(severity: Severity) -> Style {
+        match severity {
+            Severity::Error => Style {
+                colour: Colour::Red,
+                caret: '^',
+            },
+            Severity::Warning => Style {
+                colour: Colour::Yellow,
+                caret: '~',
+            },
+            Severity::Info => Style {
+                colour: Colour::Blue,
+                caret: '-',
+            },
+        }
+    }
+
+    fn paint(&self, s: &mut ColouredStr) {
+        match self.colour {
+            Colour::Red => {
+                s.red();
+            }
+            Colour::Yellow => {
+                s.yellow();
+            }
+            Colour::Blue => {
+                s.blue();
+            }
+        }
+    }
+}
+
+/// Summarizes the number of diagnostics of each severity, most
+/// severe first, like "2 errors, 1 warning".
+fn summary(diagnostics: &Diagnostics) -> String {
+    [
+        (Severity::Error, "error"),
+        (Severity::Warning, "warning"),
+        (Severity::Info, "note"),
+    ]
+    .iter()
+    .filter_map(|(severity, noun)| {
+        match diagnostics
+            .iter()
+            .filter(|d| d.severity() == *severity)
+            .count()
+        {
+            0 => None,
+            1 => Some(format!("1 {}", noun)),
+            n => Some(format!("{} {}s", n, noun)),
+        }
+    })
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aspen::{Range, Source};
+
+    #[derive(Debug)]
+    struct TestDiagnostic(Severity, Arc<Source>);
+
+    impl Diagnostic for TestDiagnostic {
+        fn severity(&self) -> Severity {
+            self.0
+        }
+
+        fn source(&self) -> &Arc<Source> {
+            &self.1
+        }
+
+        fn range(&self) -> Range {
+            self.1.range_all()
+        }
+
+        fn message(&self) -> String {
+            "Test".into()
+        }
+    }
+
+    #[test]
+    fn warnings_render_in_the_warning_style() {
+        assert_eq!(Style::of(Severity::Error).colour, Colour::Red);
+        assert_eq!(Style::of(Severity::Warning).colour, Colour::Yellow);
+        assert_ne!(
+            Style::of(Severity::Warning).caret,
+            Style::of(Severity::Error).caret
+        );
+    }
+
+    #[test]
+    fn summary_counts_each_severity() {
+        let source = Source::new("test:x", "object X.");
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.push(TestDiagnostic(Severity::Warning, source.clone()));
+        diagnostics.push(TestDiagnostic(Severity::Error, source.clone()));
+        diagnostics.push(TestDiagnostic(Severity::Error, source));
+
+        assert_eq!(summary(&diagnostics), "2 errors, 1 warning");
+    }
+
+    #[test]
+    fn rich_output_underlines_the_range_under_its_line() {
+        #[derive(Debug)]
+        struct TokenDiagnostic(Arc<Source>);
+
+        impl Diagnostic for TokenDiagnostic {
+            fn severity(&self) -> Severity {
+                Severity::Error
+            }
+
+            fn source(&self) -> &Arc<Source> {
+                &self.0
+            }
+
+            fn range(&self) -> Range {
+                Range {
+                    start: self.0.location_at(19),
+                    end: self.0.location_at(22),
+                }
+            }
+
+            fn message(&self) -> String {
+                "Undefined reference".into()
+            }
+        }
+
+        let context = Context::temporary(None).unwrap();
+        let source = Source::new("test:x", "object X {\n  a! -> Foo.\n}");
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.push(TokenDiagnostic(source));
+
+        assert_eq!(
+            render_rich(&context, diagnostics, false),
+            "error --> test:x:2:9\n  \
+             |\n\
+             2 |   a! -> Foo.\n  \
+             |         ^^^ Undefined reference\n\
+             \n\
+             1 error\n"
+        );
+    }
+
+    #[test]
+    fn no_color_output_has_no_escape_sequences() {
+        let context = Context::temporary(None).unwrap();
+        let source = Source::new("test:x", "object X.");
+        let diagnostics = || {
+            let mut diagnostics = Diagnostics::new();
+            diagnostics.push(TestDiagnostic(Severity::Error, source.clone()));
+            diagnostics
+        };
+
+        assert!(render(&context, diagnostics(), true).contains('\x1b'));
+
+        let plain = render(&context, diagnostics(), false);
+        assert!(plain.contains("DIAGNOSIS"));
+        assert!(!plain.contains('\x1b'));
+    }
 }
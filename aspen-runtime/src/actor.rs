@@ -1,4 +1,4 @@
-use crate::{ActorAddress, ActorRef, Object, ObjectRef, Runtime, WeakObjectRef};
+use crate::{ActorAddress, ActorRef, Mutex, Object, ObjectRef, Runtime, WeakObjectRef};
 use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::fmt;
@@ -25,13 +25,19 @@ pub struct Envelope {
     pub message: ObjectRef,
     pub reply_to: ObjectRef,
     pub continuation_ref: Option<ObjectRef>,
+    pub sender: Option<ObjectRef>,
 }
 
 pub type Inbox = SegQueue<Envelope>;
 
+/// The sender of the message that an actor is currently receiving,
+/// if it was sent by another actor.
+pub type SenderSlot = Mutex<Option<ObjectRef>>;
+
 pub struct Actor {
     runtime: *const Runtime,
     inbox: Pin<Box<Inbox>>,
+    sender: Pin<Box<SenderSlot>>,
     state_ptr: Pin<Vec<u8>>,
     recv_fn: RecvFn,
     drop_fn: DropFn,
@@ -45,30 +51,53 @@ impl fmt::Debug for Actor {
     }
 }
 
+/// How an actor is set up, apart from the functions it runs.
+#[derive(Debug, Clone, Copy)]
+pub struct ActorOptions {
+    pub address: ActorAddress,
+    /// With a capacity, the actor answers `busy!` to anything that is
+    /// asked of it while its inbox is full.
+    pub mailbox_capacity: Option<usize>,
+    pub state_size: usize,
+}
+
+impl ActorOptions {
+    pub fn new(address: ActorAddress, state_size: usize) -> ActorOptions {
+        ActorOptions {
+            address,
+            mailbox_capacity: None,
+            state_size,
+        }
+    }
+}
+
 impl Actor {
     pub fn new(
         runtime: &Runtime,
-        address: ActorAddress,
-        state_size: usize,
+        options: ActorOptions,
         init_msg: ObjectRef,
         init_fn: InitFn,
         recv_fn: RecvFn,
         drop_fn: DropFn,
     ) -> (ObjectRef, Actor) {
         let inbox = Box::pin(Inbox::new());
+        let sender = Box::pin(SenderSlot::new(None));
         let self_ = ObjectRef::new(Object::Actor(ActorRef::new(
             runtime,
-            address,
+            options.address,
+            options.mailbox_capacity,
             inbox.deref(),
+            sender.deref(),
         )));
         let mut actor = Actor {
             runtime,
             inbox,
-            state_ptr: Pin::new(Vec::with_capacity(state_size)),
+            sender,
+            state_ptr: Pin::new(Vec::with_capacity(options.state_size)),
             recv_fn,
             drop_fn,
             self_: self_.weak(),
-            address,
+            address: options.address,
         };
         init_fn(runtime, &actor.reference_to(), actor.state(), init_msg);
         (self_, actor)
@@ -86,13 +115,16 @@ impl Actor {
 
     pub fn receive(&mut self) -> bool {
         if let Ok(envelope) = self.inbox.pop() {
+            unsafe { &*self.runtime }.count_received();
             let Envelope {
                 self_ref,
                 message,
                 reply_to,
                 continuation_ref,
+                sender,
             } = envelope;
             let state = self.state();
+            *self.sender.lock() = sender;
             match continuation_ref.as_ref().map(|c| c.deref()) {
                 Some(Object::Continuation(cont)) => {
                     let frame = cont.frame_ptr();
@@ -103,6 +135,7 @@ impl Actor {
                     (self.recv_fn)(self.runtime, &self_ref, state, reply_to, message);
                 }
             }
+            *self.sender.lock() = None;
             true
         } else {
             false
@@ -120,3 +153,97 @@ impl Drop for Actor {
         (self.drop_fn)(self.runtime, state);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AspenMailboxDepth, AspenNewStatelessActor, AspenRuntimeStats, AspenSender};
+
+    static mut SENDER: Option<ObjectRef> = None;
+
+    extern "C" fn init(
+        _rt: *const Runtime,
+        _self: *const ObjectRef,
+        _state: *mut libc::c_void,
+        _msg: ObjectRef,
+    ) {
+    }
+
+    extern "C" fn record_sender(
+        _rt: *const Runtime,
+        self_: *const ObjectRef,
+        _state: *mut libc::c_void,
+        _reply_to: ObjectRef,
+        _msg: ObjectRef,
+    ) {
+        unsafe {
+            SENDER = Some(AspenSender(&*self_));
+        }
+    }
+
+    extern "C" fn ignore(
+        _rt: *const Runtime,
+        _self: *const ObjectRef,
+        _state: *mut libc::c_void,
+        _reply_to: ObjectRef,
+        _msg: ObjectRef,
+    ) {
+    }
+
+    extern "C" fn drop_state(_rt: *const Runtime, _state: *mut libc::c_void) {}
+
+    #[test]
+    fn method_can_read_its_sender() {
+        let rt = Runtime::new();
+        let (receiver, mut actor) = Actor::new(
+            &rt,
+            ActorOptions::new(ActorAddress(1), 0),
+            rt.noop_object.clone(),
+            init,
+            record_sender,
+            drop_state,
+        );
+        let sender = ObjectRef::new(Object::Int(7));
+
+        receiver.tell_from(Some(sender.clone()), ObjectRef::new(Object::Atom("ping!")));
+        assert!(actor.receive());
+        assert!(unsafe { SENDER.take() }.unwrap().is_identical(&sender));
+
+        receiver.tell(ObjectRef::new(Object::Atom("ping!")));
+        assert!(actor.receive());
+        assert_eq!(*unsafe { SENDER.take() }.unwrap(), Object::Noop);
+    }
+
+    #[test]
+    fn stats_count_enqueued_messages_until_received() {
+        let rt = Runtime::new();
+        let (receiver, mut actor) = Actor::new(
+            &rt,
+            ActorOptions::new(ActorAddress(1), 0),
+            rt.noop_object.clone(),
+            init,
+            ignore,
+            drop_state,
+        );
+        let other = AspenNewStatelessActor(&rt, ignore);
+
+        for _ in 0..3 {
+            receiver.tell(ObjectRef::new(Object::Atom("ping!")));
+        }
+        other.tell(ObjectRef::new(Object::Atom("ping!")));
+
+        let stats = AspenRuntimeStats(&rt);
+        assert_eq!(stats.pending_messages, 4);
+        assert_eq!(stats.received_messages, 0);
+        assert_eq!(AspenMailboxDepth(&receiver), 3);
+        assert_eq!(AspenMailboxDepth(&other), 1);
+        assert_eq!(AspenMailboxDepth(&rt.noop_object), 0);
+
+        assert!(actor.receive());
+
+        let stats = AspenRuntimeStats(&rt);
+        assert_eq!(stats.pending_messages, 3);
+        assert_eq!(stats.received_messages, 1);
+        assert_eq!(AspenMailboxDepth(&receiver), 2);
+    }
+}
@@ -0,0 +1,50 @@
+//! The command line arguments of the running program.
+//!
+//! The entry point stores `argc` and `argv` as it received them, so the
+//! arguments are borrowed for the lifetime of the process. The first
+//! element of `argv` is the program name, which is not counted as an
+//! argument. Arguments that aren't valid UTF-8 are copied once, with
+//! the invalid sequences replaced.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+static ARGS: AtomicPtr<&'static str> = AtomicPtr::new(ptr::null_mut());
+
+/// # Safety
+///
+/// `argv` must point to at least `argc` pointers to NUL-terminated
+/// strings, all of which must outlive the program.
+pub unsafe fn set(argc: libc::c_int, argv: *const *const libc::c_char) {
+    let args: Vec<&'static str> = (1..argc.max(0) as usize)
+        .map(|i| {
+            let arg = *argv.add(i);
+            let bytes = core::slice::from_raw_parts(arg as *const u8, libc::strlen(arg));
+            match core::str::from_utf8(bytes) {
+                Ok(arg) => arg,
+                Err(_) => Box::leak(String::from_utf8_lossy(bytes).into_owned().into_boxed_str()),
+            }
+        })
+        .collect();
+
+    // The arguments are kept for the rest of the program, like `argv`.
+    let args = Box::leak(args.into_boxed_slice());
+    COUNT.store(0, Ordering::SeqCst);
+    ARGS.store(args.as_mut_ptr(), Ordering::SeqCst);
+    COUNT.store(args.len(), Ordering::SeqCst);
+}
+
+pub fn count() -> usize {
+    COUNT.load(Ordering::SeqCst)
+}
+
+pub fn get(index: usize) -> Option<&'static str> {
+    if index >= count() {
+        return None;
+    }
+    unsafe { Some(*ARGS.load(Ordering::SeqCst).add(index)) }
+}
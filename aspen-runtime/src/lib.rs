@@ -40,6 +40,8 @@ use self::object::*;
 
 mod cpus;
 
+mod args;
+
 mod mutex;
 use self::mutex::*;
 
@@ -50,6 +52,7 @@ mod semaphore;
 use self::semaphore::*;
 
 mod object_ref;
+pub use self::object_ref::ObjectRef;
 use self::object_ref::*;
 
 mod runtime;
@@ -67,35 +70,138 @@ use self::actor::*;
 mod continuation;
 use self::continuation::*;
 
+mod timer;
+use self::timer::*;
+
+mod promise;
+use self::promise::*;
+
+mod stats;
+pub use self::stats::RuntimeStats;
+
 use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::ops::Deref;
 use core::pin::Pin;
 
+/// The number of workers spawned unless another number is requested,
+/// leaving one CPU to the thread that started the runtime.
+fn default_workers() -> usize {
+    cpus::count().saturating_sub(1)
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn AspenNewRuntime() -> *mut Runtime {
-    let mut rt = Runtime::new();
-    for _ in 1..cpus::count() {
-        rt.spawn_worker();
-    }
-    Box::into_raw(rt)
+    AspenNewRuntimeWithWorkers(default_workers())
+}
+
+/// Creates a runtime with exactly `workers` worker threads. Without any
+/// workers, messages are only handled by threads that attach to the
+/// runtime themselves.
+///
+/// # Safety
+///
+/// The runtime must be freed with `AspenExit`, and must not be used
+/// after that.
+#[no_mangle]
+pub unsafe extern "C" fn AspenNewRuntimeWithWorkers(workers: usize) -> *mut Runtime {
+    Box::into_raw(Runtime::with_workers(workers))
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn AspenStartRuntime(f: extern "C" fn(*const Runtime)) {
-    let mut rt = Runtime::new();
-    for _ in 1..cpus::count() {
-        rt.spawn_worker();
-    }
+    AspenStartRuntimeWithWorkers(f, default_workers())
+}
+
+/// Starts a runtime with `workers` worker threads besides the calling
+/// thread, which works for the runtime until it exits. With zero
+/// workers, the program runs on the calling thread only.
+///
+/// # Safety
+///
+/// `f` must not keep the runtime it's passed, which is freed once it
+/// has stopped.
+#[no_mangle]
+pub unsafe extern "C" fn AspenStartRuntimeWithWorkers(
+    f: extern "C" fn(*const Runtime),
+    workers: usize,
+) {
+    let mut rt = Runtime::with_workers(workers);
     f(rt.deref());
     rt.attach_current_thread_as_worker();
 }
 
+/// Stores the arguments the program was started with. Entry points
+/// call this with the `argc` and `argv` of `main` before starting the
+/// runtime.
+///
+/// # Safety
+///
+/// `argv` must point to at least `argc` pointers to NUL-terminated
+/// strings, all of which must outlive the program.
+#[no_mangle]
+pub unsafe extern "C" fn AspenSetArgs(argc: libc::c_int, argv: *const *const libc::c_char) {
+    args::set(argc, argv);
+}
+
+/// The number of arguments the program was started with, not counting
+/// the program name.
+#[no_mangle]
+pub extern "C" fn AspenArgCount() -> usize {
+    args::count()
+}
+
+/// The argument at `index` as an atom, or a noop object if there is no
+/// such argument.
+#[no_mangle]
+pub extern "C" fn AspenArg(index: usize) -> ObjectRef {
+    match args::get(index) {
+        Some(arg) => ObjectRef::new(Object::Atom(arg)),
+        None => ObjectRef::new(Object::Noop),
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn AspenExit(rt: *const Runtime) {
     Box::from_raw(rt as *mut Runtime);
 }
 
+/// Stops and frees a runtime that may still have actors, like that of a
+/// live session. Messages that haven't been handled are dropped.
+///
+/// # Safety
+///
+/// The runtime must not be used after this.
+#[no_mangle]
+pub unsafe extern "C" fn AspenStopRuntime(rt: *const Runtime) {
+    (*rt).stop();
+    AspenExit(rt);
+}
+
+/// A snapshot of the runtime's counters, like the number of messages
+/// waiting to be received.
+#[no_mangle]
+pub extern "C" fn AspenRuntimeStats(rt: &Runtime) -> RuntimeStats {
+    rt.stats()
+}
+
+/// The stats of the runtime that was most recently shut down, as they
+/// were when it exited. All zeroes if no runtime has exited yet.
+#[no_mangle]
+pub extern "C" fn AspenLastRuntimeStats() -> RuntimeStats {
+    stats::last()
+}
+
+/// The number of messages waiting in the inbox of an actor. Other
+/// objects have no inbox, so their depth is always zero.
+#[no_mangle]
+pub extern "C" fn AspenMailboxDepth(object: &ObjectRef) -> usize {
+    match object.deref() {
+        Object::Actor(a) => a.mailbox_depth(),
+        _ => 0,
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn AspenNewActor(
     rt: &Runtime,
@@ -108,6 +214,30 @@ pub extern "C" fn AspenNewActor(
     rt.spawn(state_size, init_msg, init_fn, recv_fn, drop_fn)
 }
 
+/// Spawns an actor whose mailbox holds at most `mailbox_capacity`
+/// messages. Asking it anything while its mailbox is full replies
+/// `busy!` right away instead, so that callers can shed load. Telling
+/// it messages is never refused.
+#[no_mangle]
+pub extern "C" fn AspenNewBoundedActor(
+    rt: &Runtime,
+    mailbox_capacity: usize,
+    state_size: usize,
+    init_msg: ObjectRef,
+    init_fn: InitFn,
+    recv_fn: RecvFn,
+    drop_fn: DropFn,
+) -> ObjectRef {
+    rt.spawn_bounded(
+        Some(mailbox_capacity),
+        state_size,
+        init_msg,
+        init_fn,
+        recv_fn,
+        drop_fn,
+    )
+}
+
 #[no_mangle]
 pub extern "C" fn AspenNewStatelessActor(rt: &Runtime, recv_fn: RecvFn) -> ObjectRef {
     AspenNewActor(rt, 0, rt.noop_object.clone(), noop_init, recv_fn, noop_drop)
@@ -153,6 +283,76 @@ pub extern "C" fn AspenAsk(receiver: &ObjectRef, reply_to: ObjectRef, message: O
     receiver.ask(reply_to, message);
 }
 
+/// Asks the message, telling `reply_to` the atom `timeout!` instead if
+/// there is no reply within `millis` milliseconds. See
+/// `Runtime::ask_with_timeout` for how a late reply is handled.
+#[no_mangle]
+pub extern "C" fn AspenAskTimeout(
+    rt: &Runtime,
+    receiver: &ObjectRef,
+    reply_to: ObjectRef,
+    message: ObjectRef,
+    millis: u64,
+) {
+    rt.ask_with_timeout(receiver, reply_to, message, millis);
+}
+
+/// Tells the message on behalf of `sender`, which the receiving
+/// actor can read with `AspenSender`.
+#[no_mangle]
+pub extern "C" fn AspenTellFrom(receiver: &ObjectRef, sender: &ObjectRef, message: ObjectRef) {
+    receiver.tell_from(Some(sender.clone()), message);
+}
+
+#[no_mangle]
+pub extern "C" fn AspenAskFrom(
+    receiver: &ObjectRef,
+    sender: &ObjectRef,
+    reply_to: ObjectRef,
+    message: ObjectRef,
+) {
+    receiver.ask_from(Some(sender.clone()), reply_to, message);
+}
+
+/// The sender of the message that the actor `self_` is receiving. If
+/// the message is anonymous, e.g. because it was sent from outside of
+/// any actor, this is a noop object.
+#[no_mangle]
+pub extern "C" fn AspenSender(self_: &ObjectRef) -> ObjectRef {
+    match self_.deref() {
+        Object::Actor(a) => a.sender(),
+        _ => None,
+    }
+    .unwrap_or_else(|| ObjectRef::new(Object::Noop))
+}
+
+#[no_mangle]
+pub extern "C" fn AspenNewPromise() -> ObjectRef {
+    ObjectRef::new(Object::Promise(Promise::new()))
+}
+
+/// Fulfils the promise with `value`, telling it to every subscriber.
+/// Fulfilling a promise that is already fulfilled has no effect.
+#[no_mangle]
+pub extern "C" fn AspenFulfill(promise: &ObjectRef, value: ObjectRef) {
+    match promise.deref() {
+        Object::Promise(p) => {
+            p.fulfill(value);
+        }
+        _ => panic!("Expected a promise, got {}", promise),
+    }
+}
+
+/// Tells `subscriber` the value of the promise once it is fulfilled,
+/// or right away if it already is.
+#[no_mangle]
+pub extern "C" fn AspenOnFulfill(promise: &ObjectRef, subscriber: ObjectRef) {
+    match promise.deref() {
+        Object::Promise(p) => p.on_fulfill(subscriber),
+        _ => panic!("Expected a promise, got {}", promise),
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn AspenNewInt(value: i128) -> ObjectRef {
     ObjectRef::new(Object::Int(value))
@@ -191,6 +391,39 @@ pub extern "C" fn AspenEqInt(value: i128) -> *mut Matcher {
     Box::into_raw(Box::new(Matcher::Equal(Object::Int(value))))
 }
 
+/// Whether `a` and `b` are the same object. Actors and continuations
+/// are compared by reference, while primitives are compared by value.
+#[no_mangle]
+pub extern "C" fn AspenIdentical(a: &ObjectRef, b: &ObjectRef) -> bool {
+    a.is_identical(b)
+}
+
+#[no_mangle]
+pub extern "C" fn AspenIdenticalTo(object: &ObjectRef) -> *mut Matcher {
+    Box::into_raw(Box::new(Matcher::Identical(object.clone())))
+}
+
+/// Combines two matchers, taking ownership of both, into one that
+/// matches whatever either of them matches.
+#[no_mangle]
+pub unsafe extern "C" fn AspenAnyOf(a: *mut Matcher, b: *mut Matcher) -> *mut Matcher {
+    let a = *Box::from_raw(a);
+    let b = *Box::from_raw(b);
+    let matchers = match a {
+        Matcher::Any(mut matchers) => {
+            matchers.push(b);
+            matchers
+        }
+        a => {
+            let mut matchers = Vec::with_capacity(2);
+            matchers.push(a);
+            matchers.push(b);
+            matchers
+        }
+    };
+    Box::into_raw(Box::new(Matcher::Any(matchers)))
+}
+
 #[no_mangle]
 pub extern "C" fn AspenMatch(matcher: &Matcher, subject: &ObjectRef) -> bool {
     subject.matches(matcher)
@@ -200,3 +433,164 @@ pub extern "C" fn AspenMatch(matcher: &Matcher, subject: &ObjectRef) -> bool {
 pub unsafe extern "C" fn AspenDropMatcher(matcher: *mut Matcher) {
     Box::from_raw(matcher);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn args_reach_the_runtime() {
+        let args: Vec<_> = ["main", "arg1", "arg2"]
+            .iter()
+            .map(|a| CString::new(*a).unwrap())
+            .collect();
+        let argv: Vec<_> = args.iter().map(|a| a.as_ptr()).collect();
+
+        unsafe {
+            AspenSetArgs(argv.len() as libc::c_int, argv.as_ptr());
+        }
+
+        assert_eq!(AspenArgCount(), 2);
+        assert_eq!(*AspenArg(0), Object::Atom("arg1"));
+        assert_eq!(*AspenArg(1), Object::Atom("arg2"));
+        assert_eq!(*AspenArg(2), Object::Noop);
+
+        // The arguments are global, so arguments that aren't valid
+        // UTF-8 are tested here rather than in a test of their own.
+        let args: Vec<_> = [&b"main"[..], &b"caf\xe9"[..]]
+            .iter()
+            .map(|a| CString::new(*a).unwrap())
+            .collect();
+        let argv: Vec<_> = args.iter().map(|a| a.as_ptr()).collect();
+
+        unsafe {
+            AspenSetArgs(argv.len() as libc::c_int, argv.as_ptr());
+        }
+
+        assert_eq!(*AspenArg(0), Object::Atom("caf\u{FFFD}"));
+    }
+
+    fn wait_for_value(promise: &ObjectRef) -> ObjectRef {
+        for _ in 0..1000 {
+            if let Object::Promise(p) = promise.deref() {
+                if let Some(value) = p.value() {
+                    return value;
+                }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        panic!("{} was never fulfilled", promise);
+    }
+
+    #[test]
+    fn requested_number_of_workers_are_spawned() {
+        for workers in &[0, 1, 3] {
+            unsafe {
+                let rt = AspenNewRuntimeWithWorkers(*workers);
+                assert_eq!((*rt).worker_count(), *workers);
+                AspenStopRuntime(rt);
+            }
+        }
+    }
+
+    #[test]
+    fn stopped_runtime_joins_its_workers() {
+        unsafe {
+            let rt = AspenNewRuntimeWithWorkers(3);
+            let actor = (*rt).spawn(0, (*rt).noop_object.clone(), noop_init, ignore, noop_drop);
+            drop(actor);
+            AspenStopRuntime(rt);
+        }
+    }
+
+    extern "C" fn ignore(
+        _rt: *const Runtime,
+        _self: *const ObjectRef,
+        _state: *mut libc::c_void,
+        _reply_to: ObjectRef,
+        _msg: ObjectRef,
+    ) {
+    }
+
+    #[test]
+    fn asking_a_full_mailbox_replies_busy() {
+        // Without workers, nothing empties the mailbox.
+        let rt = Runtime::new();
+        let actor = AspenNewBoundedActor(
+            &rt,
+            2,
+            0,
+            rt.noop_object.clone(),
+            noop_init,
+            ignore,
+            noop_drop,
+        );
+
+        let replies: Vec<_> = (0..3).map(|_| AspenNewPromise()).collect();
+        for reply_to in replies.iter() {
+            AspenAsk(&actor, reply_to.clone(), AspenNewInt(1));
+        }
+
+        assert_eq!(AspenMailboxDepth(&actor), 2);
+        assert_eq!(*wait_for_value(&replies[2]), Object::Atom("busy!"));
+        for reply_to in replies[..2].iter() {
+            match reply_to.deref() {
+                Object::Promise(p) => assert!(p.value().is_none()),
+                o => panic!("expected a promise, got {}", o),
+            }
+        }
+
+        AspenTell(&actor, AspenNewInt(1));
+        assert_eq!(AspenMailboxDepth(&actor), 3);
+    }
+
+    #[test]
+    fn ask_without_reply_times_out() {
+        let rt = Runtime::new();
+        let reply_to = AspenNewPromise();
+
+        AspenAskTimeout(
+            &rt,
+            &rt.noop_object,
+            reply_to.clone(),
+            ObjectRef::new(Object::Atom("never?")),
+            10,
+        );
+
+        assert_eq!(*wait_for_value(&reply_to), Object::Atom("timeout!"));
+    }
+
+    #[test]
+    fn reply_before_timeout_wins() {
+        let rt = Runtime::new();
+        let reply_to = AspenNewPromise();
+
+        AspenAskTimeout(&rt, &AspenNewInt(6), reply_to.clone(), AspenNewInt(7), 10);
+        std::thread::sleep(std::time::Duration::from_millis(30));
+
+        assert_eq!(*wait_for_value(&reply_to), Object::Int(42));
+    }
+
+    #[test]
+    fn any_of_matches_each_alternative() {
+        let matcher =
+            unsafe { AspenAnyOf(AspenAnyOf(AspenEqInt(1), AspenEqInt(2)), AspenEqInt(3)) };
+
+        for (value, expected) in &[(1, true), (2, true), (3, true), (4, false)] {
+            assert_eq!(
+                AspenMatch(unsafe { &*matcher }, &AspenNewInt(*value)),
+                *expected,
+                "{}",
+                value
+            );
+        }
+
+        match unsafe { &*matcher } {
+            Matcher::Any(matchers) => assert_eq!(matchers.len(), 3),
+            m => panic!("expected a flat matcher, got {:?}", m),
+        }
+
+        unsafe { AspenDropMatcher(matcher) };
+    }
+}
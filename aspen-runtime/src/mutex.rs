@@ -48,6 +48,55 @@ impl<T> Drop for Mutex<T> {
     }
 }
 
+/// A condition variable to wait on while holding the guard of a
+/// `Mutex`, which is released while waiting.
+pub struct Condvar {
+    cond: UnsafeCell<libc::pthread_cond_t>,
+}
+
+impl Condvar {
+    pub fn new() -> Condvar {
+        Condvar {
+            cond: UnsafeCell::new(libc::PTHREAD_COND_INITIALIZER),
+        }
+    }
+
+    /// Waits until notified. Wakeups may be spurious.
+    pub fn wait<T>(&self, guard: &mut Guard<T>) {
+        unsafe {
+            libc::pthread_cond_wait(self.cond.get(), (&*guard.mutex).lock.get());
+        }
+    }
+
+    /// Waits until notified, or until `millis` milliseconds have
+    /// passed. Wakeups may be spurious.
+    pub fn wait_timeout<T>(&self, guard: &mut Guard<T>, millis: u64) {
+        unsafe {
+            let mut deadline: libc::timespec = core::mem::zeroed();
+            libc::clock_gettime(libc::CLOCK_REALTIME, &mut deadline);
+            let nanos = deadline.tv_nsec as u64 + (millis % 1000) * 1_000_000;
+            deadline.tv_sec += (millis / 1000 + nanos / 1_000_000_000) as libc::time_t;
+            deadline.tv_nsec = (nanos % 1_000_000_000) as _;
+
+            libc::pthread_cond_timedwait(self.cond.get(), (&*guard.mutex).lock.get(), &deadline);
+        }
+    }
+
+    pub fn notify_all(&self) {
+        unsafe {
+            libc::pthread_cond_broadcast(self.cond.get());
+        }
+    }
+}
+
+impl Drop for Condvar {
+    fn drop(&mut self) {
+        unsafe {
+            libc::pthread_cond_destroy(self.cond.get());
+        }
+    }
+}
+
 pub struct Guard<T> {
     mutex: *const Mutex<T>,
 }
@@ -1,5 +1,7 @@
-use crate::{ActorRef, Continuation};
+use crate::{ActorRef, Continuation, ObjectRef, Promise};
+use alloc::vec::Vec;
 use core::fmt;
+use core::ops::Deref;
 
 #[derive(Debug, PartialEq)]
 pub enum Object {
@@ -9,12 +11,41 @@ pub enum Object {
     Atom(&'static str),
     Actor(ActorRef),
     Continuation(Continuation),
+    Promise(Promise),
 }
 
 impl Object {
     pub fn matches(&self, matcher: &Matcher) -> bool {
         matcher.matches(self)
     }
+
+    /// Whether this is the very same object as `other`.
+    ///
+    /// Unlike `==`, which compares objects structurally, actors,
+    /// continuations and promises are only identical to themselves: two distinct
+    /// objects with equal contents are equal, but not identical. Since
+    /// primitives have no identity of their own, they are identical
+    /// whenever their values are equal.
+    pub fn is_identical(&self, other: &Object) -> bool {
+        match (self, other) {
+            (Object::Actor(_), _)
+            | (_, Object::Actor(_))
+            | (Object::Continuation(_), _)
+            | (_, Object::Continuation(_))
+            | (Object::Promise(_), _)
+            | (_, Object::Promise(_)) => core::ptr::eq(self, other),
+            _ => self == other,
+        }
+    }
+
+    /// Whether this is a query atom, like `value?`, which expects a
+    /// reply, as opposed to a command atom like `run!`.
+    pub fn is_query(&self) -> bool {
+        match self {
+            Object::Atom(a) => a.ends_with('?'),
+            _ => false,
+        }
+    }
 }
 
 impl fmt::Display for Object {
@@ -26,19 +57,90 @@ impl fmt::Display for Object {
             Object::Atom(v) => write!(f, "{}", v),
             Object::Actor(v) => write!(f, "{}", v),
             Object::Continuation(v) => write!(f, "{}", v),
+            Object::Promise(v) => write!(f, "{}", v),
         }
     }
 }
 
 #[derive(Debug)]
 pub enum Matcher {
+    /// Matches objects that are structurally equal to this one.
     Equal(Object),
+    /// Matches only the referenced object itself (see
+    /// `Object::is_identical`).
+    Identical(ObjectRef),
+    /// Matches objects that any of the matchers match.
+    Any(Vec<Matcher>),
 }
 
 impl Matcher {
     pub fn matches(&self, object: &Object) -> bool {
         match self {
             Matcher::Equal(o) => o == object,
+            Matcher::Identical(o) => o.deref().is_identical(object),
+            Matcher::Any(matchers) => matchers.iter().any(|m| m.matches(object)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Runtime;
+
+    extern "C" fn init(
+        _rt: *const Runtime,
+        _self: *const ObjectRef,
+        _state: *mut libc::c_void,
+        _msg: ObjectRef,
+    ) {
+    }
+
+    extern "C" fn recv(
+        _rt: *const Runtime,
+        _self: *const ObjectRef,
+        _state: *mut libc::c_void,
+        _reply_to: ObjectRef,
+        _msg: ObjectRef,
+    ) {
+    }
+
+    extern "C" fn drop_state(_rt: *const Runtime, _state: *mut libc::c_void) {}
+
+    fn spawn(rt: &Runtime) -> ObjectRef {
+        rt.spawn(0, rt.noop_object.clone(), init, recv, drop_state)
+    }
+
+    #[test]
+    fn actor_is_identical_to_itself_and_its_clones() {
+        let rt = Runtime::new();
+        let actor = spawn(&rt);
+        let clone = actor.clone();
+        let other = spawn(&rt);
+
+        assert!(actor.is_identical(&actor));
+        assert!(actor.is_identical(&clone));
+        assert!(!actor.is_identical(&other));
+
+        assert!(Matcher::Identical(clone.clone()).matches(&actor));
+        assert!(!Matcher::Identical(other.clone()).matches(&actor));
+    }
+
+    #[test]
+    fn primitives_are_identical_by_value() {
+        let a = ObjectRef::new(Object::Int(42));
+        let b = ObjectRef::new(Object::Int(42));
+        let c = ObjectRef::new(Object::Atom("x"));
+
+        assert!(a.is_identical(&b));
+        assert!(!a.is_identical(&c));
+        assert!(Matcher::Identical(a.clone()).matches(&b));
+    }
+
+    #[test]
+    fn queries_are_distinguished_from_commands() {
+        assert!(Object::Atom("value?").is_query());
+        assert!(!Object::Atom("run!").is_query());
+        assert!(!Object::Int(1).is_query());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{ActorAddress, Envelope, Inbox, Object, Runtime};
+use crate::{ActorAddress, Envelope, Inbox, Object, Runtime, SenderSlot};
 use alloc::boxed::Box;
 use core::fmt;
 use core::ops::Deref;
@@ -25,6 +25,12 @@ impl ObjectRef {
     }
 
     pub fn tell(&self, message: ObjectRef) {
+        self.tell_from(None, message)
+    }
+
+    /// Tells the message, letting the receiving actor know who sent
+    /// it. Messages without a sender are anonymous.
+    pub fn tell_from(&self, sender: Option<ObjectRef>, message: ObjectRef) {
         match self.deref() {
             Object::Noop => {
                 #[cfg(debug_assertions)]
@@ -40,7 +46,7 @@ impl ObjectRef {
                 println!("Handle builtin tell {} -> {}", message, a);
             }
             Object::Actor(a) => {
-                a.enqueue(self.clone(), a.address, None, message, None);
+                a.enqueue(self.clone(), a.address, None, message, None, sender);
             }
             Object::Continuation(continuation) => {
                 if let Object::Actor(a) = continuation.actor.deref() {
@@ -50,13 +56,22 @@ impl ObjectRef {
                         None,
                         message,
                         Some(self.clone()),
+                        sender,
                     );
                 }
             }
+            Object::Promise(promise) => {
+                promise.fulfill(message);
+            }
         }
     }
 
     pub fn ask(&self, reply_to: ObjectRef, message: ObjectRef) {
+        self.ask_from(None, reply_to, message)
+    }
+
+    /// Asks the message, letting the receiving actor know who sent it.
+    pub fn ask_from(&self, sender: Option<ObjectRef>, reply_to: ObjectRef, message: ObjectRef) {
         match self.deref() {
             Object::Noop => {
                 #[cfg(debug_assertions)]
@@ -77,7 +92,14 @@ impl ObjectRef {
                 println!("Handle builtin ask {} -> {}", message, a);
             }
             Object::Actor(a) => {
-                a.enqueue(self.clone(), a.address, Some(reply_to), message, None);
+                a.enqueue(
+                    self.clone(),
+                    a.address,
+                    Some(reply_to),
+                    message,
+                    None,
+                    sender,
+                );
             }
             Object::Continuation(continuation) => {
                 if let Object::Actor(a) = continuation.actor.deref() {
@@ -87,11 +109,15 @@ impl ObjectRef {
                         Some(reply_to),
                         message,
                         Some(self.clone()),
+                        sender,
                     );
                 } else {
                     panic!("Expected an actor, got {}", continuation.actor);
                 }
             }
+            Object::Promise(p) => {
+                println!("Handle builtin ask {} -> {}", message, p);
+            }
         }
     }
 }
@@ -181,16 +207,43 @@ impl WeakObjectRef {
 pub struct ActorRef {
     runtime: *const Runtime,
     address: ActorAddress,
+    mailbox_capacity: Option<usize>,
     inbox: *const Inbox,
+    sender: *const SenderSlot,
 }
 
 impl ActorRef {
     #[inline]
-    pub fn new(runtime: *const Runtime, address: ActorAddress, inbox: *const Inbox) -> ActorRef {
+    pub fn new(
+        runtime: *const Runtime,
+        address: ActorAddress,
+        mailbox_capacity: Option<usize>,
+        inbox: *const Inbox,
+        sender: *const SenderSlot,
+    ) -> ActorRef {
         ActorRef {
             runtime,
             address,
+            mailbox_capacity,
             inbox,
+            sender,
+        }
+    }
+
+    /// The sender of the message the actor is currently receiving.
+    pub fn sender(&self) -> Option<ObjectRef> {
+        (*unsafe { &*self.sender }.lock()).clone()
+    }
+
+    /// The number of messages waiting in the actor's inbox.
+    pub fn mailbox_depth(&self) -> usize {
+        unsafe { &*self.inbox }.len()
+    }
+
+    fn is_full(&self) -> bool {
+        match self.mailbox_capacity {
+            Some(capacity) => self.mailbox_depth() >= capacity,
+            None => false,
         }
     }
 
@@ -201,12 +254,25 @@ impl ActorRef {
         reply_to: Option<ObjectRef>,
         message: ObjectRef,
         continuation_ref: Option<ObjectRef>,
+        sender: Option<ObjectRef>,
     ) {
+        // Replies to the actor's own questions must always get through,
+        // so only new asks are turned away. The check isn't atomic with
+        // the push, so concurrent asks may overshoot the capacity a bit.
+        if let (Some(reply_to), None) = (&reply_to, &continuation_ref) {
+            if self.is_full() {
+                reply_to.tell(ObjectRef::new(Object::Atom("busy!")));
+                return;
+            }
+        }
+
+        unsafe { &*self.runtime }.count_enqueued();
         unsafe { &*self.inbox }.push(Envelope {
             self_ref,
             reply_to: reply_to.unwrap_or_else(|| unsafe { &*self.runtime }.noop_object.clone()),
             message,
             continuation_ref,
+            sender,
         });
         unsafe { &*self.runtime }.notify();
     }
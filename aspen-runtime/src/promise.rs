@@ -0,0 +1,162 @@
+use crate::{Mutex, ObjectRef};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A value that becomes available later, which any number of
+/// subscribers can wait for.
+///
+/// A promise is fulfilled at most once. When it is, the value is told
+/// to every subscriber, and objects subscribing afterwards are told the
+/// value right away. Fulfilling a promise a second time has no effect,
+/// which is what happens to whichever of a reply and its timeout comes
+/// last.
+pub struct Promise {
+    state: Mutex<PromiseState>,
+}
+
+enum PromiseState {
+    Pending(Vec<ObjectRef>),
+    Fulfilled(ObjectRef),
+}
+
+impl Promise {
+    pub fn new() -> Promise {
+        Promise {
+            state: Mutex::new(PromiseState::Pending(Vec::new())),
+        }
+    }
+
+    /// Fulfils the promise, returning whether this was the first time.
+    pub fn fulfill(&self, value: ObjectRef) -> bool {
+        let subscribers = {
+            let mut state = self.state.lock();
+            match &mut *state {
+                PromiseState::Fulfilled(_) => return false,
+                PromiseState::Pending(subscribers) => {
+                    let subscribers = core::mem::take(subscribers);
+                    *state = PromiseState::Fulfilled(value.clone());
+                    subscribers
+                }
+            }
+        };
+
+        for subscriber in subscribers {
+            subscriber.tell(value.clone());
+        }
+        true
+    }
+
+    pub fn on_fulfill(&self, subscriber: ObjectRef) {
+        let value = {
+            let mut state = self.state.lock();
+            match &mut *state {
+                PromiseState::Pending(subscribers) => {
+                    subscribers.push(subscriber);
+                    return;
+                }
+                PromiseState::Fulfilled(value) => value.clone(),
+            }
+        };
+
+        subscriber.tell(value);
+    }
+
+    pub fn value(&self) -> Option<ObjectRef> {
+        match &*self.state.lock() {
+            PromiseState::Pending(_) => None,
+            PromiseState::Fulfilled(value) => Some(value.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Promise {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value() {
+            None => write!(f, "promise[...]"),
+            Some(value) => write!(f, "promise[{}]", value),
+        }
+    }
+}
+
+impl fmt::Debug for Promise {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Promises have identity, so they are only equal to themselves.
+impl PartialEq for Promise {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Object, ObjectRef, Promise};
+    use core::ops::Deref;
+
+    fn promise() -> ObjectRef {
+        ObjectRef::new(Object::Promise(Promise::new()))
+    }
+
+    fn value_of(promise: &ObjectRef) -> Option<i128> {
+        match promise.deref() {
+            Object::Promise(p) => p.value().map(|v| match v.deref() {
+                Object::Int(i) => *i,
+                _ => panic!("not an integer"),
+            }),
+            _ => panic!("not a promise"),
+        }
+    }
+
+    fn fulfill(promise: &ObjectRef, value: ObjectRef) -> bool {
+        match promise.deref() {
+            Object::Promise(p) => p.fulfill(value),
+            _ => panic!("not a promise"),
+        }
+    }
+
+    fn on_fulfill(promise: &ObjectRef, subscriber: ObjectRef) {
+        match promise.deref() {
+            Object::Promise(p) => p.on_fulfill(subscriber),
+            _ => panic!("not a promise"),
+        }
+    }
+
+    #[test]
+    fn fulfill_then_subscribe() {
+        let source = promise();
+        let subscriber = promise();
+
+        assert!(fulfill(&source, ObjectRef::new(Object::Int(42))));
+        assert_eq!(value_of(&subscriber), None);
+
+        on_fulfill(&source, subscriber.clone());
+        assert_eq!(value_of(&subscriber), Some(42));
+    }
+
+    #[test]
+    fn subscribe_then_fulfill() {
+        let source = promise();
+        let first = promise();
+        let second = promise();
+
+        on_fulfill(&source, first.clone());
+        on_fulfill(&source, second.clone());
+        assert_eq!(value_of(&first), None);
+
+        assert!(fulfill(&source, ObjectRef::new(Object::Int(42))));
+        assert_eq!(value_of(&first), Some(42));
+        assert_eq!(value_of(&second), Some(42));
+    }
+
+    #[test]
+    fn second_fulfill_is_ignored() {
+        let source = promise();
+
+        assert!(fulfill(&source, ObjectRef::new(Object::Int(1))));
+        assert!(!fulfill(&source, ObjectRef::new(Object::Int(2))));
+        assert_eq!(value_of(&source), Some(1));
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{Actor, ActorAddress, DropFn, InitFn, Object, ObjectRef, RecvFn, Scheduler, Worker};
+use crate::{
+    stats, Actor, ActorAddress, ActorOptions, DropFn, InitFn, Object, ObjectRef, Promise, RecvFn,
+    RuntimeStats, Scheduler, Timer, Worker,
+};
 use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::sync::atomic::{AtomicUsize, Ordering};
@@ -6,6 +9,7 @@ use core::sync::atomic::{AtomicUsize, Ordering};
 pub struct Runtime {
     workers: Vec<Worker>,
     scheduler: Scheduler,
+    timer: Timer,
     id_gen: AtomicUsize,
     pub noop_object: ObjectRef,
 }
@@ -15,6 +19,8 @@ impl Drop for Runtime {
         for worker in self.workers.iter() {
             worker.join();
         }
+        self.timer.stop();
+        stats::record_last(self.stats());
     }
 }
 
@@ -23,11 +29,25 @@ impl Runtime {
         Box::new(Runtime {
             workers: Vec::new(),
             scheduler: Scheduler::new(),
+            timer: Timer::new(),
             id_gen: AtomicUsize::new(1),
             noop_object: ObjectRef::new(Object::Noop),
         })
     }
 
+    /// A runtime with `workers` threads of its own handling messages.
+    pub fn with_workers(workers: usize) -> Box<Runtime> {
+        let mut rt = Runtime::new();
+        for _ in 0..workers {
+            rt.spawn_worker();
+        }
+        rt
+    }
+
+    pub fn worker_count(&self) -> usize {
+        self.workers.len()
+    }
+
     pub fn spawn_worker(&mut self) {
         let rt = self as *mut _;
         self.workers.push(Worker::new(rt))
@@ -39,6 +59,12 @@ impl Runtime {
         self.work();
     }
 
+    /// Stops the workers, even if some actors are still alive. Messages
+    /// that haven't been handled are dropped.
+    pub fn stop(&self) {
+        self.scheduler.stop();
+    }
+
     pub fn work(&self) {
         while self.scheduler.work() {}
     }
@@ -48,6 +74,27 @@ impl Runtime {
         self.scheduler.notify();
     }
 
+    /// Counts a message about to be pushed onto an actor's inbox.
+    #[inline]
+    pub fn count_enqueued(&self) {
+        self.scheduler.count_enqueued();
+    }
+
+    /// Counts a message that an actor has taken from its inbox.
+    #[inline]
+    pub fn count_received(&self) {
+        self.scheduler.count_received();
+    }
+
+    pub fn stats(&self) -> RuntimeStats {
+        RuntimeStats {
+            workers: self.worker_count(),
+            actors: self.scheduler.actors_count(),
+            pending_messages: self.scheduler.pending_messages(),
+            received_messages: self.scheduler.received_messages(),
+        }
+    }
+
     pub fn spawn(
         &self,
         state_size: usize,
@@ -56,14 +103,57 @@ impl Runtime {
         recv_fn: RecvFn,
         drop_fn: DropFn,
     ) -> ObjectRef {
-        let address = self.new_address();
-        let (actor_ref, actor) = Actor::new(
-            self, address, state_size, init_msg, init_fn, recv_fn, drop_fn,
-        );
+        self.spawn_bounded(None, state_size, init_msg, init_fn, recv_fn, drop_fn)
+    }
+
+    /// Spawns an actor that replies `busy!` to asks rather than queueing
+    /// them once `mailbox_capacity` messages are waiting in its inbox.
+    pub fn spawn_bounded(
+        &self,
+        mailbox_capacity: Option<usize>,
+        state_size: usize,
+        init_msg: ObjectRef,
+        init_fn: InitFn,
+        recv_fn: RecvFn,
+        drop_fn: DropFn,
+    ) -> ObjectRef {
+        let options = ActorOptions {
+            mailbox_capacity,
+            ..ActorOptions::new(self.new_address(), state_size)
+        };
+        let (actor_ref, actor) = Actor::new(self, options, init_msg, init_fn, recv_fn, drop_fn);
         self.scheduler.add_actor(actor);
         actor_ref
     }
 
+    /// Asks the message, but tells `reply_to` the atom `timeout!`
+    /// instead if no reply has arrived within `millis` milliseconds.
+    ///
+    /// The reply and the timeout race to fulfil a promise that stands
+    /// in for `reply_to`, so `reply_to` is told exactly one of them,
+    /// whichever comes first, and the other is discarded. Once the
+    /// timeout has been delivered, the promise no longer holds on to
+    /// `reply_to`, so a pending continuation is dropped even if the
+    /// receiver never replies.
+    pub fn ask_with_timeout(
+        &self,
+        receiver: &ObjectRef,
+        reply_to: ObjectRef,
+        message: ObjectRef,
+        millis: u64,
+    ) {
+        let promise = ObjectRef::new(Object::Promise(Promise::new()));
+        if let Object::Promise(p) = &*promise {
+            p.on_fulfill(reply_to);
+        }
+        self.timer.schedule(
+            millis,
+            promise.clone(),
+            ObjectRef::new(Object::Atom("timeout!")),
+        );
+        receiver.ask(promise, message);
+    }
+
     fn new_address(&self) -> ActorAddress {
         ActorAddress(self.id_gen.fetch_add(1, Ordering::Relaxed))
     }
@@ -9,6 +9,8 @@ pub struct Scheduler {
     idle_actors: SegQueue<Actor>,
     deleted_actors: Mutex<Set<ActorAddress>>,
     actors_count: AtomicUsize,
+    pending_messages: AtomicUsize,
+    received_messages: AtomicUsize,
     is_done: AtomicBool,
 }
 
@@ -19,6 +21,8 @@ impl Scheduler {
             idle_actors: SegQueue::new(),
             deleted_actors: Mutex::new(Set::new()),
             actors_count: AtomicUsize::new(0),
+            pending_messages: AtomicUsize::new(0),
+            received_messages: AtomicUsize::new(0),
             is_done: AtomicBool::new(false),
         }
     }
@@ -33,6 +37,33 @@ impl Scheduler {
         self.semaphore.notify();
     }
 
+    /// Counts a message that is about to be pushed onto an inbox. This
+    /// must happen before the push, so that the message can't be
+    /// received before it has been counted.
+    #[inline]
+    pub fn count_enqueued(&self) {
+        self.pending_messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a message that has been popped from an inbox.
+    #[inline]
+    pub fn count_received(&self) {
+        self.pending_messages.fetch_sub(1, Ordering::Relaxed);
+        self.received_messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn actors_count(&self) -> usize {
+        self.actors_count.load(Ordering::Relaxed)
+    }
+
+    pub fn pending_messages(&self) -> usize {
+        self.pending_messages.load(Ordering::Relaxed)
+    }
+
+    pub fn received_messages(&self) -> usize {
+        self.received_messages.load(Ordering::Relaxed)
+    }
+
     pub fn work(&self) -> bool {
         self.semaphore.wait();
         loop {
@@ -66,6 +97,13 @@ impl Scheduler {
         true
     }
 
+    /// Makes every worker stop once it's done with the message it's
+    /// handling, even if there are actors left.
+    pub fn stop(&self) {
+        self.is_done.store(true, Ordering::Relaxed);
+        self.notify();
+    }
+
     pub fn delete(&self, address: ActorAddress) {
         let mut da = self.deleted_actors.lock();
         da.insert(address);
@@ -0,0 +1,42 @@
+//! Counters describing what a runtime is doing, for monitoring.
+//!
+//! The stats of the last runtime to be dropped are kept around, so that
+//! they can be reported after a program has exited.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RuntimeStats {
+    /// The number of worker threads handling messages.
+    pub workers: usize,
+    /// The number of actors that haven't been deleted yet.
+    pub actors: usize,
+    /// The number of messages that have been enqueued but not yet
+    /// received, across all actors.
+    pub pending_messages: usize,
+    /// The number of messages that have been received since the runtime
+    /// started.
+    pub received_messages: usize,
+}
+
+static LAST_WORKERS: AtomicUsize = AtomicUsize::new(0);
+static LAST_ACTORS: AtomicUsize = AtomicUsize::new(0);
+static LAST_PENDING_MESSAGES: AtomicUsize = AtomicUsize::new(0);
+static LAST_RECEIVED_MESSAGES: AtomicUsize = AtomicUsize::new(0);
+
+pub fn record_last(stats: RuntimeStats) {
+    LAST_WORKERS.store(stats.workers, Ordering::SeqCst);
+    LAST_ACTORS.store(stats.actors, Ordering::SeqCst);
+    LAST_PENDING_MESSAGES.store(stats.pending_messages, Ordering::SeqCst);
+    LAST_RECEIVED_MESSAGES.store(stats.received_messages, Ordering::SeqCst);
+}
+
+pub fn last() -> RuntimeStats {
+    RuntimeStats {
+        workers: LAST_WORKERS.load(Ordering::SeqCst),
+        actors: LAST_ACTORS.load(Ordering::SeqCst),
+        pending_messages: LAST_PENDING_MESSAGES.load(Ordering::SeqCst),
+        received_messages: LAST_RECEIVED_MESSAGES.load(Ordering::SeqCst),
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{Condvar, Mutex, ObjectRef};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+struct Timeout {
+    deadline: u64,
+    target: ObjectRef,
+    message: ObjectRef,
+}
+
+/// Tells messages to objects after a delay.
+///
+/// The timer runs on a thread of its own, which is only started when
+/// the first timeout is scheduled, and which sleeps until the next
+/// deadline or until another timeout is scheduled.
+pub struct Timer {
+    timeouts: Mutex<Vec<Timeout>>,
+    changed: Condvar,
+    thread: Mutex<Option<libc::pthread_t>>,
+    is_stopped: AtomicBool,
+}
+
+impl Timer {
+    pub fn new() -> Timer {
+        Timer {
+            timeouts: Mutex::new(Vec::new()),
+            changed: Condvar::new(),
+            thread: Mutex::new(None),
+            is_stopped: AtomicBool::new(false),
+        }
+    }
+
+    /// Tells `message` to `target` once `millis` milliseconds have
+    /// passed. The timer must not move while it has scheduled timeouts.
+    pub fn schedule(&self, millis: u64, target: ObjectRef, message: ObjectRef) {
+        self.timeouts.lock().push(Timeout {
+            deadline: now_millis() + millis,
+            target,
+            message,
+        });
+        self.changed.notify_all();
+        self.ensure_started();
+    }
+
+    fn ensure_started(&self) {
+        let mut thread = self.thread.lock();
+        if thread.is_some() {
+            return;
+        }
+
+        extern "C" fn run(timer: *mut libc::c_void) -> *mut libc::c_void {
+            let timer = unsafe { &*(timer as *const Timer) };
+            timer.run();
+            core::ptr::null_mut()
+        }
+
+        unsafe {
+            let mut handle = core::mem::zeroed();
+            libc::pthread_create(
+                &mut handle,
+                core::ptr::null(),
+                run,
+                self as *const Timer as *mut _,
+            );
+            *thread = Some(handle);
+        }
+    }
+
+    fn run(&self) {
+        let mut timeouts = self.timeouts.lock();
+        while !self.is_stopped.load(Ordering::Relaxed) {
+            let now = now_millis();
+            let (expired, pending): (Vec<_>, Vec<_>) = core::mem::take(&mut *timeouts)
+                .into_iter()
+                .partition(|t| t.deadline <= now);
+            *timeouts = pending;
+
+            if !expired.is_empty() {
+                // The lock isn't held while telling, so that the
+                // receivers can schedule timeouts of their own.
+                drop(timeouts);
+                for Timeout {
+                    target, message, ..
+                } in expired
+                {
+                    target.tell(message);
+                }
+                timeouts = self.timeouts.lock();
+                continue;
+            }
+
+            match timeouts.iter().map(|t| t.deadline).min() {
+                Some(deadline) => self.changed.wait_timeout(&mut timeouts, deadline - now),
+                None => self.changed.wait(&mut timeouts),
+            }
+        }
+    }
+
+    /// Stops the timer thread. Timeouts that haven't expired yet are
+    /// never fired.
+    pub fn stop(&self) {
+        {
+            let _timeouts = self.timeouts.lock();
+            self.is_stopped.store(true, Ordering::Relaxed);
+            self.changed.notify_all();
+        }
+        if let Some(thread) = self.thread.lock().take() {
+            unsafe {
+                libc::pthread_join(thread, core::ptr::null_mut());
+            }
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    let mut time: libc::timespec = unsafe { core::mem::zeroed() };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time);
+    }
+    time.tv_sec as u64 * 1000 + time.tv_nsec as u64 / 1_000_000
+}
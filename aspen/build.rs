@@ -4,4 +4,17 @@ fn main() {
         std::env::var("TARGET").unwrap()
     );
     println!("cargo:rustc-link-search=../aspen-runtime/target/release");
+
+    if std::env::var("CARGO_FEATURE_EMBEDDED_RUNTIME").is_ok() {
+        let archive = std::env::var("ASPEN_RUNTIME_ARCHIVE").unwrap_or_else(|_| {
+            format!(
+                "{}/../target/release/libaspenrt.a",
+                std::env::var("CARGO_MANIFEST_DIR").unwrap()
+            )
+        });
+        println!("cargo:rerun-if-changed=build.rs");
+        println!("cargo:rerun-if-env-changed=ASPEN_RUNTIME_ARCHIVE");
+        println!("cargo:rerun-if-changed={}", archive);
+        println!("cargo:rustc-env=ASPEN_RUNTIME_ARCHIVE={}", archive);
+    }
 }
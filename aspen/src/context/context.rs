@@ -1,5 +1,5 @@
-use crate::semantics::Host;
-use crate::URI;
+use crate::semantics::{is_prelude, Host};
+use crate::{Severity, Source, URI};
 use mktemp::Temp;
 use std::convert::TryInto;
 use std::env::consts::{DLL_EXTENSION, DLL_PREFIX, EXE_EXTENSION};
@@ -13,6 +13,13 @@ use std::sync::Arc;
 use tokio::fs::{self, DirEntry};
 use tokio::io::AsyncWriteExt;
 
+/// Matches the files in workspace directories of contexts, which
+/// contain build artifacts rather than sources.
+pub const WORKSPACE_GLOB: &str = "**/.aspen/**";
+
+/// The file extensions of source files, unless configured otherwise.
+pub const DEFAULT_SOURCE_EXTENSIONS: &[&str] = &["aspen"];
+
 /// The context in which the compiler will work with code
 /// emission and configuration.
 ///
@@ -21,29 +28,42 @@ use tokio::io::AsyncWriteExt;
 pub struct Context {
     parent: Option<Arc<Context>>,
     kind: ContextKind,
+    source_extensions: Vec<String>,
+    naming_convention_severity: Option<Severity>,
 }
 
 #[derive(Clone)]
 enum ContextKind {
     Global(PathBuf),
     Directory(PathBuf),
-    Temporary(Temp),
+    Temporary(Temp, Option<PathBuf>),
 
     #[cfg(test)]
     Test,
 }
 
 impl Context {
+    /// A context that keeps its artifacts in a temporary directory and
+    /// has no sources of its own.
     pub fn temporary(parent: Option<Arc<Context>>) -> io::Result<Context> {
-        Ok(Self::new(parent, ContextKind::Temporary(Temp::new_dir()?)))
+        Ok(Self::new(
+            parent,
+            ContextKind::Temporary(Temp::new_dir()?, None),
+        ))
+    }
+
+    /// A context that keeps its artifacts in a temporary directory, with
+    /// the sources in the given directory.
+    pub fn temporary_in(parent: Option<Arc<Context>>, root: PathBuf) -> io::Result<Context> {
+        Ok(Self::new(
+            parent,
+            ContextKind::Temporary(Temp::new_dir()?, Some(root)),
+        ))
     }
 
     #[cfg(test)]
     pub fn test() -> Context {
-        Context {
-            parent: None,
-            kind: ContextKind::Test,
-        }
+        Self::new(None, ContextKind::Test)
     }
 
     pub fn directory(parent: Option<Arc<Context>>, dir: PathBuf) -> Context {
@@ -59,7 +79,15 @@ impl Context {
     }
 
     fn new(parent: Option<Arc<Context>>, kind: ContextKind) -> Context {
-        Context { parent, kind }
+        Context {
+            parent,
+            kind,
+            source_extensions: DEFAULT_SOURCE_EXTENSIONS
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            naming_convention_severity: Some(Severity::Warning),
+        }
     }
 
     pub async fn infer() -> io::Result<Arc<Context>> {
@@ -67,9 +95,9 @@ impl Context {
     }
 
     pub async fn infer_from(root: PathBuf) -> io::Result<Arc<Context>> {
-        let mut local = Self::from(root).await?;
+        let mut local = Self::from(root.clone()).await?;
         if local.is_global() {
-            local = Arc::new(Self::temporary(Some(local))?);
+            local = Arc::new(Self::temporary_in(Some(local), root)?);
         }
         Ok(local)
     }
@@ -134,6 +162,43 @@ impl Context {
         metadata.is_file() && entry.file_name() == "pkg.yml"
     }
 
+    /// Recognizes files with the given extensions as sources, instead
+    /// of the default ones.
+    pub fn with_source_extensions<I, S>(mut self, extensions: I) -> Context
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.source_extensions = extensions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn source_extensions(&self) -> &[String] {
+        self.source_extensions.as_slice()
+    }
+
+    /// Reports object names that don't start with an uppercase letter
+    /// with the given severity, or not at all.
+    pub fn with_naming_convention_severity(mut self, severity: Option<Severity>) -> Context {
+        self.naming_convention_severity = severity;
+        self
+    }
+
+    pub fn naming_convention_severity(&self) -> Option<Severity> {
+        self.naming_convention_severity
+    }
+
+    /// All source files under the root directory, excluding any
+    /// workspace directories.
+    pub async fn sources(&self) -> io::Result<Vec<Arc<Source>>> {
+        Ok(Source::files_with_extensions(
+            &self.root_dir()?,
+            self.source_extensions(),
+            &[WORKSPACE_GLOB],
+        )
+        .await)
+    }
+
     fn is_global(&self) -> bool {
         if let ContextKind::Global(_) = self.kind {
             true
@@ -144,7 +209,11 @@ impl Context {
 
     pub fn root_dir(&self) -> io::Result<PathBuf> {
         match &self.kind {
-            ContextKind::Temporary(_) => current_dir(),
+            ContextKind::Temporary(_, Some(root)) => root.canonicalize(),
+            ContextKind::Temporary(_, None) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "temporary contexts have no source root",
+            )),
             ContextKind::Directory(dir) => dir.canonicalize(),
             ContextKind::Global(dir) => Ok(dir.clone()),
 
@@ -153,9 +222,21 @@ impl Context {
         }
     }
 
+    /// Renders the URI for humans, relative to the root directory for
+    /// files inside of it, and as the full URI otherwise.
+    pub fn display_uri(&self, uri: &URI) -> String {
+        let relative = uri.file_path().and_then(|path| {
+            let root = self.root_dir().ok()?;
+            path.strip_prefix(root)
+                .ok()
+                .map(|p| p.display().to_string())
+        });
+        relative.unwrap_or_else(|| uri.uri().into())
+    }
+
     fn workspace_dir(&self, subdir: Option<&str>) -> PathBuf {
         let mut dir = match &self.kind {
-            ContextKind::Temporary(tmp) => tmp.to_path_buf(),
+            ContextKind::Temporary(tmp, _) => tmp.to_path_buf(),
             ContextKind::Directory(dir) => {
                 let mut dir = dir.clone();
                 dir.push(".aspen");
@@ -191,17 +272,41 @@ impl Context {
     }
 
     pub fn object_file_path(&self, uri: &URI) -> io::Result<PathBuf> {
-        let mut path: PathBuf = uri.try_into()?;
-        path.set_extension("o");
-        self.in_workspace(Some("cache"), path)
+        self.cache_path(uri, "o")
     }
 
     pub fn header_file_path(&self, uri: &URI) -> io::Result<PathBuf> {
+        self.cache_path(uri, "ah")
+    }
+
+    /// Built-in modules, like the prelude, are cached in a directory
+    /// of their own, while the others mirror the workspace.
+    fn cache_path(&self, uri: &URI, extension: &str) -> io::Result<PathBuf> {
+        if is_prelude(uri) {
+            let mut path = self.workspace_dir(Some("cache"));
+            path.push("aspen");
+            path.push("prelude");
+            path.set_extension(extension);
+            return Ok(path);
+        }
+
         let mut path: PathBuf = uri.try_into()?;
-        path.set_extension("ah");
+        path.set_extension(extension);
         self.in_workspace(Some("cache"), path)
     }
 
+    pub fn cache_manifest_path(&self) -> PathBuf {
+        let mut path = self.workspace_dir(Some("cache"));
+        path.push("manifest.json");
+        path
+    }
+
+    /// The settings of the workspace, kept next to its build artifacts
+    /// but checked in along with the sources.
+    pub fn config_path(&self) -> PathBuf {
+        self.workspace_dir(Some("config.json"))
+    }
+
     pub fn main_object_file_path(&self, main: &str) -> PathBuf {
         let mut path = self.workspace_dir(Some("cache"));
         path.push(main);
@@ -218,7 +323,7 @@ impl Context {
 
     fn out_dir(&self) -> PathBuf {
         match &self.kind {
-            ContextKind::Temporary(tmp) => self.root_dir().unwrap_or(tmp.to_path_buf()),
+            ContextKind::Temporary(tmp, _) => self.root_dir().unwrap_or(tmp.to_path_buf()),
             _ => self.workspace_dir(Some("out")),
         }
     }
@@ -249,8 +354,9 @@ impl Context {
         Ok(path)
     }
 
-    pub fn host(self: &Arc<Self>) -> Host {
-        Host::new(self.clone())
+    /// A host with the prelude, but none of the sources of the context.
+    pub async fn host(self: &Arc<Self>) -> Host {
+        Host::from(self.clone(), vec![]).await
     }
 
     pub fn name(&self) -> Option<String> {
@@ -278,10 +384,60 @@ impl Context {
         Ok(())
     }
 
+    /// Removes the build artifacts of the workspace, or the whole
+    /// workspace directory if `all` is set, returning the paths that
+    /// were (or, in a dry run, would have been) removed.
+    pub async fn clean(&self, all: bool, dry_run: bool) -> io::Result<Vec<PathBuf>> {
+        let workspace = self.workspace_dir(None);
+        if let Ok(root) = self.root_dir() {
+            if root.starts_with(&workspace) {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!(
+                        "refusing to remove {:?} which contains the sources",
+                        workspace
+                    ),
+                ));
+            }
+        }
+
+        let candidates = if all {
+            vec![workspace.clone()]
+        } else {
+            vec![
+                self.workspace_dir(Some("cache")),
+                self.workspace_dir(Some("out")),
+            ]
+        };
+
+        let mut removed = vec![];
+        for path in candidates {
+            if !path.starts_with(&workspace) {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("refusing to remove {:?} outside of {:?}", path, workspace),
+                ));
+            }
+            if fs::metadata(&path).await.is_err() {
+                continue;
+            }
+            if !dry_run {
+                fs::remove_dir_all(&path).await?;
+            }
+            removed.push(path);
+        }
+        Ok(removed)
+    }
+
     pub async fn ensure_binary_dir(&self) -> io::Result<()> {
         self.ensure_workspace_dir(Some("out")).await
     }
 
+    /// The directory that object files are cached in.
+    pub fn object_file_dir(&self) -> PathBuf {
+        self.workspace_dir(Some("cache"))
+    }
+
     pub async fn ensure_object_file_dir(&self) -> io::Result<()> {
         self.ensure_workspace_dir(Some("cache")).await
     }
@@ -307,10 +463,86 @@ impl fmt::Debug for ContextKind {
         match self {
             ContextKind::Global(p) => write!(f, "Global {:?}", p),
             ContextKind::Directory(p) => write!(f, "Directory {:?}", p),
-            ContextKind::Temporary(p) => write!(f, "Temporary {:?}", p.as_os_str()),
+            ContextKind::Temporary(p, None) => write!(f, "Temporary {:?}", p.as_os_str()),
+            ContextKind::Temporary(p, Some(root)) => {
+                write!(f, "Temporary {:?} in {:?}", p.as_os_str(), root)
+            }
 
             #[cfg(test)]
             ContextKind::Test => write!(f, "Test"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn clean_removes_cache_but_not_sources() {
+        let dir = Temp::new_dir().unwrap();
+        let context = Context::directory(None, dir.to_path_buf());
+        let source = dir.to_path_buf().join("main.aspen");
+        fs::write(&source, "object Main.").await.unwrap();
+
+        context.ensure_object_file_dir().await.unwrap();
+        let object = context.workspace_dir(Some("cache")).join("main.o");
+        fs::write(&object, "").await.unwrap();
+
+        let dry = context.clean(false, true).await.unwrap();
+        assert_eq!(dry, vec![context.workspace_dir(Some("cache"))]);
+        assert!(fs::metadata(&object).await.is_ok());
+
+        let removed = context.clean(false, false).await.unwrap();
+        assert_eq!(removed, dry);
+        assert!(fs::metadata(&object).await.is_err());
+        assert!(fs::metadata(&source).await.is_ok());
+        assert!(fs::metadata(context.workspace_dir(None)).await.is_ok());
+
+        context.clean(true, false).await.unwrap();
+        assert!(fs::metadata(context.workspace_dir(None)).await.is_err());
+        assert!(fs::metadata(&source).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn only_inferred_temporary_contexts_have_sources() {
+        let temp = Temp::new_dir().unwrap();
+        let dir = temp.to_path_buf().canonicalize().unwrap();
+        fs::write(dir.join("main.aspen"), "object Main.")
+            .await
+            .unwrap();
+
+        let inferred = Context::infer_from(dir.clone()).await.unwrap();
+        assert_eq!(inferred.root_dir().unwrap(), dir);
+        assert_eq!(inferred.sources().await.unwrap().len(), 1);
+
+        let temporary = Context::temporary(None).unwrap();
+        assert!(temporary.root_dir().is_err());
+        assert!(temporary.sources().await.is_err());
+    }
+
+    #[test]
+    fn display_uri_relative_to_root() {
+        let temp = Temp::new_dir().unwrap();
+        let dir = temp.to_path_buf().canonicalize().unwrap();
+        let context = Context::directory(None, dir.clone());
+
+        let nested = URI::file(dir.join("lib").join("main.aspen"));
+        assert_eq!(
+            context.display_uri(&nested),
+            PathBuf::from("lib")
+                .join("main.aspen")
+                .display()
+                .to_string()
+        );
+
+        let outside = URI::file("/elsewhere/main.aspen");
+        assert_eq!(
+            context.display_uri(&outside),
+            "file:///elsewhere/main.aspen"
+        );
+
+        let inline = URI::new("repl", "1");
+        assert_eq!(context.display_uri(&inline), "repl:1");
+    }
+}
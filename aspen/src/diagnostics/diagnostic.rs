@@ -1,15 +1,42 @@
 use crate::syntax::Node;
 use crate::{Range, Source};
+use std::any::Any;
+use std::cmp::Ordering;
 use std::fmt::{self, Debug, Display};
 use std::sync::Arc;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Severity {
     Error,
-    // Warning,
+    Warning,
+    Info,
     // Hint,
 }
 
+impl Severity {
+    fn rank(&self) -> u8 {
+        match self {
+            Severity::Info => 0,
+            Severity::Warning => 1,
+            Severity::Error => 2,
+        }
+    }
+}
+
+/// Severities are ordered by how serious they are, so that
+/// `Severity::Error` is the greatest.
+impl PartialOrd for Severity {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Severity {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
 pub trait Diagnostic
 where
     Self: Send + Sync + Debug,
@@ -53,3 +80,94 @@ impl Diagnostic for DuplicateExport {
         format!("Duplicate export `{}`", self.0)
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct SuppressedDiagnostics(pub usize, pub Arc<Source>, pub Range);
+
+impl Diagnostic for SuppressedDiagnostics {
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+
+    fn source(&self) -> &Arc<Source> {
+        &self.1
+    }
+
+    fn range(&self) -> Range {
+        self.2.clone()
+    }
+
+    fn message(&self) -> String {
+        format!("{} more diagnostics suppressed", self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidEncoding(pub Arc<Source>, pub Range);
+
+impl Diagnostic for InvalidEncoding {
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn source(&self) -> &Arc<Source> {
+        &self.0
+    }
+
+    fn range(&self) -> Range {
+        self.1.clone()
+    }
+
+    fn message(&self) -> String {
+        "Invalid UTF-8 was replaced with U+FFFD".into()
+    }
+}
+
+/// A panic in the compiler while working on a module, reported as a
+/// diagnostic so that the rest of the modules can still be compiled.
+#[derive(Debug, Clone)]
+pub struct InternalCompilerError(pub Arc<Source>, pub String);
+
+impl InternalCompilerError {
+    pub fn from_panic(source: Arc<Source>, payload: Box<dyn Any + Send>) -> InternalCompilerError {
+        let message = InternalCompilerError::describe_panic(payload.as_ref());
+        InternalCompilerError(source, message)
+    }
+
+    /// The message a panic was raised with, if it was raised with one.
+    pub fn describe_panic(payload: &(dyn Any + Send)) -> String {
+        if let Some(s) = payload.downcast_ref::<&str>() {
+            s.to_string()
+        } else if let Some(s) = payload.downcast_ref::<String>() {
+            s.clone()
+        } else {
+            "unknown cause".into()
+        }
+    }
+}
+
+impl Diagnostic for InternalCompilerError {
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn source(&self) -> &Arc<Source> {
+        &self.0
+    }
+
+    fn range(&self) -> Range {
+        let start = self.0.location_at(0);
+        Range {
+            start: start.clone(),
+            end: start,
+        }
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "Internal compiler error in module {:?}: {}",
+            self.0.uri(),
+            self.1
+        )
+    }
+}
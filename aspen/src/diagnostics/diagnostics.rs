@@ -1,4 +1,4 @@
-use crate::{Diagnostic, Severity, Source};
+use crate::{Diagnostic, Severity, Source, SuppressedDiagnostics};
 use std::collections::HashMap;
 use std::fmt;
 use std::iter::FromIterator;
@@ -52,10 +52,26 @@ impl Diagnostics {
     }
 
     pub fn is_ok(&self) -> bool {
-        !self
-            .diagnostics
-            .iter()
-            .any(|d| d.severity() == Severity::Error)
+        self.is_ok_at(Severity::Error)
+    }
+
+    /// Whether there are no diagnostics at least as severe as the
+    /// threshold.
+    pub fn is_ok_at(&self, threshold: Severity) -> bool {
+        !self.diagnostics.iter().any(|d| d.severity() >= threshold)
+    }
+
+    /// Keeps at most `limit` diagnostics, replacing the rest with a
+    /// single note saying how many were left out.
+    pub fn capped(mut self, limit: usize) -> Diagnostics {
+        if self.diagnostics.len() <= limit {
+            return self;
+        }
+        let suppressed = self.diagnostics.split_off(limit);
+        let first = &suppressed[0];
+        let note = SuppressedDiagnostics(suppressed.len(), first.source().clone(), first.range());
+        self.push(note);
+        self
     }
 
     pub fn group_by_source(self) -> HashMap<Arc<Source>, Diagnostics> {
@@ -135,3 +151,40 @@ impl Default for Diagnostics {
         Diagnostics::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Range;
+
+    #[derive(Debug)]
+    struct TestWarning(Arc<Source>);
+
+    impl Diagnostic for TestWarning {
+        fn severity(&self) -> Severity {
+            Severity::Warning
+        }
+
+        fn source(&self) -> &Arc<Source> {
+            &self.0
+        }
+
+        fn range(&self) -> Range {
+            self.0.range_all()
+        }
+
+        fn message(&self) -> String {
+            "Test warning".into()
+        }
+    }
+
+    #[test]
+    fn warnings_only_fail_when_denied() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.push(TestWarning(Source::new("test:x", "object X.")));
+
+        assert!(diagnostics.is_ok());
+        assert!(diagnostics.is_ok_at(Severity::Error));
+        assert!(!diagnostics.is_ok_at(Severity::Warning));
+    }
+}
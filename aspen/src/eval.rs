@@ -0,0 +1,121 @@
+use crate::generation::{CapturedPrints, JIT};
+use crate::semantics::{Host, Module};
+use crate::{Context, Diagnostic, Diagnostics, Range, Severity, Source, URI};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long an evaluated expression may take to produce its value.
+const EVAL_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    /// Taken for the duration of an evaluation, since the objects
+    /// printed by JIT compiled code all go through the same function.
+    static ref EVALUATING: Mutex<()> = Mutex::new(());
+}
+
+/// Compiles and runs an expression, like `2 3`, and returns the value
+/// it evaluates to as it would have been printed in `aspen live`.
+///
+/// Any errors found in the expression are returned instead, along with
+/// the rest of its diagnostics.
+pub async fn eval(code: &str) -> Result<String, Diagnostics> {
+    let source = Source::inline(URI::new("eval", "1"), code);
+    let fail = |message: String| {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.push(EvaluationFailed(source.clone(), message));
+        diagnostics
+    };
+
+    let context = Context::temporary(None).map_err(|e| fail(e.to_string()))?;
+    let host = Host::from(Arc::new(context), vec![]).await;
+    let module = host.set(source.clone()).await;
+
+    let diagnostics = module.diagnostics().await;
+    if !diagnostics.is_ok() {
+        return Err(diagnostics);
+    }
+    if module.inline_result_type().await.is_none() {
+        return Err(fail("Only expressions evaluate to a value".into()));
+    }
+
+    let prelude = host.prelude().await;
+    let _turn = EVALUATING.lock().unwrap_or_else(|e| e.into_inner());
+    let printed = CapturedPrints::start();
+    run(host, prelude, module, &printed).map_err(fail)
+}
+
+fn run(
+    host: Host,
+    prelude: Option<Arc<Module>>,
+    module: Arc<Module>,
+    printed: &CapturedPrints,
+) -> Result<String, String> {
+    let jit = JIT::new(host.context.clone())
+        .with_workers(Some(1))
+        .with_print(printed.print_fn());
+    jit.init_live_env(host.clone()).map_err(|e| e.to_string())?;
+
+    let value = (|| {
+        if let Some(prelude) = prelude {
+            jit.evaluate(prelude).map_err(|e| e.to_string())?;
+        }
+        jit.evaluate(module).map_err(|e| e.to_string())?;
+
+        printed
+            .recv_timeout(EVAL_TIMEOUT)
+            .map_err(|_| "The expression didn't evaluate to a value".to_string())
+    })();
+
+    // Every evaluation has a runtime of its own, which is stopped once
+    // the value has arrived.
+    jit.exit_live_env();
+    value
+}
+
+/// The expression couldn't be compiled or run, even though there were
+/// no errors in it.
+#[derive(Debug, Clone)]
+pub struct EvaluationFailed(pub Arc<Source>, pub String);
+
+impl Diagnostic for EvaluationFailed {
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn source(&self) -> &Arc<Source> {
+        &self.0
+    }
+
+    fn range(&self) -> Range {
+        self.0.range_all()
+    }
+
+    fn message(&self) -> String {
+        self.1.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn expression_evaluates_to_its_printed_value() {
+        assert_eq!(eval("2 3").await.unwrap(), "6");
+    }
+
+    #[tokio::test]
+    async fn declarations_have_no_value() {
+        let diagnostics = eval("object X.").await.unwrap_err();
+        assert_eq!(
+            diagnostics.iter().next().unwrap().message(),
+            "Only expressions evaluate to a value"
+        );
+    }
+
+    #[tokio::test]
+    async fn errors_are_returned_as_diagnostics() {
+        let diagnostics = eval("2 Nothing").await.unwrap_err();
+        assert!(!diagnostics.is_ok());
+    }
+}
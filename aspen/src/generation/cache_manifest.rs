@@ -0,0 +1,227 @@
+use crate::generation::TargetSpec;
+use crate::{stable_hash, Context, Source};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+/// A record of the sources that were used to produce the object
+/// files in the workspace cache.
+///
+/// Modification times are unreliable across checkouts, so reuse is
+/// decided by comparing content hashes, and the whole manifest is
+/// discarded when it was written by another version of the compiler.
+/// Objects are only reused for the target they were generated for, and
+/// while the modules they refer to are unchanged. The entry selector
+/// only affects the main object, which is never cached.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct CacheManifest {
+    version: String,
+    modules: HashMap<String, CacheEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct CacheEntry {
+    modified: u64,
+    hash: String,
+    #[serde(default)]
+    target: String,
+    #[serde(default)]
+    dependencies: String,
+}
+
+impl CacheManifest {
+    pub fn new() -> CacheManifest {
+        CacheManifest {
+            version: crate::version().into(),
+            modules: HashMap::new(),
+        }
+    }
+
+    /// Reads the manifest of the context, falling back to an empty
+    /// manifest if there is none or if it cannot be used.
+    pub async fn read(context: &Context) -> CacheManifest {
+        match tokio::fs::read(context.cache_manifest_path()).await {
+            Ok(bytes) => Self::from_json(bytes.as_slice()).unwrap_or_else(|_| Self::new()),
+            Err(_) => Self::new(),
+        }
+    }
+
+    pub async fn write(&self, context: &Context) -> io::Result<()> {
+        context.ensure_object_file_dir().await?;
+        tokio::fs::write(context.cache_manifest_path(), self.to_json()?).await
+    }
+
+    fn from_json(json: &[u8]) -> io::Result<CacheManifest> {
+        let manifest: CacheManifest = serde_json::from_slice(json)?;
+        if manifest.version != crate::version() {
+            return Ok(Self::new());
+        }
+        Ok(manifest)
+    }
+
+    fn to_json(&self) -> io::Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Whether the source and its dependencies have the same content as
+    /// when the source was last recorded for the target, regardless of
+    /// when they were modified.
+    pub fn is_fresh(
+        &self,
+        source: &Source,
+        target: &TargetSpec,
+        dependencies: &[Arc<Source>],
+    ) -> bool {
+        self.modules
+            .get(source.uri().uri())
+            .map(|entry| {
+                entry.hash == Self::hash_of(source)
+                    && entry.target == target.fingerprint()
+                    && entry.dependencies == Self::hash_of_dependencies(dependencies)
+            })
+            .unwrap_or(false)
+    }
+
+    pub fn record(&mut self, source: &Source, target: &TargetSpec, dependencies: &[Arc<Source>]) {
+        self.modules.insert(
+            source.uri().uri().into(),
+            CacheEntry {
+                modified: source
+                    .modified
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                hash: Self::hash_of(source),
+                target: target.fingerprint(),
+                dependencies: Self::hash_of_dependencies(dependencies),
+            },
+        );
+    }
+
+    pub fn forget(&mut self, source: &Source) {
+        self.modules.remove(source.uri().uri());
+    }
+
+    fn hash_of(source: &Source) -> String {
+        format!("{:016x}", source.content_hash())
+    }
+
+    fn hash_of_dependencies(dependencies: &[Arc<Source>]) -> String {
+        let mut dependencies: Vec<_> = dependencies
+            .iter()
+            .map(|source| format!("{} {}", source.uri().uri(), Self::hash_of(source)))
+            .collect();
+        dependencies.sort();
+        format!("{:016x}", stable_hash(dependencies.join("\n").as_bytes()))
+    }
+}
+
+impl Default for CacheManifest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantics::Host;
+    use crate::URI;
+
+    #[test]
+    fn touched_but_identical_source_is_fresh() {
+        let host = TargetSpec::host();
+        let mut manifest = CacheManifest::new();
+        let original = Source::new("test:x", "object X.");
+        manifest.record(&original, &host, &[]);
+
+        let touched = Source::new("test:x", "object X.");
+        assert!(manifest.is_fresh(&touched, &host, &[]));
+
+        let changed = Source::new("test:x", "object Y.");
+        assert!(!manifest.is_fresh(&changed, &host, &[]));
+
+        let other = Source::new("test:y", "object X.");
+        assert!(!manifest.is_fresh(&other, &host, &[]));
+    }
+
+    #[test]
+    fn objects_are_only_fresh_for_their_target() {
+        let source = Source::new("test:x", "object X.");
+        let mut manifest = CacheManifest::new();
+        manifest.record(&source, &TargetSpec::host(), &[]);
+
+        assert!(manifest.is_fresh(&source, &TargetSpec::host(), &[]));
+        assert!(!manifest.is_fresh(&source, &TargetSpec::host().with_cpu("skylake"), &[]));
+        assert!(!manifest.is_fresh(
+            &source,
+            &TargetSpec::host().with_feature("+avx2").unwrap(),
+            &[]
+        ));
+        assert!(!manifest.is_fresh(&source, &TargetSpec::new("wasm32-unknown-unknown"), &[]));
+
+        let native = TargetSpec::host().with_feature("+avx2").unwrap();
+        manifest.record(&source, &native, &[]);
+        assert!(manifest.is_fresh(&source, &native, &[]));
+        assert!(!manifest.is_fresh(&source, &TargetSpec::host(), &[]));
+    }
+
+    #[tokio::test]
+    async fn objects_are_stale_when_a_dependency_changes() {
+        let target = TargetSpec::host();
+        let host = Host::new(Arc::new(Context::test()));
+        host.set(Source::new("test:a", "object A.")).await;
+        let b = host
+            .set(Source::new("test:b", "object B { 1 -> A. }"))
+            .await;
+        let c = host.set(Source::new("test:c", "object C.")).await;
+
+        let dependencies = b.dependencies().await;
+        assert_eq!(dependencies.len(), 1);
+        assert_eq!(dependencies[0].uri(), &URI::from("test:a"));
+
+        let mut manifest = CacheManifest::new();
+        manifest.record(&b.source, &target, &dependencies);
+        manifest.record(&c.source, &target, &c.dependencies().await);
+
+        host.set(Source::new("test:a", "object A { 1 -> 2. }"))
+            .await;
+        let b = host.set(b.source.clone()).await;
+        let c = host.set(c.source.clone()).await;
+
+        assert!(!manifest.is_fresh(&b.source, &target, &b.dependencies().await));
+        assert!(manifest.is_fresh(&c.source, &target, &c.dependencies().await));
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let mut manifest = CacheManifest::new();
+        manifest.record(
+            &Source::new("test:x", "object X."),
+            &TargetSpec::host(),
+            &[],
+        );
+
+        let json = manifest.to_json().unwrap();
+        assert_eq!(CacheManifest::from_json(json.as_slice()).unwrap(), manifest);
+    }
+
+    #[test]
+    fn manifest_from_other_version_is_discarded() {
+        let mut manifest = CacheManifest::new();
+        manifest.version = "0.0.0-other".into();
+        manifest.record(
+            &Source::new("test:x", "object X."),
+            &TargetSpec::host(),
+            &[],
+        );
+
+        let json = manifest.to_json().unwrap();
+        assert_eq!(
+            CacheManifest::from_json(json.as_slice()).unwrap(),
+            CacheManifest::new()
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryIter};
+use std::sync::Mutex;
+use std::time::Duration;
+
+lazy_static! {
+    /// Where the objects printed by JIT compiled code are sent while
+    /// they are being captured.
+    static ref PRINTED: Mutex<Option<Sender<String>>> = Mutex::new(None);
+}
+
+extern "C" fn capture_print(object: &aspenrt::ObjectRef) {
+    if let Some(printed) = PRINTED.lock().unwrap().as_ref() {
+        printed.send(object.to_string()).unwrap_or(());
+    }
+}
+
+/// The objects printed by JIT compiled code, as they would have been
+/// printed, from when the capture starts until it is dropped.
+///
+/// All printing goes through the same function, so there should only be
+/// one capture at a time.
+pub struct CapturedPrints {
+    receiver: Receiver<String>,
+}
+
+impl CapturedPrints {
+    pub fn start() -> CapturedPrints {
+        let (sender, receiver) = mpsc::channel();
+        *PRINTED.lock().unwrap() = Some(sender);
+        CapturedPrints { receiver }
+    }
+
+    /// The function to pass to `JIT::with_print` for the printed
+    /// objects to be captured.
+    pub fn print_fn(&self) -> extern "C" fn(&aspenrt::ObjectRef) {
+        capture_print
+    }
+
+    /// The objects printed so far that haven't been received yet.
+    pub fn try_iter(&self) -> TryIter<String> {
+        self.receiver.try_iter()
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<String, RecvTimeoutError> {
+        self.receiver.recv_timeout(timeout)
+    }
+}
+
+impl Drop for CapturedPrints {
+    fn drop(&mut self) {
+        *PRINTED.lock().unwrap() = None;
+    }
+}
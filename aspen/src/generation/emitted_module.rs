@@ -1,4 +1,5 @@
 use crate::generation::*;
+use crate::{Range, URI};
 use inkwell::execution_engine::ExecutionEngine;
 use inkwell::module::Module;
 use inkwell::values::FunctionValue;
@@ -8,6 +9,7 @@ pub struct EmittedModule<'ctx> {
     pub module: Module<'ctx>,
     intrinsics: Intrinsics<'ctx>,
     init_fn: Option<FunctionValue<'ctx>>,
+    source_map: Vec<(String, URI, Range)>,
 }
 
 impl<'ctx> EmittedModule<'ctx> {
@@ -16,6 +18,7 @@ impl<'ctx> EmittedModule<'ctx> {
             module,
             intrinsics,
             init_fn: None,
+            source_map: vec![],
         }
     }
 
@@ -28,19 +31,57 @@ impl<'ctx> EmittedModule<'ctx> {
             module,
             intrinsics,
             init_fn: Some(init_fn),
+            source_map: vec![],
         }
     }
 
+    pub fn with_source_map(mut self, source_map: Vec<(String, URI, Range)>) -> Self {
+        self.source_map = source_map;
+        self
+    }
+
+    /// The symbol of each function generated from code, along with the
+    /// range of code it was generated from.
+    pub fn source_map(&self) -> &[(String, URI, Range)] {
+        &self.source_map
+    }
+
     pub unsafe fn evaluate(&self, engine: ExecutionEngine<'ctx>) {
+        self.evaluate_with_print(engine, None);
+    }
+
+    /// Like [`evaluate`](EmittedModule::evaluate), but objects printed
+    /// by the module are passed to `print` if one is given.
+    pub unsafe fn evaluate_with_print(
+        &self,
+        engine: ExecutionEngine<'ctx>,
+        print: Option<extern "C" fn(&aspenrt::ObjectRef)>,
+    ) {
         engine.add_module(&self.module).unwrap_or(());
 
         self.intrinsics.map_in_jit(&engine);
+        if let Some(print) = print {
+            self.intrinsics.map_print_in_jit(&engine, print);
+        }
 
         if let Some(init_fn) = &self.init_fn {
             engine.run_function(*init_fn, &[]);
         }
     }
 
+    /// Runs the init function as the `main` function of a program, passing
+    /// `args` as its `argv`.
+    pub unsafe fn evaluate_main(&self, engine: ExecutionEngine<'ctx>, args: &[&str]) -> i32 {
+        engine.add_module(&self.module).unwrap_or(());
+
+        self.intrinsics.map_in_jit(&engine);
+
+        match &self.init_fn {
+            Some(main_fn) => engine.run_function_as_main(*main_fn, args),
+            None => 0,
+        }
+    }
+
     pub fn verify(&self) -> GenResult<()> {
         match self.module.verify() {
             Ok(()) => Ok(()),
@@ -1,19 +1,74 @@
-use crate::generation::{GenError, GenResult, Generator, ObjectFile};
+use crate::generation::{
+    runtime_dir, validate_entry_selector, CacheManifest, GenError, GenResult, Generator,
+    ObjectFile, TargetSpec, DEFAULT_ENTRY_SELECTOR,
+};
 use crate::semantics::Host;
+use crate::URI;
 use futures::future::join_all;
-use std::env::{current_dir, current_exe};
+use std::env::current_dir;
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Semaphore};
+
+/// Called with the URI of each module as its object file is done,
+/// along with how long it took to generate.
+pub type ProgressCallback = Arc<dyn Fn(&URI, Duration) + Send + Sync>;
+
+/// How many object files are generated at the same time by default.
+pub const DEFAULT_CONCURRENCY: usize = 4;
 
 pub struct Executable {
     pub path: PathBuf,
     pub objects: Vec<ObjectFile>,
 }
 
+/// The sizes of what a build produced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildSummary {
+    pub objects: usize,
+    pub object_bytes: u64,
+    pub binary_bytes: u64,
+}
+
+impl fmt::Display for BuildSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} object{} ({}), binary {}",
+            self.objects,
+            if self.objects == 1 { "" } else { "s" },
+            format_bytes(self.object_bytes),
+            format_bytes(self.binary_bytes)
+        )
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
 pub struct ExecutableBuilder {
     pub host: Host,
     pub main: Option<String>,
+    pub entry: String,
     pub static_linkage: bool,
+    pub pie: Option<bool>,
+    pub target: TargetSpec,
+    pub on_progress: Option<ProgressCallback>,
+    pub concurrency: usize,
 }
 
 impl ExecutableBuilder {
@@ -21,7 +76,12 @@ impl ExecutableBuilder {
         ExecutableBuilder {
             host,
             main: None,
+            entry: DEFAULT_ENTRY_SELECTOR.into(),
             static_linkage: false,
+            pie: None,
+            target: TargetSpec::host(),
+            on_progress: None,
+            concurrency: DEFAULT_CONCURRENCY,
         }
     }
 
@@ -30,14 +90,58 @@ impl ExecutableBuilder {
         self
     }
 
+    /// The atom told to the main object to start the program, `run!`
+    /// by default.
+    pub fn entry_selector<S: Into<String>>(&mut self, selector: S) -> GenResult<&mut Self> {
+        let selector = selector.into();
+        validate_entry_selector(&selector)?;
+        self.entry = selector;
+        Ok(self)
+    }
+
     pub fn link_statically(&mut self) -> &mut Self {
         self.static_linkage = true;
         self
     }
 
+    /// Whether executables are position-independent. By default, the
+    /// linker decides.
+    pub fn position_independent(&mut self, pie: bool) -> &mut Self {
+        self.pie = Some(pie);
+        self
+    }
+
+    /// The machine to generate code for. By default, this is the
+    /// machine the compiler runs on.
+    pub fn target(&mut self, target: TargetSpec) -> &mut Self {
+        self.target = target;
+        self
+    }
+
+    pub fn on_progress<F: Fn(&URI, Duration) + Send + Sync + 'static>(
+        &mut self,
+        callback: F,
+    ) -> &mut Self {
+        self.on_progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Limits how many object files are generated at the same time.
+    pub fn concurrency(&mut self, concurrency: usize) -> &mut Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
     pub async fn write(&self) -> GenResult<Executable> {
         Executable::new(self).await
     }
+
+    /// Generates the object files and writes them into `dir`, without
+    /// linking them. The object files of modules keep their layout from
+    /// the cache, and the main object, if any, is placed at the top.
+    pub async fn write_objects<P: AsRef<Path>>(&self, dir: P) -> GenResult<Vec<ObjectFile>> {
+        Executable::write_objects(self, dir.as_ref()).await
+    }
 }
 
 impl Executable {
@@ -45,37 +149,32 @@ impl Executable {
         ExecutableBuilder::new(host)
     }
 
-    async fn new(builder: &ExecutableBuilder) -> GenResult<Executable> {
-        let host = &builder.host;
-        let modules = host.modules().await;
-        let object_results =
-            join_all(modules.iter().map(|module| ObjectFile::new(module.clone()))).await;
-
-        let mut objects = vec![];
-        let mut errors = vec![];
-
-        for object_result in object_results {
-            match object_result {
-                Ok(o) => objects.push(o),
-                Err(e) => errors.push(e),
-            }
-        }
-
-        if errors.len() > 0 {
-            return Err(GenError::Multi(errors));
+    /// Reads the sizes of the object files and the linked binary.
+    pub async fn summary(&self) -> GenResult<BuildSummary> {
+        let mut object_bytes = 0;
+        for object in self.objects.iter() {
+            object_bytes += tokio::fs::metadata(&object.path).await?.len();
         }
 
-        if let Some(main) = builder.main.as_ref() {
-            let context = inkwell::context::Context::create();
-            let generator = Generator::new(host.clone(), &context);
+        Ok(BuildSummary {
+            objects: self.objects.len(),
+            object_bytes,
+            binary_bytes: tokio::fs::metadata(&self.path).await?.len(),
+        })
+    }
 
-            let emitted_module = generator.generate_main(main.as_ref())?;
+    async fn new(builder: &ExecutableBuilder) -> GenResult<Executable> {
+        let host = &builder.host;
+        let target = &builder.target;
+        let mut objects = Executable::generate_objects(builder).await?;
 
+        if let Some(main) = builder.main.as_ref() {
             host.context.ensure_object_file_dir().await?;
             objects.push(
-                ObjectFile::write(
+                Executable::generate_main_object(
+                    builder,
+                    main,
                     host.context.main_object_file_path(main.as_ref()),
-                    emitted_module,
                 )
                 .await?,
             );
@@ -83,10 +182,18 @@ impl Executable {
             let path = host.context.binary_file_path(main.as_ref());
             host.context.ensure_binary_dir().await?;
 
-            Executable::link_executable(path, objects, builder.static_linkage).await
+            if target.is_wasm() {
+                Executable::link_wasm(path.with_extension("wasm"), objects).await
+            } else {
+                Executable::link_executable(path, objects, builder.static_linkage, builder.pie)
+                    .await
+            }
         } else {
             host.context.ensure_binary_dir().await?;
-            if builder.static_linkage {
+            if target.is_wasm() {
+                let path = host.context.binary_dylib_file_path()?;
+                Executable::link_wasm(path.with_extension("wasm"), objects).await
+            } else if builder.static_linkage {
                 let path = host.context.binary_archive_file_path()?;
                 Executable::link_archive(path, objects).await
             } else {
@@ -96,19 +203,143 @@ impl Executable {
         }
     }
 
+    /// Writes the object files into `dir` instead of linking them, so
+    /// that they can be linked by some other build.
+    async fn write_objects(builder: &ExecutableBuilder, dir: &Path) -> GenResult<Vec<ObjectFile>> {
+        let cache_dir = builder.host.context.object_file_dir();
+        let mut objects = vec![];
+
+        for object in Executable::generate_objects(builder).await? {
+            let path = dir.join(object.path.strip_prefix(&cache_dir).unwrap_or(&object.path));
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::copy(&object.path, &path).await?;
+            objects.push(ObjectFile { path });
+        }
+
+        if let Some(main) = builder.main.as_ref() {
+            tokio::fs::create_dir_all(dir).await?;
+            let mut path = dir.join(main);
+            path.set_extension("main.o");
+            objects.push(Executable::generate_main_object(builder, main, path).await?);
+        }
+
+        Ok(objects)
+    }
+
+    async fn generate_objects(builder: &ExecutableBuilder) -> GenResult<Vec<ObjectFile>> {
+        let host = &builder.host;
+        let target = &builder.target;
+        let modules = host.modules().await;
+        let manifest = Mutex::new(CacheManifest::read(&host.context).await);
+
+        // Modules are checked against the headers of the modules they
+        // refer to, so the headers of all modules that are about to be
+        // generated are written first. Cached modules keep the headers
+        // that their object files were generated with.
+        for module in modules.iter() {
+            if !ObjectFile::is_cached(module, &manifest, target).await {
+                ObjectFile::write_header(module).await?;
+            }
+        }
+
+        let semaphore = Semaphore::new(builder.concurrency);
+        let object_results = join_all(modules.iter().map(|module| {
+            let manifest = &manifest;
+            let semaphore = &semaphore;
+            async move {
+                let _permit = semaphore.acquire().await;
+                let start = Instant::now();
+                let result = ObjectFile::new(module.clone(), manifest, target).await;
+                if let Some(on_progress) = builder.on_progress.as_ref() {
+                    on_progress(module.uri(), start.elapsed());
+                }
+                result
+            }
+        }))
+        .await;
+        manifest.into_inner().write(&host.context).await?;
+
+        let mut objects = vec![];
+        let mut errors = vec![];
+
+        for object_result in object_results {
+            match object_result {
+                Ok(o) => objects.push(o),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if errors.len() > 0 {
+            return Err(GenError::Multi(errors));
+        }
+
+        Ok(objects)
+    }
+
+    async fn generate_main_object(
+        builder: &ExecutableBuilder,
+        main: &str,
+        path: PathBuf,
+    ) -> GenResult<ObjectFile> {
+        let context = inkwell::context::Context::create();
+        let generator = Generator::new(builder.host.clone(), &context);
+
+        let emitted_module = generator.generate_main_with_entry(main, &builder.entry)?;
+
+        ObjectFile::write(path, emitted_module, &builder.target).await
+    }
+
     async fn link_executable(
         path: PathBuf,
         objects: Vec<ObjectFile>,
         static_linkage: bool,
+        pie: Option<bool>,
     ) -> GenResult<Executable> {
-        let mut runtime_path = current_exe()?;
-        runtime_path.pop();
+        let cc = Executable::link_executable_command(&path, &objects, static_linkage, pie)?;
+
+        let command = format!("{:?}", cc);
+
+        let status = tokio::process::Command::from(cc).spawn()?.await?;
+
+        if !status.success() {
+            return Err(GenError::FailedToLink(command));
+        }
+
+        let mut strip = std::process::Command::new("strip");
+        strip.arg(&path);
+        let status = tokio::process::Command::from(strip).spawn()?.await?;
+        if !status.success() {
+            eprintln!("Failed to strip static executable");
+        }
+
+        Ok(Executable { objects, path })
+    }
+
+    fn link_executable_command(
+        path: &Path,
+        objects: &[ObjectFile],
+        static_linkage: bool,
+        pie: Option<bool>,
+    ) -> GenResult<std::process::Command> {
+        let runtime_path = runtime_dir()?;
 
         let mut cc = std::process::Command::new("cc");
         if static_linkage {
             cc.arg("-static");
         }
 
+        match pie {
+            Some(true) => {
+                cc.arg("-fPIE").arg("-pie");
+            }
+            Some(false) => {
+                cc.arg("-no-pie");
+            }
+            None => {}
+        }
+
         for object in objects.iter() {
             cc.arg(&object.path);
         }
@@ -125,29 +356,44 @@ impl Executable {
             }
         }
 
-        cc.arg("-o").arg(&path);
+        cc.arg("-o").arg(path);
 
-        let command = format!("{:?}", cc);
+        Ok(cc)
+    }
 
-        let status = tokio::process::Command::from(cc).spawn()?.await?;
+    /// Links the objects into a single WebAssembly module. The runtime
+    /// isn't available for WebAssembly, so its functions are left to
+    /// be imported from the embedder.
+    async fn link_wasm(path: PathBuf, objects: Vec<ObjectFile>) -> GenResult<Executable> {
+        let ld = Executable::link_wasm_command(&path, &objects);
+
+        let command = format!("{:?}", ld);
+
+        let status = tokio::process::Command::from(ld).spawn()?.await?;
 
         if !status.success() {
             return Err(GenError::FailedToLink(command));
         }
 
-        let mut strip = std::process::Command::new("strip");
-        strip.arg(&path);
-        let status = tokio::process::Command::from(strip).spawn()?.await?;
-        if !status.success() {
-            eprintln!("Failed to strip static executable");
+        Ok(Executable { objects, path })
+    }
+
+    fn link_wasm_command(path: &Path, objects: &[ObjectFile]) -> std::process::Command {
+        let mut ld = std::process::Command::new("wasm-ld");
+        ld.arg("--no-entry")
+            .arg("--export-dynamic")
+            .arg("--allow-undefined");
+
+        for object in objects.iter() {
+            ld.arg(&object.path);
         }
 
-        Ok(Executable { objects, path })
+        ld.arg("-o").arg(path);
+        ld
     }
 
     async fn link_lib(path: PathBuf, objects: Vec<ObjectFile>) -> GenResult<Executable> {
-        let mut runtime_path = current_exe()?;
-        runtime_path.pop();
+        let runtime_path = runtime_dir()?;
 
         let mut cc = std::process::Command::new("cc");
         cc.arg("-shared");
@@ -179,9 +425,6 @@ impl Executable {
     }
 
     async fn link_archive(path: PathBuf, objects: Vec<ObjectFile>) -> GenResult<Executable> {
-        let mut runtime_path = current_exe()?;
-        runtime_path.pop();
-
         let mut ar = std::process::Command::new("ar");
 
         ar.arg(&path);
@@ -215,3 +458,221 @@ impl fmt::Display for Executable {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantics::PRELUDE_URI;
+    use crate::{Context, Source};
+    use std::sync::Mutex as SyncMutex;
+
+    #[tokio::test]
+    async fn progress_is_reported_per_module() {
+        let dir = current_dir().unwrap();
+        let context = Arc::new(Context::temporary_in(None, dir.clone()).unwrap());
+        let host = Host::from(
+            context,
+            vec![
+                Source::new(URI::file(dir.join("progress_a.aspen")), "object A."),
+                Source::new(URI::file(dir.join("progress_b.aspen")), "object B."),
+            ],
+        )
+        .await;
+
+        let reported = Arc::new(SyncMutex::new(vec![]));
+        let mut builder = Executable::build(host);
+        {
+            let reported = reported.clone();
+            builder.on_progress(move |uri, _| reported.lock().unwrap().push(uri.clone()));
+        }
+
+        // Linking may fail without the runtime library, but every module
+        // has been generated by then.
+        let _ = builder.write().await;
+
+        let mut reported = reported.lock().unwrap().clone();
+        reported.sort();
+        assert_eq!(
+            reported,
+            vec![
+                URI::from(PRELUDE_URI),
+                URI::file(dir.join("progress_a.aspen")),
+                URI::file(dir.join("progress_b.aspen")),
+            ]
+        );
+    }
+
+    #[test]
+    fn pie_flag_controls_linker_arguments() {
+        let path = PathBuf::from("main");
+        let command = |pie| {
+            format!(
+                "{:?}",
+                Executable::link_executable_command(&path, &[], false, pie).unwrap()
+            )
+        };
+
+        assert!(!command(None).contains("-no-pie"));
+        assert!(!command(None).contains("\"-pie\""));
+        assert!(command(Some(false)).contains("\"-no-pie\""));
+        assert!(!command(Some(true)).contains("-no-pie"));
+        assert!(command(Some(true)).contains("\"-pie\""));
+    }
+
+    #[cfg(feature = "embedded-runtime")]
+    #[tokio::test]
+    async fn embedded_runtime_links_without_an_external_runtime() {
+        let mut exe_dir = std::env::current_exe().unwrap();
+        exe_dir.pop();
+        assert!(std::fs::metadata(exe_dir.join(crate::generation::RUNTIME_ARCHIVE)).is_err());
+
+        let runtime = runtime_dir().unwrap();
+        assert_ne!(runtime, exe_dir);
+        assert!(std::fs::metadata(runtime.join(crate::generation::RUNTIME_ARCHIVE)).is_ok());
+
+        let dir = current_dir().unwrap();
+        let context = Arc::new(Context::temporary_in(None, dir.clone()).unwrap());
+        let host = Host::from(
+            context.clone(),
+            vec![Source::new(
+                URI::file(dir.join("embedded_main.aspen")),
+                "object Main { run! -> 1. }",
+            )],
+        )
+        .await;
+
+        let executable = Executable::build(host)
+            .main("Main")
+            .link_statically()
+            .write()
+            .await
+            .unwrap();
+        assert!(tokio::fs::metadata(&executable.path).await.is_ok());
+    }
+
+    #[test]
+    fn wasm_is_linked_without_the_native_runtime() {
+        let objects = vec![ObjectFile {
+            path: PathBuf::from("x.o"),
+        }];
+        let command = format!(
+            "{:?}",
+            Executable::link_wasm_command(Path::new("main.wasm"), &objects)
+        );
+
+        assert!(command.starts_with("\"wasm-ld\""));
+        assert!(command.contains("\"x.o\""));
+        assert!(!command.contains("aspenrt"));
+        assert!(TargetSpec::new("wasm32-unknown-unknown").is_wasm());
+    }
+
+    #[tokio::test]
+    async fn modules_are_generated_concurrently() {
+        let dir = current_dir().unwrap();
+        let context = Arc::new(Context::temporary_in(None, dir.clone()).unwrap());
+        let uris: Vec<_> = (0..6)
+            .map(|i| URI::file(dir.join(format!("concurrent_{}.aspen", i))))
+            .collect();
+        let host = Host::from(
+            context.clone(),
+            uris.iter()
+                .enumerate()
+                .map(|(i, uri)| Source::new(uri.clone(), format!("object O{}.", i))),
+        )
+        .await;
+
+        let _ = Executable::build(host).concurrency(2).write().await;
+
+        for uri in uris.iter() {
+            let path = context.object_file_path(uri).unwrap();
+            assert!(tokio::fs::metadata(&path).await.is_ok(), "{:?}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn objects_can_be_emitted_without_linking() {
+        let dir = current_dir().unwrap();
+        let context = Arc::new(Context::temporary_in(None, dir.clone()).unwrap());
+        let host = Host::from(
+            context.clone(),
+            vec![Source::new(
+                URI::file(dir.join("emitted_main.aspen")),
+                "object Main.",
+            )],
+        )
+        .await;
+        let temp = mktemp::Temp::new_dir().unwrap();
+        let out = temp.to_path_buf();
+
+        let objects = Executable::build(host)
+            .main("Main")
+            .write_objects(&out)
+            .await
+            .unwrap();
+
+        assert_eq!(objects.len(), 3);
+        for object in objects.iter() {
+            assert!(object.path.starts_with(&out), "{:?}", object.path);
+            assert!(tokio::fs::metadata(&object.path).await.is_ok());
+        }
+        assert!(tokio::fs::metadata(context.binary_file_path("Main"))
+            .await
+            .is_err());
+    }
+
+    #[cfg(feature = "embedded-runtime")]
+    #[tokio::test]
+    async fn summary_counts_objects_and_sizes() {
+        let dir = current_dir().unwrap();
+        let context = Arc::new(Context::temporary_in(None, dir.clone()).unwrap());
+        let host = Host::from(
+            context,
+            vec![
+                Source::new(URI::file(dir.join("summary_a.aspen")), "object A."),
+                Source::new(
+                    URI::file(dir.join("summary_main.aspen")),
+                    "object SummaryMain { run! -> A. }",
+                ),
+            ],
+        )
+        .await;
+
+        let executable = Executable::build(host)
+            .main("SummaryMain")
+            .link_statically()
+            .write()
+            .await
+            .unwrap();
+        let summary = executable.summary().await.unwrap();
+
+        // The prelude, both modules and the main object.
+        assert_eq!(summary.objects, 4);
+        let mut object_bytes = 0;
+        for object in executable.objects.iter() {
+            object_bytes += tokio::fs::metadata(&object.path).await.unwrap().len();
+        }
+        assert!(object_bytes > 0);
+        assert_eq!(summary.object_bytes, object_bytes);
+        assert_eq!(
+            summary.binary_bytes,
+            tokio::fs::metadata(&executable.path).await.unwrap().len()
+        );
+    }
+
+    #[test]
+    fn summaries_are_shown_in_binary_units() {
+        let summary = BuildSummary {
+            objects: 2,
+            object_bytes: 2100,
+            binary_bytes: 5000,
+        };
+        assert_eq!(summary.to_string(), "2 objects (2.1 KiB), binary 4.9 KiB");
+
+        let summary = BuildSummary {
+            objects: 1,
+            object_bytes: 512,
+            binary_bytes: 3 * 1024 * 1024,
+        };
+        assert_eq!(summary.to_string(), "1 object (512 B), binary 3.0 MiB");
+    }
+}
@@ -1,6 +1,9 @@
-use crate::generation::{EmittedModule, GenError, GenResult, Intrinsics};
+use crate::generation::{
+    mangle, EmittedModule, GenError, GenResult, Intrinsics, ModuleHeader, ObjectHeader,
+};
 use crate::semantics::{Host, Module as HostModule};
-use crate::syntax;
+use crate::syntax::{self, Lexer, Node, TokenKind};
+use crate::{Range, Source, URI};
 use futures::executor::block_on;
 use inkwell::basic_block::BasicBlock;
 use inkwell::builder::Builder;
@@ -9,15 +12,39 @@ use inkwell::module::{Linkage, Module};
 use inkwell::types::{FunctionType, IntType, PointerType, StructType, VoidType};
 use inkwell::values::{BasicValue, FunctionValue, IntValue, PointerValue};
 use inkwell::AddressSpace;
+use std::cell::RefCell;
 use std::sync::Arc;
 
+/// The message that is told to the main object to start a program.
+pub const DEFAULT_ENTRY_SELECTOR: &str = "run!";
+
+/// The function of a live environment that stops its runtime.
+pub const LIVE_EXIT_FN: &str = "live_exit";
+
+/// Checks that the selector is a single atom, like `run!`, that can be
+/// told to the main object.
+pub fn validate_entry_selector(selector: &str) -> GenResult<()> {
+    let source = Source::new("aspen:entry", selector);
+    let tokens = Lexer::tokenize(&source);
+    match tokens.as_slice() {
+        [atom, eof] if eof.kind == TokenKind::EOF => match atom.kind {
+            TokenKind::NullaryAtom(_) => Ok(()),
+            _ => Err(GenError::InvalidEntrySelector(selector.into())),
+        },
+        _ => Err(GenError::InvalidEntrySelector(selector.into())),
+    }
+}
+
 pub struct Generator<'ctx> {
     host: Host,
     context: &'ctx Context,
+    workers: Option<usize>,
+    check_headers: bool,
 
     pub void_type: VoidType<'ctx>,
     pub void_ptr_type: PointerType<'ctx>,
     pub bool_type: IntType<'ctx>,
+    pub c_int_type: IntType<'ctx>,
 
     pub isize_type: IntType<'ctx>,
     pub i128_type: IntType<'ctx>,
@@ -58,6 +85,8 @@ impl<'ctx> Generator<'ctx> {
 
         let bool_type = context.bool_type();
 
+        let c_int_type = context.i32_type();
+
         let i128_type = context.i128_type();
 
         #[cfg(target_pointer_width = "32")]
@@ -147,10 +176,13 @@ impl<'ctx> Generator<'ctx> {
         Generator {
             host,
             context,
+            workers: None,
+            check_headers: false,
 
             void_type,
             void_ptr_type,
             bool_type,
+            c_int_type,
 
             isize_type,
             i128_type,
@@ -179,6 +211,48 @@ impl<'ctx> Generator<'ctx> {
         }
     }
 
+    /// Starts runtimes with exactly `workers` worker threads rather
+    /// than one for each CPU but one.
+    pub fn with_workers(mut self, workers: Option<usize>) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Checks references to objects declared in other modules against
+    /// the headers written when those modules were compiled, so that a
+    /// module is never linked against an object file it doesn't match.
+    /// The headers must be written to the context beforehand.
+    pub fn with_headers_checked(mut self) -> Self {
+        self.check_headers = true;
+        self
+    }
+
+    fn new_runtime(&self, intrinsics: &Intrinsics<'ctx>, builder: &Builder<'ctx>) -> PointerValue<'ctx> {
+        match self.workers {
+            None => intrinsics.new_runtime(builder),
+            Some(workers) => intrinsics.new_runtime_with_workers(
+                builder,
+                self.isize_type.const_int(workers as u64, false),
+            ),
+        }
+    }
+
+    fn start_runtime(
+        &self,
+        intrinsics: &Intrinsics<'ctx>,
+        builder: &Builder<'ctx>,
+        start_fn: FunctionValue<'ctx>,
+    ) {
+        match self.workers {
+            None => intrinsics.start_runtime(builder, start_fn),
+            Some(workers) => intrinsics.start_runtime_with_workers(
+                builder,
+                start_fn,
+                self.isize_type.const_int(workers as u64, false),
+            ),
+        }
+    }
+
     pub fn generate_live_init(&self) -> GenResult<EmittedModule<'ctx>> {
         let module = self.context.create_module("live_init");
         let rt_global = module.add_global(self.rt_ptr_type, Some(AddressSpace::Generic), "RUNTIME");
@@ -196,15 +270,36 @@ impl<'ctx> Generator<'ctx> {
 
         builder.build_store(
             rt_global.as_pointer_value(),
-            intrinsics.new_runtime(&builder),
+            self.new_runtime(&intrinsics, &builder),
         );
 
         builder.build_return(None);
 
+        // Stops the runtime when the session is over, which is looked up
+        // by name since it isn't run when the module is evaluated.
+        let exit_fn = module.add_function(LIVE_EXIT_FN, self.void_type.fn_type(&[], false), None);
+        let exit_block = self.context.append_basic_block(exit_fn, "entry");
+        builder.position_at_end(exit_block);
+        let rt = builder
+            .build_load(rt_global.as_pointer_value(), "rt")
+            .into_pointer_value();
+        intrinsics.stop_runtime(&builder, rt);
+        builder.build_return(None);
+
         Ok(EmittedModule::new_executable(module, intrinsics, init_fn))
     }
 
     pub fn generate_main<'a>(&'a self, main: &str) -> GenResult<EmittedModule<'ctx>> {
+        self.generate_main_with_entry(main, DEFAULT_ENTRY_SELECTOR)
+    }
+
+    /// Generates a `main` function that starts the program by telling
+    /// `entry` to the main object.
+    pub fn generate_main_with_entry<'a>(
+        &'a self,
+        main: &str,
+        entry: &str,
+    ) -> GenResult<EmittedModule<'ctx>> {
         let main = match block_on(self.host.find_declaration(main)) {
             None => return Err(GenError::InvalidMainObject(format!("`{}` is not defined", main))),
             Some(m) => m,
@@ -221,11 +316,22 @@ impl<'ctx> Generator<'ctx> {
 
         let start_fn = module.add_function("start", self.start_fn_type, None);
 
-        let main_fn = module.add_function("main", self.void_type.fn_type(&[], false), None);
+        let argv_type = self.string_ptr_type.ptr_type(AddressSpace::Generic);
+        let main_fn = module.add_function(
+            "main",
+            self.c_int_type
+                .fn_type(&[self.c_int_type.into(), argv_type.into()], false),
+            None,
+        );
         let entry_block = self.context.append_basic_block(main_fn, "entry");
         builder.position_at_end(entry_block);
-        intrinsics.start_runtime(&builder, start_fn);
-        builder.build_return(None);
+        intrinsics.set_args(
+            &builder,
+            main_fn.get_nth_param(0).unwrap().into_int_value(),
+            main_fn.get_nth_param(1).unwrap().into_pointer_value(),
+        );
+        self.start_runtime(&intrinsics, &builder, start_fn);
+        builder.build_return(Some(&self.c_int_type.const_zero()));
 
         let entry_block = self.context.append_basic_block(start_fn, "entry");
         builder.position_at_end(entry_block);
@@ -251,7 +357,7 @@ impl<'ctx> Generator<'ctx> {
         intrinsics.tell(
             &builder,
             main_object_ptr,
-            intrinsics.new_atom(&builder, "run!"),
+            intrinsics.new_atom(&builder, entry),
         );
         intrinsics.drop(&builder, main_object);
         builder.build_return(None);
@@ -265,20 +371,47 @@ impl<'ctx> Generator<'ctx> {
     ) -> GenResult<EmittedModule<'ctx>> {
         let module_gen = self.create_module(module);
 
-        match module_gen.generate_module()? {
-            None => {
-                Ok(EmittedModule::new(module_gen.module, module_gen.intrinsics))
-            }
+        let emitted = match module_gen.generate_module()? {
+            None => EmittedModule::new(module_gen.module, module_gen.intrinsics),
             Some(fun) => {
-                Ok(EmittedModule::new_executable(
-                    module_gen.module,
-                    module_gen.intrinsics,
-                    fun,
-                ))
+                EmittedModule::new_executable(module_gen.module, module_gen.intrinsics, fun)
             }
+        };
+        Ok(emitted.with_source_map(module_gen.source_map.into_inner()))
+    }
+
+    /// Describes the objects exported by the module, as they will be
+    /// emitted by [`generate_module`](Generator::generate_module).
+    pub fn generate_header(&self, module: &Arc<HostModule>) -> ModuleHeader {
+        let signature = self.constructor_fn_type.print_to_string().to_string();
+        ModuleHeader {
+            uri: module.uri().uri().into(),
+            objects: block_on(module.exported_declarations())
+                .into_iter()
+                .map(|(name, declaration)| match declaration.as_ref() {
+                    syntax::Declaration::Object(o) => ObjectHeader {
+                        name,
+                        constructor: ModuleGenerator::constructor_fn_name(o),
+                        signature: signature.clone(),
+                    },
+                })
+                .collect(),
         }
     }
 
+    /// The header that the module with the URI was compiled with.
+    fn read_header(&self, uri: &URI) -> GenResult<ModuleHeader> {
+        let path = self.host.context.header_file_path(uri)?;
+        ModuleHeader::read_blocking(&path).map_err(|error| {
+            GenError::HeaderMismatch(format!(
+                "Couldn't read the header of {} from {}: {}",
+                uri,
+                path.display(),
+                error
+            ))
+        })
+    }
+
     fn create_module<'mdl>(
         &'mdl self,
         host_module: &'mdl Arc<HostModule>,
@@ -291,6 +424,7 @@ impl<'ctx> Generator<'ctx> {
             module,
             intrinsics,
             host_module,
+            source_map: RefCell::new(vec![]),
         }
     }
 }
@@ -300,6 +434,8 @@ struct ModuleGenerator<'ctx: 'mdl, 'mdl> {
     module: Module<'ctx>,
     intrinsics: Intrinsics<'ctx>,
     host_module: &'mdl Arc<HostModule>,
+    /// The range of code that each generated function came from.
+    source_map: RefCell<Vec<(String, URI, Range)>>,
 }
 
 impl<'ctx: 'mdl, 'mdl> ModuleGenerator<'ctx, 'mdl> {
@@ -308,15 +444,30 @@ impl<'ctx: 'mdl, 'mdl> ModuleGenerator<'ctx, 'mdl> {
         name: &str,
         ty: FunctionType<'ctx>,
         linkage: Option<Linkage>,
+        origin: Range,
     ) -> FunctionGenerator<'ctx, 'mdl, 'fun> {
+        let function = self
+            .module
+            .get_function(name)
+            .unwrap_or_else(|| self.module.add_function(name, ty, linkage));
+        self.map_to_source(function, origin);
         FunctionGenerator {
             module: self,
-            function: self.module.get_function(name).unwrap_or_else(|| self.module.add_function(name, ty, linkage)),
+            function,
             rt_reference: None,
             self_reference: None,
+            bindings: vec![],
         }
     }
 
+    fn map_to_source(&self, function: FunctionValue<'ctx>, origin: Range) {
+        self.source_map.borrow_mut().push((
+            function.get_name().to_string_lossy().into_owned(),
+            self.host_module.uri().clone(),
+            origin,
+        ));
+    }
+
     fn generate_module(&self) -> GenResult<Option<FunctionValue<'ctx>>> {
         let root = self.host_module.syntax_tree();
 
@@ -351,7 +502,12 @@ impl<'ctx: 'mdl, 'mdl> ModuleGenerator<'ctx, 'mdl> {
             }
             syntax::Inline::Expression(e, _) => {
                 let mut function =
-                    self.create_function("Inline", self.global.void_type.fn_type(&[], false), None);
+                    self.create_function(
+                        "Inline",
+                        self.global.void_type.fn_type(&[], false),
+                        None,
+                        e.range(),
+                    );
 
                 let rt = self.module.add_global(
                     self.global.rt_ptr_type,
@@ -371,7 +527,7 @@ impl<'ctx: 'mdl, 'mdl> ModuleGenerator<'ctx, 'mdl> {
                 function.set_rt_reference(rt);
 
                 let mut inline_recv =
-                    self.create_function("Inline::Recv", self.global.recv_fn_type, None);
+                    self.create_function("Inline::Recv", self.global.recv_fn_type, None, e.range());
                 inline_recv.with_rt_reference_in_first_parameter();
                 inline_recv.with_self_reference_in_second_parameter();
 
@@ -406,19 +562,19 @@ impl<'ctx: 'mdl, 'mdl> ModuleGenerator<'ctx, 'mdl> {
     }
 
     fn constructor_fn_name(declaration: &Arc<syntax::ObjectDeclaration>) -> String {
-        format!("{}::New", declaration.symbol())
+        mangle(declaration.source().uri(), declaration.symbol(), "New")
     }
 
     fn init_fn_name(declaration: &Arc<syntax::ObjectDeclaration>) -> String {
-        format!("{}::Init", declaration.symbol())
+        mangle(declaration.source().uri(), declaration.symbol(), "Init")
     }
 
     fn recv_fn_name(declaration: &Arc<syntax::ObjectDeclaration>) -> String {
-        format!("{}::Recv", declaration.symbol())
+        mangle(declaration.source().uri(), declaration.symbol(), "Recv")
     }
 
     fn drop_fn_name(declaration: &Arc<syntax::ObjectDeclaration>) -> String {
-        format!("{}::Drop", declaration.symbol())
+        mangle(declaration.source().uri(), declaration.symbol(), "Drop")
     }
 
     fn generate_object_declaration(
@@ -432,7 +588,12 @@ impl<'ctx: 'mdl, 'mdl> ModuleGenerator<'ctx, 'mdl> {
 
         let init_fn = {
             let mut init_fn =
-                self.create_function(init_fn_name.as_ref(), self.global.init_fn_type, None);
+                self.create_function(
+                    init_fn_name.as_ref(),
+                    self.global.init_fn_type,
+                    None,
+                    declaration.range(),
+                );
             init_fn.with_rt_reference_in_first_parameter();
             init_fn.with_self_reference_in_second_parameter();
             init_fn.generate_initializer(declaration)?;
@@ -441,7 +602,12 @@ impl<'ctx: 'mdl, 'mdl> ModuleGenerator<'ctx, 'mdl> {
 
         let recv_fn = {
             let mut recv_fn =
-                self.create_function(recv_fn_name.as_ref(), self.global.recv_fn_type, None);
+                self.create_function(
+                    recv_fn_name.as_ref(),
+                    self.global.recv_fn_type,
+                    None,
+                    declaration.range(),
+                );
             recv_fn.with_rt_reference_in_first_parameter();
             recv_fn.with_self_reference_in_second_parameter();
             recv_fn.generate_receiver(declaration)?;
@@ -450,7 +616,12 @@ impl<'ctx: 'mdl, 'mdl> ModuleGenerator<'ctx, 'mdl> {
 
         let drop_fn = {
             let mut drop_fn =
-                self.create_function(drop_fn_name.as_ref(), self.global.drop_fn_type, None);
+                self.create_function(
+                    drop_fn_name.as_ref(),
+                    self.global.drop_fn_type,
+                    None,
+                    declaration.range(),
+                );
             drop_fn.with_rt_reference_in_first_parameter();
             drop_fn.generate_destructor(declaration)?;
             drop_fn.function.as_global_value().as_pointer_value()
@@ -461,6 +632,7 @@ impl<'ctx: 'mdl, 'mdl> ModuleGenerator<'ctx, 'mdl> {
                 constructor_fn_name.as_ref(),
                 self.global.constructor_fn_type,
                 Some(Linkage::External),
+                declaration.range(),
             );
             constructor_fn.with_rt_reference_in_first_parameter();
             constructor_fn.generate_constructor(init_fn, recv_fn, drop_fn, declaration)?;
@@ -475,6 +647,9 @@ struct FunctionGenerator<'ctx: 'mdl, 'mdl: 'fun, 'fun> {
     function: FunctionValue<'ctx>,
     rt_reference: Option<PointerValue<'ctx>>,
     self_reference: Option<PointerValue<'ctx>>,
+    /// The values bound by the statements generated so far in this
+    /// function. They don't survive into continuations.
+    bindings: Vec<(Arc<syntax::Statement>, PointerValue<'ctx>)>,
 }
 
 impl<'ctx: 'mdl, 'mdl: 'fun, 'fun> FunctionGenerator<'ctx, 'mdl, 'fun> {
@@ -495,7 +670,18 @@ impl<'ctx: 'mdl, 'mdl: 'fun, 'fun> FunctionGenerator<'ctx, 'mdl, 'fun> {
             syntax::Expression::Answer(a) => {
                 self.generate_reply(builder, &a.expression, reply_handling)
             }
-            _ => unimplemented!("expression {:?}", expression),
+            syntax::Expression::SelfReference(_) => {
+                Ok(Some(self.generate_self_reference(builder)?))
+            }
+            syntax::Expression::Sender(_) => Ok(Some(self.generate_sender_reference(builder)?)),
+            syntax::Expression::Float(_) => Err(GenError::Unsupported(
+                "Float expressions".into(),
+                expression.range(),
+            )),
+            syntax::Expression::NullaryAtom(_) => Err(GenError::Unsupported(
+                "Atom expressions".into(),
+                expression.range(),
+            )),
         }
     }
 
@@ -601,7 +787,7 @@ impl<'ctx: 'mdl, 'mdl: 'fun, 'fun> FunctionGenerator<'ctx, 'mdl, 'fun> {
                         let message = builder.build_load(message, "message").into_struct_value();
                         match reply_handling {
                             ReplyHandling::Sync => {
-                                let cont_fn: FunctionGenerator<'ctx, 'mdl, 'fun> = self.create_continuation();
+                                let cont_fn: FunctionGenerator<'ctx, 'mdl, 'fun> = self.create_continuation(send.range());
                                 cont_fn.function.get_nth_param(0).unwrap().set_name("rt");
                                 cont_fn.function.get_nth_param(1).unwrap().set_name("self");
                                 cont_fn.function.get_nth_param(2).unwrap().set_name("state");
@@ -653,6 +839,7 @@ impl<'ctx: 'mdl, 'mdl: 'fun, 'fun> FunctionGenerator<'ctx, 'mdl, 'fun> {
                                     self.module.global.drop_fn_type,
                                     None,
                                 );
+                                self.module.map_to_source(drop_fn, send.range());
                                 {
                                     let entry_block = self
                                         .module
@@ -682,9 +869,10 @@ impl<'ctx: 'mdl, 'mdl: 'fun, 'fun> FunctionGenerator<'ctx, 'mdl, 'fun> {
                                 let _frame_ptr = builder
                                     .build_load(frame_ptr_ptr.into_pointer_value(), "frame_ptr");
 
-                                self.module.intrinsics.ask(
+                                self.module.intrinsics.ask_from(
                                     builder,
                                     receiver,
+                                    self.self_reference.unwrap(),
                                     continuation.into(),
                                     message.into(),
                                 );
@@ -702,7 +890,13 @@ impl<'ctx: 'mdl, 'mdl: 'fun, 'fun> FunctionGenerator<'ctx, 'mdl, 'fun> {
                                 Ok(Some(message_ptr))
                             }
                             ReplyHandling::Async => {
-                                self.module.intrinsics.tell(builder, receiver, message);
+                                match self.self_reference {
+                                    Some(sender) => self
+                                        .module
+                                        .intrinsics
+                                        .tell_from(builder, receiver, sender, message),
+                                    None => self.module.intrinsics.tell(builder, receiver, message),
+                                }
                                 Ok(None)
                             }
                         }
@@ -712,16 +906,19 @@ impl<'ctx: 'mdl, 'mdl: 'fun, 'fun> FunctionGenerator<'ctx, 'mdl, 'fun> {
         }
     }
 
-    fn create_continuation(&self) -> FunctionGenerator<'ctx, 'mdl, 'fun> {
+    fn create_continuation(&self, origin: Range) -> FunctionGenerator<'ctx, 'mdl, 'fun> {
+        let function = self.module.module.add_function(
+            self.function.get_name().to_str().unwrap(),
+            self.module.global.cont_fn_type,
+            None,
+        );
+        self.module.map_to_source(function, origin);
         let mut gen = FunctionGenerator {
             module: &self.module,
-            function: self.module.module.add_function(
-                self.function.get_name().to_str().unwrap(),
-                self.module.global.cont_fn_type,
-                None,
-            ),
+            function,
             rt_reference: None,
             self_reference: None,
+            bindings: vec![],
         };
         gen.with_rt_reference_in_first_parameter();
         gen.with_self_reference_in_second_parameter();
@@ -753,29 +950,72 @@ impl<'ctx: 'mdl, 'mdl: 'fun, 'fun> FunctionGenerator<'ctx, 'mdl, 'fun> {
         }
     }
 
+    fn generate_self_reference(&self, builder: &Builder<'ctx>) -> GenResult<PointerValue<'ctx>> {
+        let self_reference = self.self_reference.ok_or(GenError::BadNode)?;
+        let self_ptr = builder.build_alloca(self.module.global.object_ptr_type, "self_ptr");
+        builder.build_store(
+            self_ptr,
+            self.module.intrinsics.clone(builder, self_reference),
+        );
+        Ok(self_ptr)
+    }
+
+    fn generate_sender_reference(&self, builder: &Builder<'ctx>) -> GenResult<PointerValue<'ctx>> {
+        let self_reference = self.self_reference.ok_or(GenError::BadNode)?;
+        let sender_ptr = builder.build_alloca(self.module.global.object_ptr_type, "sender_ptr");
+        builder.build_store(
+            sender_ptr,
+            self.module.intrinsics.sender(builder, self_reference),
+        );
+        Ok(sender_ptr)
+    }
+
     fn generate_reference_expression(
         &self,
         builder: &Builder<'ctx>,
         expression: &Arc<syntax::ReferenceExpression>,
     ) -> GenResult<PointerValue<'ctx>> {
+        if let Some(statement) = self.module.host_module.binding_referenced_by(expression) {
+            return self.generate_binding_reference(builder, &statement, expression);
+        }
+
         let declaration = block_on(
             self.module
                 .host_module
                 .declaration_referenced_by(expression.clone()),
         );
-        if declaration.is_none() {
-            return Err(GenError::BadNode);
-        }
-        let declaration = declaration.unwrap();
+        let declaration = declaration.ok_or_else(|| {
+            GenError::UndefinedReference(
+                expression.symbol.identifier.lexeme().into(),
+                expression.range(),
+            )
+        })?;
 
         match declaration.as_ref() {
             syntax::Declaration::Object(o) => {
                 let constructor_fn_name = ModuleGenerator::constructor_fn_name(o);
-                let constructor = self.module.module.add_function(
-                    constructor_fn_name.as_ref(),
-                    self.module.global.constructor_fn_type,
-                    Some(Linkage::External),
-                );
+                let constructor_fn_type = self.module.global.constructor_fn_type;
+                let signature = constructor_fn_type.print_to_string().to_string();
+                let declaring_uri = declaration.source().uri();
+                if self.module.global.check_headers && declaring_uri != self.module.host_module.uri()
+                {
+                    self.module.global.read_header(declaring_uri)?.validate(
+                        o.symbol(),
+                        constructor_fn_name.as_ref(),
+                        signature.as_ref(),
+                    )?;
+                }
+                let constructor = self
+                    .module
+                    .module
+                    .get_function(constructor_fn_name.as_ref())
+                    .unwrap_or_else(|| {
+                        self.module.module.add_function(
+                            constructor_fn_name.as_ref(),
+                            constructor_fn_type,
+                            Some(Linkage::External),
+                        )
+                    });
                 let object_ptr =
                     builder.build_alloca(self.module.global.object_ptr_type, "object_ptr");
                 let (opt0, opt1) = self
@@ -904,6 +1144,7 @@ impl<'ctx: 'mdl, 'mdl: 'fun, 'fun> FunctionGenerator<'ctx, 'mdl, 'fun> {
         for statement in method.statements.iter() {
             self.generate_statement(builder, statement)?;
         }
+        self.bindings.clear();
 
         builder.build_unconditional_branch(exit_block);
 
@@ -928,10 +1169,41 @@ impl<'ctx: 'mdl, 'mdl: 'fun, 'fun> FunctionGenerator<'ctx, 'mdl, 'fun> {
         builder: &Builder<'ctx>,
         statement: &Arc<syntax::Statement>,
     ) -> GenResult<()> {
-        self.generate_expression(builder, &statement.expression, ReplyHandling::Async)?;
+        if statement.binding.is_none() {
+            self.generate_expression(builder, &statement.expression, ReplyHandling::Async)?;
+            return Ok(());
+        }
+
+        let value = self
+            .generate_expression(builder, &statement.expression, ReplyHandling::Sync)?
+            .ok_or(GenError::BadNode)?;
+        self.bindings.push((statement.clone(), value));
         Ok(())
     }
 
+    fn generate_binding_reference(
+        &self,
+        builder: &Builder<'ctx>,
+        statement: &Arc<syntax::Statement>,
+        reference: &Arc<syntax::ReferenceExpression>,
+    ) -> GenResult<PointerValue<'ctx>> {
+        let value = self
+            .bindings
+            .iter()
+            .find(|(s, _)| Arc::ptr_eq(s, statement))
+            .map(|(_, value)| *value)
+            .ok_or_else(|| {
+                GenError::Unsupported(
+                    "Bindings used after waiting for a reply".into(),
+                    reference.range(),
+                )
+            })?;
+
+        let binding_ptr = builder.build_alloca(self.module.global.object_ptr_type, "binding_ptr");
+        builder.build_store(binding_ptr, self.module.intrinsics.clone(builder, value));
+        Ok(binding_ptr)
+    }
+
     fn generate_pattern_matcher(
         &self,
         builder: &Builder<'ctx>,
@@ -942,7 +1214,18 @@ impl<'ctx: 'mdl, 'mdl: 'fun, 'fun> FunctionGenerator<'ctx, 'mdl, 'fun> {
                 .module
                 .intrinsics
                 .eq_int(builder, self.generate_integer_literal(i)?)),
-            p => unimplemented!("pattern matcher for pattern {:?}", p),
+            syntax::Pattern::Nullary(_) => Err(GenError::Unsupported(
+                "Atom patterns".into(),
+                pattern.range(),
+            )),
+            syntax::Pattern::Or(alternatives) => {
+                let mut matcher = self.generate_pattern_matcher(builder, &alternatives[0])?;
+                for alternative in alternatives[1..].iter() {
+                    let alternative = self.generate_pattern_matcher(builder, alternative)?;
+                    matcher = self.module.intrinsics.any_of(builder, matcher, alternative);
+                }
+                Ok(matcher)
+            }
         }
     }
 
@@ -959,3 +1242,193 @@ enum ReplyHandling {
     Sync,
     Async,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context as AspenContext, Source};
+
+    #[tokio::test]
+    async fn unsupported_expression() {
+        let host = Host::new(Arc::new(AspenContext::test()));
+        let module = host
+            .set(Source::new("test:x", "object X { 1 -> 1.5. }"))
+            .await;
+
+        let context = Context::create();
+        let generator = Generator::new(host, &context);
+
+        match generator.generate_module(&module) {
+            Err(GenError::Unsupported(what, _)) => assert_eq!(what, "Float expressions"),
+            Err(e) => panic!("expected an unsupported expression error, got {:?}", e),
+            Ok(_) => panic!("expected an unsupported expression error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn alternative_patterns_combine_matchers() {
+        let host = Host::new(Arc::new(AspenContext::test()));
+        let module = host
+            .set(Source::new("test:x", "object X { 1 | 2 | 3 -> 4. }"))
+            .await;
+
+        let context = Context::create();
+        let generator = Generator::new(host, &context);
+        let emitted = generator.generate_module(&module).unwrap();
+
+        emitted.verify().unwrap();
+        assert!(format!("{:?}", emitted).contains("AspenAnyOf"));
+    }
+
+    #[tokio::test]
+    async fn main_is_started_with_the_entry_selector() {
+        let host = Host::new(Arc::new(AspenContext::test()));
+        host.set(Source::new("test:x", "object Main.")).await;
+
+        let context = Context::create();
+        let generator = Generator::new(host, &context);
+        let emitted = generator
+            .generate_main_with_entry("Main", "start!")
+            .unwrap();
+
+        let ir = format!("{:?}", emitted);
+        assert!(ir.contains("start!"));
+        assert!(!ir.contains("run!"));
+    }
+
+    #[test]
+    fn entry_selectors_must_be_atoms() {
+        assert!(validate_entry_selector("run!").is_ok());
+        assert!(validate_entry_selector("start?").is_ok());
+        for invalid in &["start", "1", "start! run!", ""] {
+            match validate_entry_selector(invalid) {
+                Err(GenError::InvalidEntrySelector(s)) => assert_eq!(s, *invalid),
+                _ => panic!("expected {:?} to be rejected", invalid),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn source_map_links_functions_to_declarations() {
+        let host = Host::new(Arc::new(AspenContext::test()));
+        let module = host
+            .set(Source::new("test:x", "object X. object Y { 1 -> 2. }"))
+            .await;
+        let y = module.navigate().all_declarations().nth(1).unwrap();
+
+        let context = Context::create();
+        let generator = Generator::new(host, &context);
+        let emitted = generator.generate_module(&module).unwrap();
+
+        let (_, uri, range) = emitted
+            .source_map()
+            .iter()
+            .find(|(symbol, _, _)| symbol.ends_with("::Y::Recv"))
+            .unwrap();
+        assert_eq!(uri, module.uri());
+        assert!(*range == y.range());
+    }
+
+    #[tokio::test]
+    async fn statements_use_earlier_bindings() {
+        let host = Host::new(Arc::new(AspenContext::test()));
+        let module = host
+            .set(Source::new("test:x", "object X { 1 -> x = 2. x 3. }"))
+            .await;
+        assert!(module.diagnostics().await.is_empty());
+
+        let context = Context::create();
+        let generator = Generator::new(host, &context);
+        let emitted = generator.generate_module(&module).unwrap();
+
+        emitted.verify().unwrap();
+        assert!(format!("{:?}", emitted).contains("binding_ptr"));
+    }
+
+    #[tokio::test]
+    async fn self_send() {
+        let host = Host::new(Arc::new(AspenContext::test()));
+        let module = host
+            .set(Source::new("test:x", "object X { 1 -> self 2. 2 -> 3. }"))
+            .await;
+
+        let context = Context::create();
+        let generator = Generator::new(host, &context);
+        let emitted = generator.generate_module(&module).unwrap();
+
+        emitted.verify().unwrap();
+        assert!(emitted
+            .module
+            .get_function(mangle(&"test:x".into(), "X", "Recv").as_ref())
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn generating_twice_yields_identical_names() {
+        let host = Host::new(Arc::new(AspenContext::test()));
+        let module = host
+            .set(Source::new(
+                "test:x",
+                "object X { 1 -> self 2. 2 -> 3. } object Y { 1 -> X 2. }",
+            ))
+            .await;
+
+        let generate = || {
+            let context = Context::create();
+            let generator = Generator::new(host.clone(), &context);
+            generator
+                .generate_module(&module)
+                .unwrap()
+                .module
+                .print_to_string()
+                .to_string()
+        };
+
+        assert_eq!(generate(), generate());
+    }
+
+    #[tokio::test]
+    async fn same_named_objects_in_different_modules() {
+        let host = Host::new(Arc::new(AspenContext::test()));
+        let a = host.set(Source::new("test:a", "object Foo.")).await;
+        let b = host.set(Source::new("test:b", "object Foo.")).await;
+
+        let context = Context::create();
+        let generator = Generator::new(host, &context);
+        let a_header = generator.generate_header(&a);
+        let b_header = generator.generate_header(&b);
+
+        assert_eq!(a_header.objects[0].name, b_header.objects[0].name);
+        assert_ne!(
+            a_header.objects[0].constructor,
+            b_header.objects[0].constructor
+        );
+
+        let emitted = generator.generate_module(&a).unwrap();
+        assert!(emitted
+            .module
+            .get_function(a_header.objects[0].constructor.as_ref())
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn answer_tells_the_reply_to_actor() {
+        let host = Host::new(Arc::new(AspenContext::test()));
+        let module = host
+            .set(Source::new("test:x", "object X { 1 -> ^2. }"))
+            .await;
+
+        let context = Context::create();
+        let generator = Generator::new(host, &context);
+        let emitted = generator.generate_module(&module).unwrap();
+
+        emitted.verify().unwrap();
+        let recv = emitted
+            .module
+            .get_function(mangle(&"test:x".into(), "X", "Recv").as_ref())
+            .unwrap()
+            .print_to_string()
+            .to_string();
+        assert!(recv.contains("@AspenTell"));
+    }
+}
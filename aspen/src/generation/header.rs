@@ -0,0 +1,170 @@
+use crate::generation::{GenError, GenResult};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+
+/// The interface of a compiled module, written next to its object
+/// file so that other modules can be checked against it without
+/// having to be compiled together.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModuleHeader {
+    pub uri: String,
+    pub objects: Vec<ObjectHeader>,
+}
+
+/// An exported object, with the symbol and LLVM type of the
+/// constructor that instantiates it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ObjectHeader {
+    pub name: String,
+    pub constructor: String,
+    pub signature: String,
+}
+
+impl ModuleHeader {
+    pub fn object(&self, name: &str) -> Option<&ObjectHeader> {
+        self.objects.iter().find(|o| o.name == name)
+    }
+
+    /// Checks that the module exports an object with the given
+    /// constructor symbol and signature.
+    pub fn validate(&self, name: &str, constructor: &str, signature: &str) -> GenResult<()> {
+        match self.object(name) {
+            None => Err(GenError::HeaderMismatch(format!(
+                "`{}` is not exported by {}",
+                name, self.uri
+            ))),
+            Some(o) if o.constructor != constructor || o.signature != signature => {
+                Err(GenError::HeaderMismatch(format!(
+                    "`{}` in {} is constructed by `{}: {}`, not `{}: {}`",
+                    name, self.uri, o.constructor, o.signature, constructor, signature
+                )))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    pub async fn read(path: &Path) -> io::Result<ModuleHeader> {
+        Self::from_json(tokio::fs::read(path).await?.as_slice())
+    }
+
+    /// Like `read`, for callers that are already on a blocking thread,
+    /// like the generator.
+    pub fn read_blocking(path: &Path) -> io::Result<ModuleHeader> {
+        Self::from_json(std::fs::read(path)?.as_slice())
+    }
+
+    pub async fn write(&self, path: &Path) -> io::Result<()> {
+        tokio::fs::write(path, self.to_json()?).await
+    }
+
+    fn from_json(json: &[u8]) -> io::Result<ModuleHeader> {
+        Ok(serde_json::from_slice(json)?)
+    }
+
+    fn to_json(&self) -> io::Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::{mangle, Executable, Generator, ObjectFile};
+    use crate::semantics::Host;
+    use crate::{Context, Source, URI};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn building_a_module_writes_its_header() {
+        let context =
+            Arc::new(Context::temporary_in(None, std::env::current_dir().unwrap()).unwrap());
+        let host = Host::new(context.clone());
+        let uri = URI::file(std::env::current_dir().unwrap().join("header.aspen"));
+        host.set(Source::new(uri.clone(), "object A. object B."))
+            .await;
+
+        let temp = mktemp::Temp::new_dir().unwrap();
+        Executable::build(host)
+            .write_objects(temp.to_path_buf())
+            .await
+            .unwrap();
+
+        let header = ModuleHeader::read(&context.header_file_path(&uri).unwrap())
+            .await
+            .unwrap();
+        let names: Vec<_> = header.objects.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, vec!["A", "B"]);
+        assert_eq!(header.objects[0].constructor, mangle(&uri, "A", "New"));
+    }
+
+    #[tokio::test]
+    async fn references_are_checked_against_the_header_on_disk() {
+        let dir = std::env::current_dir().unwrap();
+        let context = Arc::new(Context::temporary_in(None, dir.clone()).unwrap());
+        let host = Host::new(context.clone());
+        let a_uri = URI::file(dir.join("stale_header_a.aspen"));
+        let b_uri = URI::file(dir.join("stale_header_b.aspen"));
+        host.set(Source::new(a_uri.clone(), "object A.")).await;
+        let b = host
+            .set(Source::new(b_uri.clone(), "object B { 1 -> A. }"))
+            .await;
+
+        let generate = |b: Arc<crate::semantics::Module>| {
+            let host = host.clone();
+            tokio::task::spawn_blocking(move || {
+                let context = inkwell::context::Context::create();
+                Generator::new(host, &context)
+                    .with_headers_checked()
+                    .generate_module(&b)
+                    .map(|_| ())
+            })
+        };
+
+        let header_path = context.header_file_path(&a_uri).unwrap();
+        tokio::fs::remove_file(&header_path).await.unwrap_or(());
+        match generate(b.clone()).await.unwrap() {
+            Err(GenError::HeaderMismatch(_)) => {}
+            other => panic!("expected a missing header to be rejected, got {:?}", other),
+        }
+
+        tokio::fs::create_dir_all(header_path.parent().unwrap())
+            .await
+            .unwrap();
+        ModuleHeader {
+            uri: a_uri.uri().into(),
+            objects: vec![ObjectHeader {
+                name: "A".into(),
+                constructor: "stale".into(),
+                signature: "stale".into(),
+            }],
+        }
+        .write(&header_path)
+        .await
+        .unwrap();
+        match generate(b.clone()).await.unwrap() {
+            Err(GenError::HeaderMismatch(_)) => {}
+            other => panic!("expected a stale header to be rejected, got {:?}", other),
+        }
+
+        let a = host.get(&a_uri).await.unwrap();
+        ObjectFile::write_header(&a).await.unwrap();
+        assert!(generate(b).await.unwrap().is_ok());
+    }
+
+    #[test]
+    fn validation() {
+        let header = ModuleHeader {
+            uri: "test:x".into(),
+            objects: vec![ObjectHeader {
+                name: "A".into(),
+                constructor: "A::New".into(),
+                signature: "sig".into(),
+            }],
+        };
+
+        assert!(header.validate("A", "A::New", "sig").is_ok());
+        assert!(header.validate("A", "A::New", "other").is_err());
+        assert!(header.validate("B", "B::New", "sig").is_err());
+    }
+}
@@ -11,7 +11,13 @@ use inkwell::AddressSpace;
 #[allow(non_snake_case)]
 pub struct Intrinsics<'ctx> {
     AspenNewRuntime: FunctionValue<'ctx>,
+    AspenNewRuntimeWithWorkers: FunctionValue<'ctx>,
     AspenStartRuntime: FunctionValue<'ctx>,
+    AspenStartRuntimeWithWorkers: FunctionValue<'ctx>,
+    AspenStopRuntime: FunctionValue<'ctx>,
+    AspenSetArgs: FunctionValue<'ctx>,
+    AspenArgCount: FunctionValue<'ctx>,
+    AspenArg: FunctionValue<'ctx>,
     AspenPrint: FunctionValue<'ctx>,
     AspenNewInt: FunctionValue<'ctx>,
     AspenNewAtom: FunctionValue<'ctx>,
@@ -19,9 +25,18 @@ pub struct Intrinsics<'ctx> {
     AspenDrop: FunctionValue<'ctx>,
     AspenTell: FunctionValue<'ctx>,
     AspenAsk: FunctionValue<'ctx>,
+    AspenTellFrom: FunctionValue<'ctx>,
+    AspenAskFrom: FunctionValue<'ctx>,
+    AspenSender: FunctionValue<'ctx>,
     AspenNewActor: FunctionValue<'ctx>,
     AspenNewStatelessActor: FunctionValue<'ctx>,
     AspenEqInt: FunctionValue<'ctx>,
+    AspenIdentical: FunctionValue<'ctx>,
+    AspenNewPromise: FunctionValue<'ctx>,
+    AspenFulfill: FunctionValue<'ctx>,
+    AspenOnFulfill: FunctionValue<'ctx>,
+    AspenIdenticalTo: FunctionValue<'ctx>,
+    AspenAnyOf: FunctionValue<'ctx>,
     AspenMatch: FunctionValue<'ctx>,
     AspenDropMatcher: FunctionValue<'ctx>,
     AspenContinue: FunctionValue<'ctx>,
@@ -47,7 +62,19 @@ impl<'ctx> Intrinsics<'ctx> {
 
         signature! {
             AspenNewRuntime() -> generator.rt_ptr_type
+            AspenNewRuntimeWithWorkers(generator.isize_type) -> generator.rt_ptr_type
             AspenStartRuntime(generator.start_fn_ptr_type) -> generator.void_type
+            AspenStartRuntimeWithWorkers(
+                generator.start_fn_ptr_type,
+                generator.isize_type,
+            ) -> generator.void_type
+            AspenStopRuntime(generator.rt_ptr_type) -> generator.void_type
+            AspenSetArgs(
+                generator.c_int_type,
+                generator.string_ptr_type.ptr_type(AddressSpace::Generic),
+            ) -> generator.void_type
+            AspenArgCount() -> generator.isize_type
+            AspenArg(generator.isize_type) -> generator.object_ptr_type
             AspenPrint(generator.object_ptr_ref_type) -> generator.void_type
             AspenNewInt(generator.i128_type) -> generator.object_ptr_type
             AspenNewAtom(generator.string_ptr_type) -> generator.object_ptr_type
@@ -62,6 +89,18 @@ impl<'ctx> Intrinsics<'ctx> {
                 generator.opt0, generator.opt1,
                 generator.opt0, generator.opt1,
             ) -> generator.void_type
+            AspenTellFrom(
+                generator.object_ptr_ref_type,
+                generator.object_ptr_ref_type,
+                generator.opt0, generator.opt1,
+            ) -> generator.void_type
+            AspenAskFrom(
+                generator.object_ptr_ref_type,
+                generator.object_ptr_ref_type,
+                generator.opt0, generator.opt1,
+                generator.opt0, generator.opt1,
+            ) -> generator.void_type
+            AspenSender(generator.object_ptr_ref_type) -> generator.object_ptr_type
             AspenNewActor(
                 generator.rt_ptr_type,
                 generator.isize_type,
@@ -75,6 +114,18 @@ impl<'ctx> Intrinsics<'ctx> {
                 generator.recv_fn_ptr_type,
             ) -> generator.object_ptr_type
             AspenEqInt(generator.i128_type) -> generator.matcher_ptr_type
+            AspenIdentical(generator.object_ptr_ref_type, generator.object_ptr_ref_type) -> generator.bool_type
+            AspenIdenticalTo(generator.object_ptr_ref_type) -> generator.matcher_ptr_type
+            AspenAnyOf(generator.matcher_ptr_type, generator.matcher_ptr_type) -> generator.matcher_ptr_type
+            AspenNewPromise() -> generator.object_ptr_type
+            AspenFulfill(
+                generator.object_ptr_ref_type,
+                generator.opt0, generator.opt1,
+            ) -> generator.void_type
+            AspenOnFulfill(
+                generator.object_ptr_ref_type,
+                generator.opt0, generator.opt1,
+            ) -> generator.void_type
             AspenMatch(generator.matcher_ptr_type, generator.object_ptr_ref_type) -> generator.bool_type
             AspenDropMatcher(generator.matcher_ptr_type) -> generator.void_type
             AspenContinue(
@@ -99,7 +150,13 @@ impl<'ctx> Intrinsics<'ctx> {
 
         map! {
             AspenNewRuntime
+            AspenNewRuntimeWithWorkers
             AspenStartRuntime
+            AspenStartRuntimeWithWorkers
+            AspenStopRuntime
+            AspenSetArgs
+            AspenArgCount
+            AspenArg
             AspenPrint
             AspenNewInt
             AspenNewAtom
@@ -107,15 +164,34 @@ impl<'ctx> Intrinsics<'ctx> {
             AspenDrop
             AspenTell
             AspenAsk
+            AspenTellFrom
+            AspenAskFrom
+            AspenSender
             AspenNewActor
             AspenNewStatelessActor
             AspenEqInt
+            AspenIdentical
+            AspenIdenticalTo
+            AspenAnyOf
+            AspenNewPromise
+            AspenFulfill
+            AspenOnFulfill
             AspenMatch
             AspenDropMatcher
             AspenContinue
         }
     }
 
+    /// Makes the JIT compiled code print objects with `print` rather
+    /// than to stdout.
+    pub fn map_print_in_jit(
+        &self,
+        engine: &ExecutionEngine<'ctx>,
+        print: extern "C" fn(&aspenrt::ObjectRef),
+    ) {
+        engine.add_global_mapping(&self.AspenPrint, print as usize);
+    }
+
     pub fn new_runtime(&self, builder: &Builder<'ctx>) -> PointerValue<'ctx> {
         builder
             .build_call(self.AspenNewRuntime, &[], "rt")
@@ -125,6 +201,32 @@ impl<'ctx> Intrinsics<'ctx> {
             .into_pointer_value()
     }
 
+    pub fn new_runtime_with_workers(
+        &self,
+        builder: &Builder<'ctx>,
+        workers: IntValue<'ctx>,
+    ) -> PointerValue<'ctx> {
+        builder
+            .build_call(self.AspenNewRuntimeWithWorkers, &[workers.into()], "rt")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value()
+    }
+
+    pub fn stop_runtime(&self, builder: &Builder<'ctx>, rt: PointerValue<'ctx>) {
+        builder.build_call(self.AspenStopRuntime, &[rt.into()], "");
+    }
+
+    pub fn set_args(
+        &self,
+        builder: &Builder<'ctx>,
+        argc: IntValue<'ctx>,
+        argv: PointerValue<'ctx>,
+    ) {
+        builder.build_call(self.AspenSetArgs, &[argc.into(), argv.into()], "");
+    }
+
     pub fn start_runtime(&self, builder: &Builder<'ctx>, start_fn: FunctionValue<'ctx>) {
         builder.build_call(
             self.AspenStartRuntime,
@@ -133,6 +235,22 @@ impl<'ctx> Intrinsics<'ctx> {
         );
     }
 
+    pub fn start_runtime_with_workers(
+        &self,
+        builder: &Builder<'ctx>,
+        start_fn: FunctionValue<'ctx>,
+        workers: IntValue<'ctx>,
+    ) {
+        builder.build_call(
+            self.AspenStartRuntimeWithWorkers,
+            &[
+                start_fn.as_global_value().as_pointer_value().into(),
+                workers.into(),
+            ],
+            "",
+        );
+    }
+
     pub fn new_int(&self, builder: &Builder<'ctx>, int: IntValue<'ctx>) -> StructValue<'ctx> {
         builder
             .build_call(self.AspenNewInt, &[int.into()], "new_int")
@@ -203,6 +321,63 @@ impl<'ctx> Intrinsics<'ctx> {
         builder.build_call(self.AspenTell, &[receiver.into(), opt0, opt1], "");
     }
 
+    pub fn tell_from(
+        &self,
+        builder: &Builder<'ctx>,
+        receiver: PointerValue<'ctx>,
+        sender: PointerValue<'ctx>,
+        message: StructValue<'ctx>,
+    ) {
+        let (opt0, opt1) = self.split_object_ptr(builder, message);
+        builder.build_call(
+            self.AspenTellFrom,
+            &[receiver.into(), sender.into(), opt0, opt1],
+            "",
+        );
+    }
+
+    pub fn sender(
+        &self,
+        builder: &Builder<'ctx>,
+        self_ref: PointerValue<'ctx>,
+    ) -> StructValue<'ctx> {
+        builder
+            .build_call(self.AspenSender, &[self_ref.into()], "sender")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_struct_value()
+    }
+
+    pub fn new_promise(&self, builder: &Builder<'ctx>) -> StructValue<'ctx> {
+        builder
+            .build_call(self.AspenNewPromise, &[], "new_promise")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_struct_value()
+    }
+
+    pub fn fulfill(
+        &self,
+        builder: &Builder<'ctx>,
+        promise: PointerValue<'ctx>,
+        value: StructValue<'ctx>,
+    ) {
+        let (opt0, opt1) = self.split_object_ptr(builder, value);
+        builder.build_call(self.AspenFulfill, &[promise.into(), opt0, opt1], "");
+    }
+
+    pub fn on_fulfill(
+        &self,
+        builder: &Builder<'ctx>,
+        promise: PointerValue<'ctx>,
+        subscriber: StructValue<'ctx>,
+    ) {
+        let (opt0, opt1) = self.split_object_ptr(builder, subscriber);
+        builder.build_call(self.AspenOnFulfill, &[promise.into(), opt0, opt1], "");
+    }
+
     pub fn ask(
         &self,
         builder: &Builder<'ctx>,
@@ -220,6 +395,24 @@ impl<'ctx> Intrinsics<'ctx> {
         );
     }
 
+    pub fn ask_from(
+        &self,
+        builder: &Builder<'ctx>,
+        receiver: PointerValue<'ctx>,
+        sender: PointerValue<'ctx>,
+        reply_to: StructValue<'ctx>,
+        message: StructValue<'ctx>,
+    ) {
+        let (opt0, opt1) = self.split_object_ptr(builder, reply_to);
+        let (opt2, opt3) = self.split_object_ptr(builder, message);
+
+        builder.build_call(
+            self.AspenAskFrom,
+            &[receiver.into(), sender.into(), opt0, opt1, opt2, opt3],
+            "",
+        );
+    }
+
     pub fn new_actor(
         &self,
         builder: &Builder<'ctx>,
@@ -286,6 +479,49 @@ impl<'ctx> Intrinsics<'ctx> {
             .into_pointer_value()
     }
 
+    pub fn identical(
+        &self,
+        builder: &Builder<'ctx>,
+        a: PointerValue<'ctx>,
+        b: PointerValue<'ctx>,
+    ) -> IntValue<'ctx> {
+        builder
+            .build_call(self.AspenIdentical, &[a.into(), b.into()], "identical")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value()
+    }
+
+    pub fn identical_to(
+        &self,
+        builder: &Builder<'ctx>,
+        obj: PointerValue<'ctx>,
+    ) -> PointerValue<'ctx> {
+        builder
+            .build_call(self.AspenIdenticalTo, &[obj.into()], "identical_to")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value()
+    }
+
+    /// Combines two matchers into one that matches whatever either of
+    /// them matches. Both matchers are consumed.
+    pub fn any_of(
+        &self,
+        builder: &Builder<'ctx>,
+        a: PointerValue<'ctx>,
+        b: PointerValue<'ctx>,
+    ) -> PointerValue<'ctx> {
+        builder
+            .build_call(self.AspenAnyOf, &[a.into(), b.into()], "any_of")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value()
+    }
+
     pub fn match_obj(
         &self,
         builder: &Builder<'ctx>,
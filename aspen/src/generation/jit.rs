@@ -1,4 +1,4 @@
-use crate::generation::{GenResult, Generator};
+use crate::generation::{GenResult, Generator, LIVE_EXIT_FN};
 use crate::semantics::{Host, Module};
 use crate::Context;
 use inkwell::execution_engine::ExecutionEngine;
@@ -14,6 +14,8 @@ static mut CONTEXT: Option<inkwell::context::Context> = None;
 
 pub struct JIT {
     engine: ExecutionEngine<'static>,
+    workers: Option<usize>,
+    print: Option<extern "C" fn(&aspenrt::ObjectRef)>,
 }
 
 impl JIT {
@@ -30,10 +32,28 @@ impl JIT {
                 .create_jit_execution_engine(OptimizationLevel::Default)
                 .unwrap();
 
-            JIT { engine }
+            JIT {
+                engine,
+                workers: None,
+                print: None,
+            }
         }
     }
 
+    /// Runs programs with exactly `workers` worker threads, rather than
+    /// one for each CPU but one.
+    pub fn with_workers(mut self, workers: Option<usize>) -> JIT {
+        self.workers = workers;
+        self
+    }
+
+    /// Passes the objects that evaluated code prints to `print` rather
+    /// than printing them to stdout.
+    pub fn with_print(mut self, print: extern "C" fn(&aspenrt::ObjectRef)) -> JIT {
+        self.print = Some(print);
+        self
+    }
+
     pub fn evaluate(&self, module: Arc<Module>) -> GenResult<()> {
         unsafe {
             let generator = Generator::new(module.host.clone(), CONTEXT.as_ref().unwrap());
@@ -44,14 +64,23 @@ impl JIT {
                 eprintln!("------------------\n{:?}------------------", module);
             }
 
-            module.evaluate(self.engine.clone());
+            module.evaluate_with_print(self.engine.clone(), self.print);
         }
         Ok(())
     }
 
-    pub fn evaluate_main<M: AsRef<str>>(self, host: Host, main: M) -> GenResult<()> {
+    /// Starts the program with `main` as its main object. The arguments
+    /// are made available to the program through the runtime, with the
+    /// name of the main object standing in for the program name.
+    pub fn evaluate_main<M: AsRef<str>>(
+        self,
+        host: Host,
+        main: M,
+        args: &[String],
+    ) -> GenResult<()> {
         unsafe {
-            let generator = Generator::new(host.clone(), CONTEXT.as_ref().unwrap());
+            let generator =
+                Generator::new(host.clone(), CONTEXT.as_ref().unwrap()).with_workers(self.workers);
             let module = generator.generate_main(main.as_ref())?;
 
             if cfg!(debug_assertions) {
@@ -59,14 +88,21 @@ impl JIT {
                 eprintln!("------------------\n{:?}------------------", module);
             }
 
-            module.evaluate(self.engine.clone());
+            let argv: Vec<&str> = std::iter::once(main.as_ref())
+                .chain(args.iter().map(String::as_str))
+                .collect();
+            module.evaluate_main(self.engine.clone(), argv.as_slice());
         }
         Ok(())
     }
 
     pub fn init_live_env(&self, host: Host) -> GenResult<()> {
+        // No thread attaches to the runtime of a live session, so it
+        // needs a worker to handle any messages at all.
+        let workers = self.workers.map(|workers| workers.max(1));
         unsafe {
-            let generator = Generator::new(host.clone(), CONTEXT.as_ref().unwrap());
+            let generator =
+                Generator::new(host.clone(), CONTEXT.as_ref().unwrap()).with_workers(workers);
             let module = generator.generate_live_init()?;
 
             if cfg!(debug_assertions) {
@@ -74,8 +110,22 @@ impl JIT {
                 eprintln!("------------------\n{:?}------------------", module);
             }
 
-            module.evaluate(self.engine.clone());
+            module.evaluate_with_print(self.engine.clone(), self.print);
         }
         Ok(())
     }
+
+    /// Stops the runtime of the live environment, along with its worker
+    /// threads, even if it still has actors. Nothing that has been
+    /// evaluated may be used after this.
+    pub fn exit_live_env(&self) {
+        unsafe {
+            if let Ok(exit) = self
+                .engine
+                .get_function::<unsafe extern "C" fn()>(LIVE_EXIT_FN)
+            {
+                exit.call();
+            }
+        }
+    }
 }
@@ -0,0 +1,32 @@
+use crate::{stable_hash, URI};
+
+/// The symbol of a function generated for a declaration, qualified by
+/// the module it was declared in so that declarations with the same
+/// name in different modules don't collide when linked together.
+///
+/// The module is identified by the stable hash of its URI.
+pub fn mangle(uri: &URI, symbol: &str, suffix: &str) -> String {
+    format!(
+        "m{:016x}::{}::{}",
+        stable_hash(uri.uri().as_bytes()),
+        symbol,
+        suffix
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mangling_is_deterministic() {
+        assert_eq!(
+            mangle(&"test:x".into(), "X", "New"),
+            mangle(&"test:x".into(), "X", "New")
+        );
+        assert_ne!(
+            mangle(&"test:x".into(), "X", "New"),
+            mangle(&"test:y".into(), "X", "New")
+        );
+    }
+}
@@ -1,15 +1,27 @@
+mod cache_manifest;
+mod captured_prints;
 mod emitted_module;
 mod executable;
 mod generator;
+mod header;
 mod intrinsics;
 mod jit;
+mod mangling;
 mod object_file;
 mod result;
+mod runtime;
+mod target;
 
+pub use self::cache_manifest::*;
+pub use self::captured_prints::*;
 pub use self::emitted_module::*;
 pub use self::executable::*;
 pub use self::generator::*;
+pub use self::header::*;
 pub use self::intrinsics::*;
 pub use self::jit::*;
+pub use self::mangling::*;
 pub use self::object_file::*;
 pub use self::result::*;
+pub use self::runtime::*;
+pub use self::target::*;
@@ -1,51 +1,237 @@
-use crate::generation::{EmittedModule, GenError, GenResult, Generator};
+use crate::generation::{CacheManifest, EmittedModule, GenError, GenResult, Generator, TargetSpec};
 use crate::semantics::Module;
-use inkwell::targets::{
-    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetTriple,
-};
-use inkwell::OptimizationLevel;
+use crate::InternalCompilerError;
+use inkwell::targets::FileType;
+use std::io;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::path::PathBuf;
 use std::sync::Arc;
-
-const TARGET: &str = env!("TARGET");
+use tokio::sync::Mutex;
+use tokio::task;
 
 pub struct ObjectFile {
     pub path: PathBuf,
 }
 
 impl ObjectFile {
-    pub async fn new(module: Arc<Module>) -> GenResult<ObjectFile> {
+    /// Generates the object file of the module, unless the cache has
+    /// an object file generated from the same source and dependencies
+    /// for the same target, CPU and features.
+    pub async fn new(
+        module: Arc<Module>,
+        manifest: &Mutex<CacheManifest>,
+        target: &TargetSpec,
+    ) -> GenResult<ObjectFile> {
         let path = module.host.context.object_file_path(module.uri())?;
-        let context = inkwell::context::Context::create();
 
-        let generator = Generator::new(module.host.clone(), &context);
-        let emitted = generator.generate_module(&module)?;
+        if Self::is_cached(&module, manifest, target).await {
+            return Ok(ObjectFile { path });
+        }
+
+        let result = Self::generate(path, &module, target.clone()).await;
 
+        let dependencies = module.dependencies().await;
+        let mut manifest = manifest.lock().await;
+        match &result {
+            Ok(_) => manifest.record(&module.source, target, &dependencies),
+            _ => manifest.forget(&module.source),
+        }
+
+        result
+    }
+
+    /// Whether the module's object file from an earlier build can be
+    /// used as it is.
+    pub async fn is_cached(
+        module: &Arc<Module>,
+        manifest: &Mutex<CacheManifest>,
+        target: &TargetSpec,
+    ) -> bool {
+        let path = match module.host.context.object_file_path(module.uri()) {
+            Ok(path) => path,
+            Err(_) => return false,
+        };
+
+        let dependencies = module.dependencies().await;
+        manifest
+            .lock()
+            .await
+            .is_fresh(&module.source, target, &dependencies)
+            && tokio::fs::metadata(&path).await.is_ok()
+    }
+
+    /// Writes the header of the module, which the modules referring to
+    /// it are checked against when they're generated. This must be done
+    /// for every module that is about to be generated, before any of
+    /// them are, since they're generated concurrently.
+    pub async fn write_header(module: &Arc<Module>) -> GenResult<()> {
+        let header_path = module.host.context.header_file_path(module.uri())?;
+        if let Some(dir) = header_path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+
+        let module = module.clone();
+        let header = task::spawn_blocking(move || {
+            let context = inkwell::context::Context::create();
+            Generator::new(module.host.clone(), &context).generate_header(&module)
+        })
+        .await
+        .map_err(|e| GenError::IO(io::Error::new(io::ErrorKind::Other, e)))?;
+
+        header.write(&header_path).await?;
+        Ok(())
+    }
+
+    async fn generate(
+        path: PathBuf,
+        module: &Arc<Module>,
+        target: TargetSpec,
+    ) -> GenResult<ObjectFile> {
         module.host.context.ensure_object_file_dir().await?;
-        Self::write(path, emitted).await
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+
+        // Generation blocks on semantic queries and emission is CPU bound,
+        // so both run on the blocking pool, each with its own LLVM context.
+        // A panic while generating one module is reported as an error
+        // for that module, so that the others can still be generated.
+        let module = module.clone();
+        task::spawn_blocking(move || -> GenResult<ObjectFile> {
+            let uri = module.uri().clone();
+            catch_unwind(AssertUnwindSafe(|| {
+                let context = inkwell::context::Context::create();
+
+                let generator =
+                    Generator::new(module.host.clone(), &context).with_headers_checked();
+                let emitted = generator.generate_module(&module)?;
+
+                Self::emit(path, emitted, &target)
+            }))
+            .unwrap_or_else(|payload| {
+                Err(GenError::Internal(format!(
+                    "{:?}: {}",
+                    uri,
+                    InternalCompilerError::describe_panic(payload.as_ref())
+                )))
+            })
+        })
+        .await
+        .map_err(|e| GenError::IO(io::Error::new(io::ErrorKind::Other, e)))?
+    }
+
+    pub(crate) async fn write(
+        path: PathBuf,
+        module: EmittedModule<'_>,
+        target: &TargetSpec,
+    ) -> GenResult<ObjectFile> {
+        Self::emit(path, module, target)
     }
 
-    pub(crate) async fn write(path: PathBuf, module: EmittedModule<'_>) -> GenResult<ObjectFile> {
+    /// Generates the object code of the module in memory, without
+    /// touching the workspace of its context.
+    pub async fn to_bytes(module: Arc<Module>, target: &TargetSpec) -> GenResult<Vec<u8>> {
+        let target = target.clone();
+        task::spawn_blocking(move || -> GenResult<Vec<u8>> {
+            let context = inkwell::context::Context::create();
+
+            let generator = Generator::new(module.host.clone(), &context);
+            let emitted = generator.generate_module(&module)?;
+
+            let buffer = target
+                .target_machine()?
+                .write_to_memory_buffer(&emitted.module, FileType::Object)?;
+            Ok(buffer.as_slice().to_vec())
+        })
+        .await
+        .map_err(|e| GenError::IO(io::Error::new(io::ErrorKind::Other, e)))?
+    }
+
+    fn emit(
+        path: PathBuf,
+        module: EmittedModule<'_>,
+        target: &TargetSpec,
+    ) -> GenResult<ObjectFile> {
         if cfg!(debug_assertions) {
             eprintln!("------------------\n{:?}------------------", module);
         }
 
-        Target::initialize_all(&InitializationConfig::default());
-        let triple = TargetTriple::create(TARGET);
-        let target = Target::from_triple(&triple)?;
-        let machine = target
-            .create_target_machine(
-                &triple,
-                "generic",
-                "",
-                OptimizationLevel::Aggressive,
-                RelocMode::PIC,
-                CodeModel::Default,
-            )
-            .ok_or(GenError::NoTargetMachine(triple))?;
-
-        machine.write_to_file(&module.module, FileType::Object, &path)?;
+        target
+            .target_machine()?
+            .write_to_file(&module.module, FileType::Object, &path)?;
 
         Ok(ObjectFile { path })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantics::Host;
+    use crate::{Context, Source};
+
+    #[tokio::test]
+    async fn compiles_to_an_object_in_memory() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host.set(Source::new("test:x", "object X.")).await;
+
+        let bytes = ObjectFile::to_bytes(module, &TargetSpec::host())
+            .await
+            .unwrap();
+
+        const ELF: &[u8] = b"\x7fELF";
+        const MACH_O_64: &[u8] = &[0xcf, 0xfa, 0xed, 0xfe];
+        const COFF_AMD64: &[u8] = &[0x64, 0x86];
+        assert!(
+            bytes.starts_with(ELF) || bytes.starts_with(MACH_O_64) || bytes.starts_with(COFF_AMD64),
+            "{:x?}",
+            &bytes[..4.min(bytes.len())]
+        );
+    }
+
+    #[tokio::test]
+    async fn compiles_to_a_wasm_object() {
+        let wasm = TargetSpec::new("wasm32-unknown-unknown");
+        if wasm.target_machine().is_err() {
+            // LLVM was built without the WebAssembly backend.
+            return;
+        }
+
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host.set(Source::new("test:x", "object X.")).await;
+
+        let bytes = ObjectFile::to_bytes(module, &wasm).await.unwrap();
+        assert!(
+            bytes.starts_with(b"\0asm"),
+            "{:x?}",
+            &bytes[..4.min(bytes.len())]
+        );
+    }
+
+    #[tokio::test]
+    async fn touched_but_identical_module_is_not_regenerated() {
+        let dir = std::env::current_dir().unwrap();
+        let context = Arc::new(Context::temporary_in(None, dir.clone()).unwrap());
+        let uri = crate::URI::file(dir.join("touched.aspen"));
+        let manifest = Mutex::new(CacheManifest::new());
+        let target = TargetSpec::host();
+
+        let host = Host::new(context.clone());
+        let module = host.set(Source::new(uri.clone(), "object X.")).await;
+        let object = ObjectFile::new(module, &manifest, &target).await.unwrap();
+
+        // Generating the object again would overwrite the marker.
+        tokio::fs::write(&object.path, b"cached").await.unwrap();
+
+        let host = Host::new(context.clone());
+        let touched = host.set(Source::new(uri.clone(), "object X.")).await;
+        assert!(ObjectFile::is_cached(&touched, &manifest, &target).await);
+        let object = ObjectFile::new(touched, &manifest, &target).await.unwrap();
+        assert_eq!(tokio::fs::read(&object.path).await.unwrap(), b"cached");
+
+        let host = Host::new(context);
+        let changed = host.set(Source::new(uri, "object Y.")).await;
+        let object = ObjectFile::new(changed, &manifest, &target).await.unwrap();
+        assert_ne!(tokio::fs::read(&object.path).await.unwrap(), b"cached");
+    }
+}
@@ -1,6 +1,6 @@
+use crate::Range;
 use futures::io::Error;
 use inkwell::support::LLVMString;
-use inkwell::targets::TargetTriple;
 use std::fmt;
 use std::io;
 
@@ -10,11 +10,17 @@ pub enum GenError {
     Multi(Vec<GenError>),
     IO(io::Error),
     FailedToLink(String),
-    NoTargetMachine(TargetTriple),
+    NoTargetMachine(String),
+    UnsupportedTarget(String),
+    InvalidTargetFeature(String),
     LLVM(String),
-    UndefinedReference,
+    UndefinedReference(String, Range),
+    Unsupported(String, Range),
     BadNode,
     InvalidMainObject(String),
+    InvalidEntrySelector(String),
+    HeaderMismatch(String),
+    Internal(String),
 }
 
 impl fmt::Debug for GenError {
@@ -32,11 +38,62 @@ impl fmt::Debug for GenError {
             }
             IO(e) => fmt::Debug::fmt(e, f),
             FailedToLink(s) => write!(f, "Failed to link: {}", s),
-            NoTargetMachine(t) => write!(f, "No such target machine: {:?}", t),
+            NoTargetMachine(t) => write!(f, "No such target machine: {}", t),
+            UnsupportedTarget(t) => write!(f, "Unsupported target: {}", t),
+            InvalidTargetFeature(feature) => write!(
+                f,
+                "Invalid target feature `{}`, expected e.g. +avx2 or -sse4.1",
+                feature
+            ),
             LLVM(s) => fmt::Display::fmt(s, f),
-            UndefinedReference => write!(f, "Undefined reference"),
+            UndefinedReference(name, range) => {
+                write!(f, "{}: Undefined reference `{}`", range, name)
+            }
+            Unsupported(what, range) => write!(f, "{}: {} are not supported yet", range, what),
             BadNode => write!(f, "Bad node"),
             InvalidMainObject(s) => fmt::Display::fmt(s, f),
+            InvalidEntrySelector(s) => write!(
+                f,
+                "Invalid entry selector `{}`, expected an atom like run! or start!",
+                s
+            ),
+            HeaderMismatch(s) => write!(f, "Header mismatch: {}", s),
+            Internal(s) => write!(f, "Internal compiler error: {}", s),
+        }
+    }
+}
+
+impl GenError {
+    /// The errors contained in this error, with any nested
+    /// [`GenError::Multi`](GenError::Multi) flattened away.
+    pub fn flatten(&self) -> Vec<&GenError> {
+        match self {
+            GenError::Multi(errs) => errs.iter().flat_map(GenError::flatten).collect(),
+            e => vec![e],
+        }
+    }
+}
+
+impl fmt::Display for GenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, e) in self.flatten().into_iter().enumerate() {
+            if i > 0 {
+                write!(f, "\n")?;
+            }
+            match e {
+                GenError::IO(e) => fmt::Display::fmt(e, f)?,
+                e => fmt::Debug::fmt(e, f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GenError::IO(e) => Some(e),
+            _ => None,
         }
     }
 }
@@ -52,3 +109,23 @@ impl From<LLVMString> for GenError {
         GenError::LLVM(s.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flattened_display() {
+        let error = GenError::Multi(vec![
+            GenError::Multi(vec![
+                GenError::BadNode,
+                GenError::InvalidMainObject("X".into()),
+            ]),
+            GenError::Multi(vec![]),
+            GenError::FailedToLink("cc".into()),
+        ]);
+
+        assert_eq!(error.flatten().len(), 3);
+        assert_eq!(format!("{}", error), "Bad node\nX\nFailed to link: cc");
+    }
+}
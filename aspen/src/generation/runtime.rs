@@ -0,0 +1,63 @@
+use crate::generation::GenResult;
+use std::path::PathBuf;
+
+/// The file name of the runtime archive that executables link against.
+pub const RUNTIME_ARCHIVE: &str = "libaspenrt.a";
+
+/// The runtime archive, built into the compiler so that executables can
+/// be linked without a runtime installed next to it.
+#[cfg(feature = "embedded-runtime")]
+const EMBEDDED_RUNTIME: &[u8] = include_bytes!(env!("ASPEN_RUNTIME_ARCHIVE"));
+
+/// The directory to search for the runtime archive when linking, which
+/// is next to the compiler itself.
+#[cfg(not(feature = "embedded-runtime"))]
+pub fn runtime_dir() -> GenResult<PathBuf> {
+    let mut dir = std::env::current_exe()?;
+    dir.pop();
+    Ok(dir)
+}
+
+/// The directory to search for the runtime archive when linking, where
+/// the embedded archive is extracted the first time it's needed. The
+/// directory is private to the user, and an archive that doesn't have
+/// the exact contents of the embedded one is replaced.
+#[cfg(feature = "embedded-runtime")]
+pub fn runtime_dir() -> GenResult<PathBuf> {
+    let dir = dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("aspen")
+        .join(env!("CARGO_PKG_VERSION"));
+    let path = dir.join(RUNTIME_ARCHIVE);
+
+    let is_extracted = std::fs::read(&path)
+        .map(|archive| archive.as_slice() == EMBEDDED_RUNTIME)
+        .unwrap_or(false);
+    if !is_extracted {
+        create_private_dir(&dir)?;
+
+        // Written to the side and renamed, so that concurrent builds
+        // never link a partially written archive.
+        let partial = dir.join(format!("{}.{}", RUNTIME_ARCHIVE, std::process::id()));
+        std::fs::write(&partial, EMBEDDED_RUNTIME)?;
+        std::fs::rename(&partial, &path)?;
+    }
+
+    Ok(dir)
+}
+
+#[cfg(all(feature = "embedded-runtime", unix))]
+fn create_private_dir(dir: &std::path::Path) -> std::io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+}
+
+#[cfg(all(feature = "embedded-runtime", not(unix)))]
+fn create_private_dir(dir: &std::path::Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)
+}
@@ -0,0 +1,167 @@
+use crate::generation::{GenError, GenResult};
+use inkwell::targets::{
+    CodeModel, InitializationConfig, RelocMode, Target, TargetMachine, TargetTriple,
+};
+use inkwell::OptimizationLevel;
+
+/// The triple of the machine the compiler itself was built for.
+const HOST_TRIPLE: &str = env!("TARGET");
+
+/// The CPU that code is generated for unless another one is requested.
+const GENERIC_CPU: &str = "generic";
+
+/// The machine that object code is generated for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetSpec {
+    pub triple: String,
+    pub cpu: String,
+    pub features: Vec<String>,
+}
+
+impl TargetSpec {
+    pub fn new<T: Into<String>>(triple: T) -> TargetSpec {
+        TargetSpec {
+            triple: triple.into(),
+            cpu: GENERIC_CPU.into(),
+            features: vec![],
+        }
+    }
+
+    /// Generates code for a specific CPU. `native` is the CPU of the
+    /// machine the compiler runs on, along with all of its features.
+    pub fn with_cpu(mut self, cpu: &str) -> TargetSpec {
+        if cpu == "native" {
+            self.cpu = TargetMachine::get_host_cpu_name().to_string();
+            self.features.extend(
+                TargetMachine::get_host_cpu_features()
+                    .to_string()
+                    .split(',')
+                    .filter(|f| !f.is_empty())
+                    .map(ToString::to_string),
+            );
+        } else {
+            self.cpu = cpu.into();
+        }
+        self
+    }
+
+    /// Enables or disables a CPU feature, written like `+avx2` or
+    /// `-sse4.1`.
+    pub fn with_feature(mut self, feature: &str) -> GenResult<TargetSpec> {
+        let name = match feature.chars().next() {
+            Some('+') | Some('-') => &feature[1..],
+            _ => "",
+        };
+        let is_valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-');
+        if !is_valid {
+            return Err(GenError::InvalidTargetFeature(feature.into()));
+        }
+
+        self.features.push(feature.into());
+        Ok(self)
+    }
+
+    /// Identifies the code generated for the target, so that objects
+    /// generated for one target are never reused for another.
+    pub fn fingerprint(&self) -> String {
+        format!("{} {} {}", self.triple, self.cpu, self.features.join(","))
+    }
+
+    pub fn host() -> TargetSpec {
+        TargetSpec::new(HOST_TRIPLE)
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.triple == HOST_TRIPLE
+    }
+
+    /// WebAssembly modules are linked with `wasm-ld` rather than the
+    /// native C compiler, and leave the runtime to be imported.
+    pub fn is_wasm(&self) -> bool {
+        self.triple.starts_with("wasm32-") || self.triple.starts_with("wasm64-")
+    }
+
+    pub fn target_machine(&self) -> GenResult<TargetMachine> {
+        if self.triple.contains('\0') {
+            return Err(GenError::UnsupportedTarget(format!("{:?}", self.triple)));
+        }
+        Target::initialize_all(&InitializationConfig::default());
+        let triple = TargetTriple::create(self.triple.as_str());
+        let target = Target::from_triple(&triple).map_err(|error| {
+            GenError::UnsupportedTarget(format!("{} ({})", self.triple, error.to_string().trim()))
+        })?;
+        target
+            .create_target_machine(
+                &triple,
+                self.cpu.as_str(),
+                self.features.join(",").as_str(),
+                OptimizationLevel::Aggressive,
+                RelocMode::PIC,
+                CodeModel::Default,
+            )
+            .ok_or_else(|| {
+                GenError::NoTargetMachine(format!(
+                    "{} (cpu {}, features {:?})",
+                    self.triple,
+                    self.cpu,
+                    self.features.join(",")
+                ))
+            })
+    }
+}
+
+impl Default for TargetSpec {
+    fn default() -> Self {
+        TargetSpec::host()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requested_cpu_reaches_the_target_machine() {
+        let machine = TargetSpec::host()
+            .with_cpu("native")
+            .target_machine()
+            .unwrap();
+
+        assert_eq!(
+            machine.get_cpu().to_string(),
+            TargetMachine::get_host_cpu_name().to_string()
+        );
+    }
+
+    #[test]
+    fn unknown_triples_are_unsupported_targets() {
+        for triple in &["nonsense-unknown-nowhere", "x86_64\0linux"] {
+            match TargetSpec::new(*triple).target_machine() {
+                Err(GenError::UnsupportedTarget(message)) => {
+                    assert!(message.contains("nonsense") || message.contains("x86_64"))
+                }
+                Err(e) => panic!("expected an unsupported target, got {:?}", e),
+                Ok(_) => panic!("expected {:?} to be unsupported", triple),
+            }
+        }
+    }
+
+    #[test]
+    fn feature_strings_are_validated() {
+        let spec = TargetSpec::host()
+            .with_feature("+avx2")
+            .and_then(|s| s.with_feature("-sse4.1"))
+            .unwrap();
+        assert_eq!(spec.features, vec!["+avx2", "-sse4.1"]);
+
+        for invalid in &["avx2", "+", "+avx2,+sse", "+a b"] {
+            match TargetSpec::host().with_feature(invalid) {
+                Err(GenError::InvalidTargetFeature(f)) => assert_eq!(f, *invalid),
+                _ => panic!("expected {:?} to be rejected", invalid),
+            }
+        }
+    }
+}
@@ -24,6 +24,7 @@ extern crate lazy_static;
 
 mod context;
 mod diagnostics;
+mod eval;
 pub mod generation;
 pub mod semantics;
 mod source;
@@ -31,6 +32,7 @@ pub mod syntax;
 
 pub use self::context::*;
 pub use self::diagnostics::*;
+pub use self::eval::*;
 pub use self::source::*;
 
 pub fn version() -> &'static str {
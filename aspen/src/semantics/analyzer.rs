@@ -1,8 +1,10 @@
 use crate::semantics::{Host, Module};
-use crate::syntax::Navigator;
+use crate::syntax::{Navigator, Node};
 use futures::future;
 use std::borrow::BorrowMut;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::iter::FromIterator;
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -30,6 +32,9 @@ where
     }
 }
 
+/// Runs the analyzer the first time it is needed, and returns the same
+/// output every time after that. Concurrent callers wait for the first
+/// run to finish rather than starting one of their own.
 pub struct MemoOut<A: Analyzer> {
     mutex: Mutex<Option<A::Output>>,
     analyzer: A,
@@ -47,7 +52,7 @@ impl<A: Analyzer> MemoOut<A> {
 #[async_trait]
 impl<A> Analyzer for MemoOut<A>
 where
-    A: Analyzer<Input = ()> + Clone + Sync + Send,
+    A: Analyzer<Input = ()> + Sync + Send,
     A::Output: Clone,
 {
     type Input = ();
@@ -60,8 +65,7 @@ where
             Some(t) => return t.clone(),
             None => {}
         }
-        let analyzer = self.analyzer.clone();
-        let t = analyzer.analyze(ctx).await;
+        let t = self.analyzer.analyze(ctx).await;
         *opt = Some(t.clone());
         t
     }
@@ -77,6 +81,28 @@ impl<T> PtrAsUsize for Arc<T> {
     }
 }
 
+/// Identifies a node by its location and code rather than by its
+/// address, so that a `Memo<A, u64>` keeps hitting for a node that is
+/// reconstructed elsewhere, and never hits for a different node that
+/// happens to be allocated where a freed one used to be.
+pub trait ContentKey {
+    fn content_key(&self) -> u64;
+}
+
+impl<T: Node + ?Sized> ContentKey for Arc<T> {
+    fn content_key(&self) -> u64 {
+        let range = self.range();
+        let mut hasher = DefaultHasher::new();
+        self.source().uri().hash(&mut hasher);
+        range.start.offset.hash(&mut hasher);
+        range.end.offset.hash(&mut hasher);
+        self.source()
+            .slice(range.start.offset..range.end.offset)
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
 pub struct Memo<A: Analyzer, K> {
     mutex: Mutex<HashMap<K, Arc<Mutex<Option<A::Output>>>>>,
     analyzer: A,
@@ -91,19 +117,13 @@ impl<A: Analyzer, K> Memo<A, K> {
     }
 }
 
-#[async_trait]
-impl<A> Analyzer for Memo<A, usize>
+impl<A, K> Memo<A, K>
 where
     A: Analyzer + Clone + Sync + Send,
     A::Output: Clone,
-    A::Input: PtrAsUsize,
+    K: Eq + Hash + Send + Sync,
 {
-    type Input = A::Input;
-    type Output = A::Output;
-
-    async fn analyze(&self, ctx: AnalysisContext<Self::Input>) -> A::Output {
-        let key = ctx.input.ptr_as_usize();
-
+    async fn analyze_keyed(&self, key: K, ctx: AnalysisContext<A::Input>) -> A::Output {
         {
             let map = self.mutex.lock().await;
 
@@ -139,37 +159,35 @@ where
     }
 }
 
-pub struct Once<A> {
-    analyzer: Mutex<Option<A>>,
-}
+#[async_trait]
+impl<A> Analyzer for Memo<A, usize>
+where
+    A: Analyzer + Clone + Sync + Send,
+    A::Output: Clone,
+    A::Input: PtrAsUsize,
+{
+    type Input = A::Input;
+    type Output = A::Output;
 
-impl<A> Once<A> {
-    pub fn of(analyzer: A) -> Once<A> {
-        Once {
-            analyzer: Mutex::new(Some(analyzer)),
-        }
+    async fn analyze(&self, ctx: AnalysisContext<Self::Input>) -> A::Output {
+        let key = ctx.input.ptr_as_usize();
+        self.analyze_keyed(key, ctx).await
     }
 }
 
 #[async_trait]
-impl<A> Analyzer for Once<A>
+impl<A> Analyzer for Memo<A, u64>
 where
-    A: Analyzer<Input = ()>,
-    A::Output: Default,
+    A: Analyzer + Clone + Sync + Send,
+    A::Output: Clone,
+    A::Input: ContentKey,
 {
-    type Input = ();
+    type Input = A::Input;
     type Output = A::Output;
 
-    async fn analyze(&self, ctx: AnalysisContext<()>) -> A::Output {
-        let mut lock = self.analyzer.lock().await;
-        let opt: &mut Option<A> = lock.borrow_mut();
-
-        if opt.is_none() {
-            return Default::default();
-        }
-
-        let analyzer = std::mem::replace(opt, None).unwrap();
-        analyzer.analyze(ctx).await
+    async fn analyze(&self, ctx: AnalysisContext<Self::Input>) -> A::Output {
+        let key = ctx.input.content_key();
+        self.analyze_keyed(key, ctx).await
     }
 }
 
@@ -201,3 +219,107 @@ where
         vec![a, b].into_iter().collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, Source};
+
+    /// Stands in for a node that was freed and replaced by another
+    /// one at the same address.
+    #[derive(Clone)]
+    struct Reused(&'static str);
+
+    impl PtrAsUsize for Reused {
+        fn ptr_as_usize(&self) -> usize {
+            1
+        }
+    }
+
+    impl ContentKey for Reused {
+        fn content_key(&self) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            self.0.hash(&mut hasher);
+            hasher.finish()
+        }
+    }
+
+    #[derive(Clone)]
+    struct Echo;
+
+    #[async_trait]
+    impl Analyzer for Echo {
+        type Input = Reused;
+        type Output = &'static str;
+
+        async fn analyze(&self, ctx: AnalysisContext<Reused>) -> &'static str {
+            ctx.input.0
+        }
+    }
+
+    async fn context_of(input: Reused) -> AnalysisContext<Reused> {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host.set(Source::new("test:x", "object X.")).await;
+        AnalysisContext {
+            navigator: module.navigate(),
+            module,
+            host,
+            input,
+        }
+    }
+
+    #[derive(Clone)]
+    struct Count(Arc<std::sync::atomic::AtomicUsize>);
+
+    #[async_trait]
+    impl Analyzer for Count {
+        type Input = ();
+        type Output = usize;
+
+        async fn analyze(&self, _ctx: AnalysisContext<()>) -> usize {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1
+        }
+    }
+
+    #[tokio::test]
+    async fn memo_out_runs_once_and_keeps_its_output() {
+        let memo = MemoOut::of(Count(Default::default()));
+        let unit = || async {
+            let host = Host::new(Arc::new(Context::test()));
+            let module = host.set(Source::new("test:x", "object X.")).await;
+            AnalysisContext {
+                navigator: module.navigate(),
+                module,
+                host,
+                input: (),
+            }
+        };
+
+        let (a, b) = future::join(memo.analyze(unit().await), memo.analyze(unit().await)).await;
+        assert_eq!((a, b), (1, 1));
+        assert_eq!(memo.analyze(unit().await).await, 1);
+    }
+
+    #[tokio::test]
+    async fn content_keys_avoid_pointer_reuse() {
+        let by_pointer: Memo<Echo, usize> = Memo::of(Echo);
+        assert_eq!(by_pointer.analyze(context_of(Reused("a")).await).await, "a");
+        assert_eq!(by_pointer.analyze(context_of(Reused("b")).await).await, "a");
+
+        let by_content: Memo<Echo, u64> = Memo::of(Echo);
+        assert_eq!(by_content.analyze(context_of(Reused("a")).await).await, "a");
+        assert_eq!(by_content.analyze(context_of(Reused("b")).await).await, "b");
+    }
+
+    #[tokio::test]
+    async fn reparsed_nodes_have_the_same_content_key() {
+        let host = Host::new(Arc::new(Context::test()));
+        let before = host.set(Source::new("test:x", "object X.")).await;
+        let before = before.navigate().all_declarations().next().unwrap();
+        let after = host.set(Source::new("test:x", "object X.")).await;
+        let after = after.navigate().all_declarations().next().unwrap();
+
+        assert!(!Arc::ptr_eq(&before, &after));
+        assert_eq!(before.content_key(), after.content_key());
+    }
+}
@@ -1,5 +1,5 @@
 use crate::semantics::{AnalysisContext, Analyzer};
-use crate::syntax::{Node, ReferenceExpression, ReferenceTypeExpression};
+use crate::syntax::{Node, ReferenceExpression, ReferenceTypeExpression, SelfExpression};
 use crate::{Diagnostic, Diagnostics, Range, Severity, Source};
 use std::convert::identity;
 use std::sync::Arc;
@@ -17,6 +17,9 @@ impl Analyzer for CheckAllReferencesAreDefined {
         for diagnostic in futures::future::join_all(ctx.navigator.traverse().map(
             async move |child| -> Option<Arc<dyn Diagnostic>> {
                 if let Some(reference) = child.node.clone().as_reference_expression() {
+                    if module.binding_referenced_by(&reference).is_some() {
+                        return None;
+                    }
                     if let None = module.declaration_referenced_by(reference.clone()).await {
                         return Some(Arc::new(UndefinedReference(reference)));
                     }
@@ -29,6 +32,11 @@ impl Analyzer for CheckAllReferencesAreDefined {
                         return Some(Arc::new(UndefinedTypeReference(reference)));
                     }
                 }
+                if let Some(self_expression) = child.node.clone().as_self_expression() {
+                    if module.enclosing_object(self_expression.clone()).is_none() {
+                        return Some(Arc::new(SelfOutsideOfObject(self_expression)));
+                    }
+                }
                 return None;
             },
         ))
@@ -89,3 +97,24 @@ impl Diagnostic for UndefinedTypeReference {
         )
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct SelfOutsideOfObject(pub Arc<SelfExpression>);
+
+impl Diagnostic for SelfOutsideOfObject {
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn source(&self) -> &Arc<Source> {
+        self.0.source()
+    }
+
+    fn range(&self) -> Range {
+        self.0.range()
+    }
+
+    fn message(&self) -> String {
+        "`self` can only be used inside of an object".into()
+    }
+}
@@ -0,0 +1,56 @@
+use crate::semantics::{AnalysisContext, Analyzer};
+use crate::syntax::{Declaration, Node, ObjectDeclaration};
+use crate::{Diagnostic, Diagnostics, Range, Severity, Source};
+use std::sync::Arc;
+
+/// Notes objects declared with braces but without any members, which
+/// are most likely unfinished. An object without members is better
+/// declared with a period.
+pub struct CheckForEmptyObjectBodies;
+
+#[async_trait]
+impl Analyzer for CheckForEmptyObjectBodies {
+    type Input = ();
+    type Output = Diagnostics;
+
+    async fn analyze(&self, ctx: AnalysisContext<()>) -> Diagnostics {
+        let mut diagnostics = Diagnostics::new();
+        for declaration in ctx.navigator.all_declarations() {
+            match declaration.as_ref() {
+                Declaration::Object(o) if o.has_empty_body() => {
+                    diagnostics.push(EmptyObjectBody(o.clone()));
+                }
+                _ => {}
+            }
+        }
+        diagnostics
+    }
+}
+
+#[derive(Debug)]
+pub struct EmptyObjectBody(pub Arc<ObjectDeclaration>);
+
+impl Diagnostic for EmptyObjectBody {
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+
+    fn source(&self) -> &Arc<Source> {
+        self.0.source()
+    }
+
+    fn range(&self) -> Range {
+        match &self.0.body {
+            Some(body) => body.range(),
+            None => self.0.range(),
+        }
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "`{}` has an empty body, write `object {}.` if it has no methods",
+            self.0.symbol(),
+            self.0.symbol()
+        )
+    }
+}
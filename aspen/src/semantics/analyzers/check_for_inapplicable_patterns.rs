@@ -0,0 +1,213 @@
+use crate::semantics::types::Type;
+use crate::semantics::{AnalysisContext, Analyzer, Module};
+use crate::syntax::{Declaration, Expression, Method, Node, ObjectDeclaration, ObjectMember};
+use crate::{Diagnostic, Diagnostics, Range, Severity, Source, URI};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Warns about methods whose patterns can't match any of the messages
+/// that their object is sent anywhere in the host, which makes them
+/// dead code.
+///
+/// Only objects that are sent messages from outside of themselves are
+/// checked, since objects like the main object are sent messages by the
+/// runtime. Objects that are sent messages of types that aren't fully
+/// known, or that are used as values and so could be sent anything,
+/// are skipped as well.
+///
+/// The messages sent in the host are gathered once and shared through
+/// the host's type cache, so this analyzer isn't memoized by modules,
+/// which would leave it stale when another module changes.
+pub struct CheckForInapplicablePatterns;
+
+#[async_trait]
+impl Analyzer for CheckForInapplicablePatterns {
+    type Input = ();
+    type Output = Diagnostics;
+
+    async fn analyze(&self, ctx: AnalysisContext<()>) -> Diagnostics {
+        let mut diagnostics = Diagnostics::new();
+        let objects: Vec<_> = ctx
+            .navigator
+            .all_declarations()
+            .filter_map(|d| match d.as_ref() {
+                Declaration::Object(o) if o.body.is_some() => Some(o.clone()),
+                _ => None,
+            })
+            .collect();
+        if objects.is_empty() || !ctx.host.contains(ctx.module.uri()).await {
+            return diagnostics;
+        }
+
+        let host = &ctx.host;
+        let sent_messages = host
+            .type_cache()
+            .sent_messages(async { SentMessages::of(&host.modules().await).await })
+            .await;
+
+        for object in objects {
+            let sent = match sent_messages.to(&object) {
+                Some(sent) => sent,
+                None => continue,
+            };
+
+            for member in object.body.as_ref().unwrap().members.iter() {
+                let ObjectMember::Method(method) = member.as_ref();
+                let pattern = ctx.module.get_type_of_pattern(method.pattern.clone()).await;
+                if let Type::Failed { .. } = pattern {
+                    continue;
+                }
+
+                if !sent.iter().any(|message| may_match(message, &pattern)) {
+                    diagnostics.push(InapplicablePattern {
+                        object: object.clone(),
+                        method: method.clone(),
+                        sent: sent.to_vec(),
+                    });
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+/// The messages sent to each object anywhere in a host, gathered in
+/// one pass over all of its modules.
+#[derive(Default)]
+pub struct SentMessages {
+    objects: HashMap<(URI, usize), SentTo>,
+}
+
+#[derive(Default)]
+struct SentTo {
+    sent: Vec<Type>,
+    is_sent_from_outside: bool,
+    is_sent_unknown: bool,
+    escapes: bool,
+}
+
+impl SentMessages {
+    pub async fn of(modules: &[Arc<Module>]) -> SentMessages {
+        let mut messages = SentMessages::default();
+
+        for module in modules.iter() {
+            let navigator = module.navigate();
+            let mut receivers = HashSet::new();
+
+            for send in navigator.all_message_sends() {
+                receivers.insert(Arc::as_ptr(&send.receiver) as usize);
+                let entry = match module.get_type_of(send.receiver.clone()).await {
+                    Type::Object(o) => messages.entry(&o),
+                    _ => continue,
+                };
+
+                match send.receiver.as_ref() {
+                    Expression::SelfReference(_) => {}
+                    _ => entry.is_sent_from_outside = true,
+                }
+
+                let message = module.get_type_of(send.message.clone()).await;
+                match message {
+                    Type::Integer { .. } | Type::Float(_) | Type::Atom(_) => {
+                        if !entry.sent.contains(&message) {
+                            entry.sent.push(message);
+                        }
+                    }
+                    _ => entry.is_sent_unknown = true,
+                }
+            }
+
+            // An object that is used as anything but the receiver of a
+            // message can be passed around and sent anything.
+            for expression in navigator.all_expressions() {
+                match expression.as_ref() {
+                    Expression::Reference(_) | Expression::SelfReference(_) => {}
+                    _ => continue,
+                }
+                if receivers.contains(&(Arc::as_ptr(&expression) as usize)) {
+                    continue;
+                }
+                if let Type::Object(o) = module.get_type_of(expression.clone()).await {
+                    messages.entry(&o).escapes = true;
+                }
+            }
+        }
+        messages
+    }
+
+    /// The distinct types of the messages sent to the object, or `None`
+    /// if it's never sent a message from outside of itself, if the type
+    /// of any message isn't fully known, or if it's used as a value.
+    fn to(&self, object: &Arc<ObjectDeclaration>) -> Option<&[Type]> {
+        match self.objects.get(&Self::key(object)) {
+            Some(to) if to.is_sent_from_outside && !to.is_sent_unknown && !to.escapes => {
+                Some(to.sent.as_slice())
+            }
+            _ => None,
+        }
+    }
+
+    fn entry(&mut self, object: &Arc<ObjectDeclaration>) -> &mut SentTo {
+        self.objects.entry(Self::key(object)).or_default()
+    }
+
+    fn key(object: &Arc<ObjectDeclaration>) -> (URI, usize) {
+        (object.source.uri().clone(), object.range().start.offset)
+    }
+}
+
+/// Whether some message of the type could be matched by a pattern of
+/// the other type.
+fn may_match(message: &Type, pattern: &Type) -> bool {
+    match (message, pattern) {
+        (
+            Type::Integer {
+                lower: message_lower,
+                upper: message_upper,
+            },
+            Type::Integer {
+                lower: pattern_lower,
+                upper: pattern_upper,
+            },
+        ) => {
+            let at_most = |lower: &Option<i128>, upper: &Option<i128>| match (lower, upper) {
+                (Some(l), Some(u)) => l <= u,
+                _ => true,
+            };
+            at_most(message_lower, pattern_upper) && at_most(pattern_lower, message_upper)
+        }
+        _ => message <= pattern || pattern <= message,
+    }
+}
+
+#[derive(Debug)]
+pub struct InapplicablePattern {
+    pub object: Arc<ObjectDeclaration>,
+    pub method: Arc<Method>,
+    pub sent: Vec<Type>,
+}
+
+impl Diagnostic for InapplicablePattern {
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn source(&self) -> &Arc<Source> {
+        &self.method.source
+    }
+
+    fn range(&self) -> Range {
+        self.method.pattern.range()
+    }
+
+    fn message(&self) -> String {
+        let mut sent: Vec<_> = self.sent.iter().map(ToString::to_string).collect();
+        sent.sort();
+        format!(
+            "`{}` never matches, since `{}` is only sent {}",
+            self.method.source.slice(&self.method.pattern.range()),
+            self.object.symbol(),
+            sent.join(", ")
+        )
+    }
+}
@@ -0,0 +1,82 @@
+use crate::semantics::types::{Type, TypeError, TypeSlot};
+use crate::semantics::{AnalysisContext, Analyzer};
+use crate::syntax::{AnswerExpression, Expression, Navigator, Node};
+use crate::{Diagnostic, Diagnostics, Range, Severity, Source};
+use futures::future::join_all;
+use std::sync::Arc;
+
+pub struct CheckForMismatchedReplyTypes;
+
+#[async_trait]
+impl Analyzer for CheckForMismatchedReplyTypes {
+    type Input = ();
+    type Output = Diagnostics;
+
+    async fn analyze(&self, ctx: AnalysisContext<()>) -> Diagnostics {
+        let mut answers = vec![];
+        for method in ctx.navigator.all_methods() {
+            if let Some(reply_type) = &method.reply_type {
+                for expression in Navigator::new(method.clone()).all_expressions() {
+                    if let Expression::Answer(answer) = expression.as_ref() {
+                        answers.push((reply_type.clone(), answer.clone()));
+                    }
+                }
+            }
+        }
+
+        join_all(answers.into_iter().map(|(reply_type, answer)| {
+            let module = ctx.module.clone();
+            async move {
+                let slot = TypeSlot::covariant();
+                slot.resolve_required(module.resolve_type(reply_type).await)
+                    .await;
+                slot.resolve_apparent(module.get_type_of(answer.expression.clone()).await)
+                    .await;
+
+                match slot.check().await {
+                    Ok(()) | Err(TypeError::Unresolved) => return None,
+                    Err(_) => {}
+                }
+
+                Some(MismatchedReplyType {
+                    expected: slot.wait_for_required().await,
+                    actual: slot.wait_for_apparent().await,
+                    answer,
+                })
+            }
+        }))
+        .await
+        .into_iter()
+        .filter_map(|o| o)
+        .map(|m| Arc::new(m) as Arc<dyn Diagnostic>)
+        .collect()
+    }
+}
+
+#[derive(Debug)]
+struct MismatchedReplyType {
+    expected: Type,
+    actual: Type,
+    answer: Arc<AnswerExpression>,
+}
+
+impl Diagnostic for MismatchedReplyType {
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn source(&self) -> &Arc<Source> {
+        self.answer.source()
+    }
+
+    fn range(&self) -> Range {
+        self.answer.range()
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "Expected a reply of type {}, but got {}",
+            self.expected, self.actual
+        )
+    }
+}
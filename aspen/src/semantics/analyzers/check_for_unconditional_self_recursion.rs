@@ -0,0 +1,78 @@
+use crate::semantics::{AnalysisContext, Analyzer};
+use crate::syntax::{Expression, MessageSend, Node, Pattern};
+use crate::{Diagnostic, Diagnostics, Range, Severity, Source};
+use std::sync::Arc;
+
+/// Warns about methods that send `self` the very message they handle,
+/// which would never stop. Since there is no branching within a method
+/// body, any such statement is unconditional. Only the direct case is
+/// detected, so recursion through other methods or objects is missed.
+pub struct CheckForUnconditionalSelfRecursion;
+
+#[async_trait]
+impl Analyzer for CheckForUnconditionalSelfRecursion {
+    type Input = ();
+    type Output = Diagnostics;
+
+    async fn analyze(&self, ctx: AnalysisContext<()>) -> Diagnostics {
+        let mut diagnostics = Diagnostics::new();
+        for method in ctx.navigator.all_methods() {
+            for statement in method.statements.iter() {
+                let mut expression = &statement.expression;
+                if let Expression::Answer(answer) = expression.as_ref() {
+                    expression = &answer.expression;
+                }
+
+                if let Expression::MessageSend(send) = expression.as_ref() {
+                    if is_self_send_of_pattern(send, &method.pattern) {
+                        diagnostics.push(UnconditionalSelfRecursion(send.clone()));
+                    }
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+fn is_self_send_of_pattern(send: &MessageSend, pattern: &Pattern) -> bool {
+    if let Expression::SelfReference(_) = send.receiver.as_ref() {
+        pattern_matches_message(pattern, send.message.as_ref())
+    } else {
+        false
+    }
+}
+
+fn pattern_matches_message(pattern: &Pattern, message: &Expression) -> bool {
+    match (pattern, message) {
+        (Pattern::Integer(p), Expression::Integer(m)) => p.literal.kind == m.literal.kind,
+        (Pattern::Nullary(p), Expression::NullaryAtom(m)) => p.atom.lexeme() == m.atom.lexeme(),
+        (Pattern::Or(alternatives), m) => {
+            alternatives.iter().any(|p| pattern_matches_message(p, m))
+        }
+        _ => false,
+    }
+}
+
+#[derive(Debug)]
+pub struct UnconditionalSelfRecursion(pub Arc<MessageSend>);
+
+impl Diagnostic for UnconditionalSelfRecursion {
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn source(&self) -> &Arc<Source> {
+        self.0.source()
+    }
+
+    fn range(&self) -> Range {
+        self.0.range()
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "Sending `{}` to `self` from the method handling it will never stop",
+            self.0.source.slice(&self.0.message.range())
+        )
+    }
+}
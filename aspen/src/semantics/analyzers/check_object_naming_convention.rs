@@ -0,0 +1,70 @@
+use crate::semantics::{AnalysisContext, Analyzer};
+use crate::syntax::{Declaration, Node, ObjectDeclaration};
+use crate::{Diagnostic, Diagnostics, Range, Severity, Source};
+use std::sync::Arc;
+
+/// Reports objects whose names don't start with an uppercase letter,
+/// since objects are also types and would read like references
+/// otherwise. The severity is configured by the context, which can
+/// also turn the check off.
+pub struct CheckObjectNamingConvention;
+
+#[async_trait]
+impl Analyzer for CheckObjectNamingConvention {
+    type Input = ();
+    type Output = Diagnostics;
+
+    async fn analyze(&self, ctx: AnalysisContext<()>) -> Diagnostics {
+        let mut diagnostics = Diagnostics::new();
+        let severity = match ctx.host.context.naming_convention_severity() {
+            Some(severity) => severity,
+            None => return diagnostics,
+        };
+        for declaration in ctx.navigator.all_declarations() {
+            match declaration.as_ref() {
+                Declaration::Object(o) if !is_capitalized(o.symbol()) => {
+                    diagnostics.push(LowercaseObjectName(o.clone(), severity));
+                }
+                _ => {}
+            }
+        }
+        diagnostics
+    }
+}
+
+fn is_capitalized(name: &str) -> bool {
+    name.chars().next().map(char::is_uppercase).unwrap_or(true)
+}
+
+/// The name with its first letter in uppercase.
+pub fn capitalize_object_name(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+    }
+}
+
+#[derive(Debug)]
+pub struct LowercaseObjectName(pub Arc<ObjectDeclaration>, pub Severity);
+
+impl Diagnostic for LowercaseObjectName {
+    fn severity(&self) -> Severity {
+        self.1
+    }
+
+    fn source(&self) -> &Arc<Source> {
+        self.0.source()
+    }
+
+    fn range(&self) -> Range {
+        self.0.symbol.range()
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "Object names should start with an uppercase letter, like `{}`",
+            capitalize_object_name(self.0.symbol())
+        )
+    }
+}
@@ -1,4 +1,4 @@
-use crate::semantics::{AnalysisContext, Analyzer};
+use crate::semantics::{is_prelude, AnalysisContext, Analyzer};
 use crate::syntax::{
     Declaration, Inline, IntoNode, Node, ReferenceExpression, ReferenceTypeExpression, Root,
 };
@@ -78,6 +78,18 @@ async fn find_declaration<N: Node + 'static>(
             // TODO: Imports
         }
     }
+
+    // The prelude is visible everywhere, but can be shadowed by any
+    // other declaration.
+    if !is_prelude(ctx.module.uri()) {
+        if let Some(prelude) = ctx.host.prelude().await {
+            for (n, declaration) in prelude.exported_declarations().await {
+                if n == name {
+                    return Ok(declaration);
+                }
+            }
+        }
+    }
     Err(FindDeclarationError::NotFound)
 }
 
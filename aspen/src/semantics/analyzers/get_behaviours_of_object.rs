@@ -16,10 +16,17 @@ impl Analyzer for GetBehavioursOfObject {
         join_all(ctx.input.methods().map(|method| {
             let module = ctx.module.clone();
             async move {
-                let Method { pattern, .. } = method.as_ref();
+                let Method {
+                    pattern,
+                    reply_type,
+                    ..
+                } = method.as_ref();
                 Behaviour {
                     selector: module.get_type_of_pattern(pattern.clone()).await,
-                    reply: Type::Failed { diagnosed: true },
+                    reply: match reply_type {
+                        Some(t) => module.resolve_type(t.clone()).await,
+                        None => Type::Failed { diagnosed: true },
+                    },
                 }
             }
         }))
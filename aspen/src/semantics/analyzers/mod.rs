@@ -1,8 +1,13 @@
 mod check_all_references_are_defined;
 mod check_for_duplicate_exports;
+mod check_for_empty_object_bodies;
 mod check_for_failed_expression_type_inference;
 mod check_for_failed_type_expression_type_inference;
+mod check_for_inapplicable_patterns;
+mod check_for_mismatched_reply_types;
+mod check_for_unconditional_self_recursion;
 mod check_for_ununderstandable_messages;
+mod check_object_naming_convention;
 mod find_declaration;
 mod get_behaviours_of_object;
 mod get_exported_declarations;
@@ -11,9 +16,14 @@ mod get_type_of_type_expression;
 
 pub use self::check_all_references_are_defined::*;
 pub use self::check_for_duplicate_exports::*;
+pub use self::check_for_empty_object_bodies::*;
 pub use self::check_for_failed_expression_type_inference::*;
 pub use self::check_for_failed_type_expression_type_inference::*;
+pub use self::check_for_inapplicable_patterns::*;
+pub use self::check_for_mismatched_reply_types::*;
+pub use self::check_for_unconditional_self_recursion::*;
 pub use self::check_for_ununderstandable_messages::*;
+pub use self::check_object_naming_convention::*;
 pub use self::find_declaration::*;
 pub use self::get_behaviours_of_object::*;
 pub use self::get_exported_declarations::*;
@@ -1,7 +1,10 @@
-use crate::semantics::Module;
+use crate::semantics::{prelude, Module, TypeCache, PRELUDE_URI};
 use crate::syntax;
-use crate::{Context, Diagnostics, Range, Source, URI};
+use crate::syntax::{Expression, Node, TypeExpression};
+use crate::{Context, Diagnostics, Range, Severity, Source, URI};
+use futures::stream::{self, StreamExt};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -9,24 +12,84 @@ use tokio::sync::Mutex;
 pub struct Host {
     pub context: Arc<Context>,
     modules: Arc<Mutex<HashMap<URI, Arc<Module>>>>,
+    diagnostic_limit: Arc<AtomicUsize>,
+    type_cache: TypeCache,
 }
 
+/// The number of diagnostics reported per module before the rest
+/// are suppressed.
+pub const DEFAULT_DIAGNOSTIC_LIMIT: usize = 100;
+
+/// The number of sources parsed at the same time by `parse_all`.
+const PARSE_CONCURRENCY: usize = 16;
+
 impl Host {
     pub fn new(context: Arc<Context>) -> Host {
         Host {
             context,
             modules: Arc::new(Mutex::new(HashMap::new())),
+            diagnostic_limit: Arc::new(AtomicUsize::new(DEFAULT_DIAGNOSTIC_LIMIT)),
+            type_cache: TypeCache::new(),
         }
     }
 
+    /// The analyses shared between the modules of the host.
+    pub fn type_cache(&self) -> &TypeCache {
+        &self.type_cache
+    }
+
+    pub fn diagnostic_limit(&self) -> usize {
+        self.diagnostic_limit.load(Ordering::Relaxed)
+    }
+
+    /// Sets the maximum number of diagnostics reported per module. Use
+    /// `usize::MAX` to report every diagnostic.
+    pub fn set_diagnostic_limit(&self, limit: usize) {
+        self.diagnostic_limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// Creates a host with the prelude and the given sources. A source
+    /// with the prelude URI replaces the built-in prelude.
     pub async fn from<I: IntoIterator<Item = Arc<Source>>>(context: Arc<Context>, i: I) -> Self {
         let host = Host::new(context);
-        for source in i {
-            host.set(source).await;
-        }
+        host.load_prelude().await;
+        host.parse_all(i).await;
         host
     }
 
+    /// Parses the sources in parallel, on tasks of their own, and adds
+    /// them all to the host at once, so that no module sees only some of
+    /// the others.
+    pub async fn parse_all<I: IntoIterator<Item = Arc<Source>>>(&self, i: I) {
+        let sources: Vec<_> = i.into_iter().collect();
+        let parsed: Vec<Module> = stream::iter(sources)
+            .map(|source| tokio::spawn(Module::parse(source, self.clone())))
+            .buffered(PARSE_CONCURRENCY)
+            .map(|module| module.expect("parsing a module panicked"))
+            .collect()
+            .await;
+
+        let mut modules = self.modules.lock().await;
+        self.type_cache.invalidate().await;
+        for module in parsed {
+            let uri = module.uri().clone();
+            modules.insert(uri, Arc::new(module));
+        }
+    }
+
+    /// Loads the built-in prelude, unless a prelude has already been
+    /// set.
+    pub async fn load_prelude(&self) -> Arc<Module> {
+        match self.prelude().await {
+            Some(module) => module,
+            None => self.set(prelude()).await,
+        }
+    }
+
+    pub async fn prelude(&self) -> Option<Arc<Module>> {
+        self.get(&URI::from(PRELUDE_URI)).await
+    }
+
     pub async fn diagnostics(&self) -> Diagnostics {
         let modules: Vec<Arc<Module>> = {
             let lock = self.modules.lock().await;
@@ -42,20 +105,58 @@ impl Host {
         .into()
     }
 
+    /// The diagnostics of all modules that are at least as severe as
+    /// `min`.
+    pub async fn diagnostics_at_least(&self, min: Severity) -> Diagnostics {
+        self.diagnostics()
+            .await
+            .into_iter()
+            .filter(|d| d.severity() >= min)
+            .collect()
+    }
+
     pub async fn modules(&self) -> Vec<Arc<Module>> {
         self.modules.lock().await.values().cloned().collect()
     }
 
     pub async fn set(&self, source: Arc<Source>) -> Arc<Module> {
+        self.replace(source).await.0
+    }
+
+    /// Like `set`, but also returns the module that was replaced, if
+    /// any, so that callers can compare the two versions.
+    pub async fn replace(&self, source: Arc<Source>) -> (Arc<Module>, Option<Arc<Module>>) {
         let host = self.clone();
         let mut modules = self.modules.lock().await;
         let uri = source.uri().clone();
-        modules.insert(uri.clone(), Arc::new(Module::parse(source, host).await));
-        modules.get(&uri).unwrap().clone()
+        let module = Arc::new(Module::parse(source, host).await);
+        self.type_cache.invalidate().await;
+        let previous = modules.insert(uri, module.clone());
+        (module, previous)
+    }
+
+    /// Removes the module with the given URI, returning it if it was
+    /// tracked by the host.
+    pub async fn remove(&self, uri: &URI) -> Option<Arc<Module>> {
+        self.type_cache.invalidate().await;
+        self.modules.lock().await.remove(uri)
+    }
+
+    pub async fn contains(&self, uri: &URI) -> bool {
+        self.modules.lock().await.contains_key(uri)
     }
 
-    pub async fn remove(&self, uri: &URI) {
-        self.modules.lock().await.remove(uri);
+    /// The URIs of all modules tracked by the host, in order.
+    pub async fn uris(&self) -> Vec<URI> {
+        let mut uris = self
+            .modules
+            .lock()
+            .await
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        uris.sort();
+        uris
     }
 
     pub async fn get(&self, uri: &URI) -> Option<Arc<Module>> {
@@ -66,16 +167,138 @@ impl Host {
         }
     }
 
+    /// Applies the edits to the module with the given URI. If the edits
+    /// changed any of its declarations, the URIs of the other modules
+    /// that depend on it, either before or after the edit, are
+    /// returned. Those modules are reparsed so that their analyses don't
+    /// refer to the replaced module, and should be checked again.
+    ///
+    /// Returns `None` without doing anything if the host has no module
+    /// with the URI.
     pub async fn apply_edits<I: IntoIterator<Item = (Option<Range>, String)>>(
         &self,
         uri: &URI,
         edits: I,
-    ) {
-        if let Some(module) = self.get(uri).await {
-            let new = module.source.apply_edits(edits);
+    ) -> Option<Vec<URI>> {
+        let module = self.get(uri).await?;
+
+        let (module, previous) = self.replace(module.source.apply_edits(edits)).await;
+        let previous = previous.unwrap_or_else(|| module.clone());
+        let before = Self::declarations_of(&previous);
+        let after = Self::declarations_of(&module);
+        if before == after {
+            return Some(vec![]);
+        }
 
-            self.set(new).await;
+        // Modules with references resolving to the previous module, and
+        // modules with references to names that only the edited module
+        // declares, are both found by looking for either set of names.
+        let mut declared: Vec<_> = before.into_iter().map(|(name, _, _)| name).collect();
+        for (name, _, _) in after {
+            if !declared.contains(&name) {
+                declared.push(name);
+            }
         }
+        let dependents = self.dependents_declaring(uri, &declared).await;
+
+        for dependent in dependents.iter() {
+            if let Some(module) = self.get(dependent).await {
+                self.set(module.source.clone()).await;
+            }
+        }
+        Some(dependents)
+    }
+
+    /// Moves the module with the URI `old` to the URI `new`, as when
+    /// its file is renamed, and returns it. The modules that depend on
+    /// it are reparsed so that their references resolve to the moved
+    /// module.
+    ///
+    /// Returns `None` without doing anything if the host has no module
+    /// with the URI `old`.
+    pub async fn rename_source(&self, old: &URI, new: URI) -> Option<Arc<Module>> {
+        let module = self.get(old).await?;
+        let dependents = self.dependents(old).await;
+
+        self.remove(old).await;
+        let renamed = self.set(module.source.with_uri(new)).await;
+
+        for dependent in dependents {
+            if let Some(module) = self.get(&dependent).await {
+                self.set(module.source.clone()).await;
+            }
+        }
+        Some(renamed)
+    }
+
+    /// The modules, other than the one with the given URI, that have
+    /// references resolving to declarations in it, or unresolved
+    /// references to names that it declares.
+    pub async fn dependents(&self, uri: &URI) -> Vec<URI> {
+        let declared = match self.get(uri).await {
+            Some(module) => Self::declarations_of(&module)
+                .into_iter()
+                .map(|(name, _, _)| name)
+                .collect::<Vec<_>>(),
+            None => vec![],
+        };
+        self.dependents_declaring(uri, &declared).await
+    }
+
+    /// The name, range and code of each declaration in the module.
+    fn declarations_of(module: &Module) -> Vec<(String, Range, String)> {
+        module
+            .navigate()
+            .all_declarations()
+            .map(|d| {
+                let range = d.range();
+                let code = module.source.slice(&range).to_string();
+                (d.symbol().to_string(), range, code)
+            })
+            .collect()
+    }
+
+    async fn dependents_declaring(&self, uri: &URI, declared: &[String]) -> Vec<URI> {
+        let mut dependents = vec![];
+        for module in self.modules().await {
+            if module.uri() != uri && Self::depends_on(&module, uri, &declared).await {
+                dependents.push(module.uri().clone());
+            }
+        }
+        dependents
+    }
+
+    async fn depends_on(module: &Arc<Module>, uri: &URI, declared: &[String]) -> bool {
+        let navigator = module.navigate();
+
+        for expression in navigator.all_expressions() {
+            if let Expression::Reference(reference) = expression.as_ref() {
+                if module.binding_referenced_by(reference).is_some() {
+                    continue;
+                }
+                let name = reference.symbol.identifier.lexeme();
+                match module.declaration_referenced_by(reference.clone()).await {
+                    Some(declaration) if declaration.source().uri() == uri => return true,
+                    None if declared.iter().any(|d| d == name) => return true,
+                    _ => {}
+                }
+            }
+        }
+
+        for type_expression in navigator.all_type_expressions() {
+            let TypeExpression::Reference(reference) = type_expression.as_ref();
+            let name = reference.symbol.identifier.lexeme();
+            match module
+                .declaration_referenced_by_type(reference.clone())
+                .await
+            {
+                Some(declaration) if declaration.source().uri() == uri => return true,
+                None if declared.iter().any(|d| d == name) => return true,
+                _ => {}
+            }
+        }
+
+        false
     }
 
     pub async fn find_declaration(&self, name: &str) -> Option<Arc<syntax::Declaration>> {
@@ -89,3 +312,299 @@ impl Host {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::{Declaration, ObjectDeclaration};
+
+    #[tokio::test]
+    async fn replacing_a_module_returns_the_previous_one() {
+        let host = Host::new(Arc::new(Context::test()));
+
+        let (first, previous) = host.replace(Source::new("test:x", "object A.")).await;
+        assert!(previous.is_none());
+
+        let (second, previous) = host.replace(Source::new("test:x", "object B.")).await;
+        assert!(Arc::ptr_eq(&previous.unwrap(), &first));
+        assert!(Arc::ptr_eq(&host.get(second.uri()).await.unwrap(), &second));
+    }
+
+    #[tokio::test]
+    async fn renaming_a_source_moves_its_module() {
+        let host = Host::new(Arc::new(Context::test()));
+        host.set(Source::new("test:a", "object A.")).await;
+        host.set(Source::new("test:b", "object B { 1 -> A. }"))
+            .await;
+
+        let old = URI::from("test:a");
+        let new = URI::from("test:renamed");
+        let renamed = host.rename_source(&old, new.clone()).await.unwrap();
+
+        assert!(host.get(&old).await.is_none());
+        assert_eq!(renamed.uri(), &new);
+        assert_eq!(renamed.source.slice(0..renamed.source.len()), "object A.");
+        assert!(Arc::ptr_eq(&host.get(&new).await.unwrap(), &renamed));
+
+        let b = host.get(&URI::from("test:b")).await.unwrap();
+        let reference = b
+            .navigate()
+            .traverse()
+            .filter_map(|n| n.node.clone().as_reference_expression())
+            .next()
+            .unwrap();
+        let declaration = b.declaration_referenced_by(reference).await.unwrap();
+        assert_eq!(declaration.source().uri(), &new);
+
+        assert!(host.rename_source(&old, new).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn diagnostics_can_be_filtered_by_severity() {
+        let host = Host::from(
+            Arc::new(Context::test()),
+            vec![
+                Source::from_bytes("test:warning", b"object X. \xFF"),
+                Source::new("test:error", "object"),
+            ],
+        )
+        .await;
+
+        let errors = host.diagnostics_at_least(Severity::Error).await;
+        assert!(errors.iter().all(|d| d.severity() == Severity::Error));
+        assert!(!errors.is_empty());
+
+        let warnings = host.diagnostics_at_least(Severity::Warning).await;
+        assert!(warnings.iter().any(|d| d.severity() == Severity::Warning));
+        assert!(warnings.iter().any(|d| d.severity() == Severity::Error));
+        assert!(warnings.len() > errors.len());
+    }
+
+    #[tokio::test]
+    async fn prelude_objects_resolve_without_declaration() {
+        let host = Host::from(
+            Arc::new(Context::test()),
+            vec![Source::new("test:x", "object X { a! -> Unit. }")],
+        )
+        .await;
+        let module = host.get(&"test:x".into()).await.unwrap();
+        assert!(module.diagnostics().await.is_empty());
+
+        let reference = module
+            .navigate()
+            .down_to_cast(|n| n.as_reference_expression())
+            .unwrap();
+        let declaration = module.declaration_referenced_by(reference).await.unwrap();
+        assert_eq!(declaration.source().uri(), &URI::from(PRELUDE_URI));
+    }
+
+    #[tokio::test]
+    async fn hosts_of_contexts_have_the_prelude() {
+        let host = Arc::new(Context::test()).host().await;
+        let module = host
+            .set(Source::new("test:x", "object X { a! -> Unit. }"))
+            .await;
+        assert!(module.diagnostics().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn prelude_can_be_overridden_and_shadowed() {
+        let host = Host::from(
+            Arc::new(Context::test()),
+            vec![
+                Source::new(PRELUDE_URI, "object Nil."),
+                Source::new("test:x", "object Unit. object X { a! -> Unit. b! -> Nil. }"),
+            ],
+        )
+        .await;
+        assert!(host.diagnostics().await.is_empty());
+
+        let module = host.get(&"test:x".into()).await.unwrap();
+        for reference in module
+            .navigate()
+            .traverse()
+            .filter_map(|n| n.node.clone().as_reference_expression())
+        {
+            let declaration = module.declaration_referenced_by(reference.clone()).await;
+            let expected = match reference.symbol.identifier.lexeme() {
+                "Unit" => "test:x",
+                _ => PRELUDE_URI,
+            };
+            assert_eq!(declaration.unwrap().source().uri(), &URI::from(expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn renaming_an_export_rechecks_dependents() {
+        let host = Host::new(Arc::new(Context::test()));
+        let declaring = host.set(Source::inline("repl:1", "object X.")).await;
+        let dependent = host.set(Source::inline("repl:2", "X.")).await;
+        assert!(dependent.diagnostics().await.is_empty());
+
+        let rechecked = host
+            .apply_edits(declaring.uri(), vec![(None, "object Y.".to_string())])
+            .await;
+        assert_eq!(rechecked, Some(vec![dependent.uri().clone()]));
+
+        let dependent = host.get(dependent.uri()).await.unwrap();
+        assert!(!dependent.diagnostics().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn edits_outside_of_declarations_leave_dependents_alone() {
+        let host = Host::new(Arc::new(Context::test()));
+        let declaring = host.set(Source::inline("repl:1", "object X.")).await;
+        let dependent = host.set(Source::inline("repl:2", "X.")).await;
+
+        let end = declaring.source.range_all().end;
+        let rechecked = host
+            .apply_edits(
+                declaring.uri(),
+                vec![(
+                    Some(Range {
+                        start: end.clone(),
+                        end,
+                    }),
+                    "\n".to_string(),
+                )],
+            )
+            .await;
+        assert_eq!(rechecked, Some(vec![]));
+        assert!(Arc::ptr_eq(
+            &host.get(dependent.uri()).await.unwrap(),
+            &dependent
+        ));
+    }
+
+    #[tokio::test]
+    async fn editing_an_unknown_module_is_reported() {
+        let host = Host::new(Arc::new(Context::test()));
+
+        let result = host
+            .apply_edits(
+                &"test:unknown".into(),
+                vec![(None, "object X.".to_string())],
+            )
+            .await;
+
+        assert!(result.is_none());
+        assert!(host.get(&"test:unknown".into()).await.is_none());
+        assert!(host.remove(&"test:unknown".into()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn contains_reflects_set_and_remove() {
+        let host = Host::new(Arc::new(Context::test()));
+        let uri = URI::from("test:x");
+        assert!(!host.contains(&uri).await);
+
+        host.set(Source::new("test:x", "object X.")).await;
+        host.set(Source::new("test:y", "object Y.")).await;
+        assert!(host.contains(&uri).await);
+        assert_eq!(host.uris().await, vec![uri.clone(), URI::from("test:y")]);
+
+        host.remove(&uri).await;
+        assert!(!host.contains(&uri).await);
+        assert_eq!(host.uris().await, vec![URI::from("test:y")]);
+    }
+
+    #[tokio::test]
+    async fn all_modules_are_available_after_construction() {
+        let sources = (0..100)
+            .map(|i| Source::new(URI::new("test", i.to_string()), format!("object X{}.", i)))
+            .collect::<Vec<_>>();
+        let host = Host::from(Arc::new(Context::test()), sources).await;
+
+        assert_eq!(host.modules().await.len(), 101);
+        for i in 0..100 {
+            let uri = URI::new("test", i.to_string());
+            assert!(host.get(&uri).await.is_some());
+        }
+        assert!(host.diagnostics().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn later_sources_replace_earlier_ones_with_the_same_uri() {
+        let host = Host::from(
+            Arc::new(Context::test()),
+            vec![
+                Source::new("test:x", "object A."),
+                Source::new("test:x", "object B."),
+            ],
+        )
+        .await;
+
+        let module = host.get(&URI::from("test:x")).await.unwrap();
+        assert_eq!(module.source.slice(0..module.source.len()), "object B.");
+    }
+
+    async fn behaviours(module: &Arc<Module>, object: &Arc<ObjectDeclaration>) -> Vec<String> {
+        module
+            .get_behaviours_of_object(object.clone())
+            .await
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn behaviours_of_imported_objects_are_cached() {
+        let host = Host::new(Arc::new(Context::test()));
+        let a = host
+            .set(Source::new("test:a", "object A { 1 -> 2. }"))
+            .await;
+        let b = host.set(Source::new("test:b", "object B.")).await;
+        let c = host.set(Source::new("test:c", "object C.")).await;
+
+        let object = only_object(&a);
+
+        let first = behaviours(&b, &object).await;
+        assert_eq!(host.type_cache().hits(), 0);
+        assert_eq!(behaviours(&c, &object).await, first);
+        assert_eq!(host.type_cache().hits(), 1);
+
+        // Changing the declaring module invalidates what was cached.
+        let a = host
+            .set(Source::new("test:a", "object A { 1 -> 3. }"))
+            .await;
+        let object = only_object(&a);
+        assert_ne!(behaviours(&b, &object).await, first);
+        assert_eq!(host.type_cache().hits(), 1);
+        behaviours(&c, &object).await;
+        assert_eq!(host.type_cache().hits(), 2);
+
+        // So does changing any other module, which the behaviours may
+        // depend on.
+        host.set(Source::new("test:d", "object D.")).await;
+        behaviours(&b, &object).await;
+        assert_eq!(host.type_cache().hits(), 2);
+    }
+
+    #[tokio::test]
+    async fn behaviours_of_superseded_objects_are_not_cached() {
+        let host = Host::new(Arc::new(Context::test()));
+        let a = host
+            .set(Source::new("test:a", "object A { 1 -> 2. }"))
+            .await;
+        let b = host.set(Source::new("test:b", "object B.")).await;
+        let superseded = only_object(&a);
+
+        host.set(Source::new("test:a", "object A { 1 -> 3. }"))
+            .await;
+        behaviours(&b, &superseded).await;
+        behaviours(&b, &superseded).await;
+        assert_eq!(host.type_cache().hits(), 0);
+    }
+
+    fn only_object(module: &Arc<Module>) -> Arc<ObjectDeclaration> {
+        match module
+            .navigate()
+            .all_declarations()
+            .next()
+            .unwrap()
+            .as_ref()
+        {
+            Declaration::Object(o) => o.clone(),
+        }
+    }
+}
@@ -2,8 +2,13 @@ mod analyzer;
 mod analyzers;
 mod host;
 mod module;
+mod prelude;
+mod type_cache;
 pub mod types;
 
 pub use self::analyzer::*;
+pub use self::analyzers::capitalize_object_name;
 pub use self::host::*;
 pub use self::module::*;
+pub use self::prelude::*;
+pub use self::type_cache::*;
@@ -1,33 +1,54 @@
-use crate::semantics::types::{Behaviour, Type};
+use crate::semantics::types::{Behaviour, Type, TypeExplanation};
 use crate::semantics::*;
 use crate::syntax::*;
-use crate::{Diagnostics, Source, SourceKind, URI};
+use crate::{
+    Diagnostics, InternalCompilerError, InvalidEncoding, Location, Source, SourceKind, URI,
+};
+use futures::FutureExt;
+use std::collections::HashMap;
 use std::fmt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
 use std::time::SystemTime;
-use tokio::sync::Mutex;
 
 pub struct Module {
     pub source: Arc<Source>,
     root_node: Arc<Root>,
-    diagnostics: Mutex<Diagnostics>,
+    /// The diagnostics found while parsing the source.
+    diagnostics: Diagnostics,
+    /// The statements binding the names of references to local
+    /// bindings, keyed by the address of the reference.
+    bindings: HashMap<usize, Arc<Statement>>,
     pub host: Host,
 
     // Analyzers
     exported_declarations: MemoOut<analyzers::GetExportedDeclarations>,
-    collect_diagnostics: Once<
+    collect_diagnostics: MemoOut<
         MergeTwo<
             MergeTwo<
                 MergeTwo<
                     MergeTwo<
-                        analyzers::CheckForDuplicateExports,
-                        analyzers::CheckAllReferencesAreDefined,
+                        MergeTwo<
+                            MergeTwo<
+                                MergeTwo<
+                                    MergeTwo<
+                                        analyzers::CheckForDuplicateExports,
+                                        analyzers::CheckAllReferencesAreDefined,
+                                    >,
+                                    analyzers::CheckForFailedExpressionTypeInference,
+                                >,
+                                analyzers::CheckForFailedTypeExpressionTypeInference,
+                            >,
+                            analyzers::CheckForUnunderstandableMessages,
+                        >,
+                        analyzers::CheckForMismatchedReplyTypes,
                     >,
-                    analyzers::CheckForFailedExpressionTypeInference,
+                    analyzers::CheckForUnconditionalSelfRecursion,
                 >,
-                analyzers::CheckForFailedTypeExpressionTypeInference,
+                analyzers::CheckForEmptyObjectBodies,
             >,
-            analyzers::CheckForUnunderstandableMessages,
+            analyzers::CheckObjectNamingConvention,
         >,
     >,
     find_declaration: Memo<analyzers::FindDeclaration, usize>,
@@ -39,21 +60,38 @@ pub struct Module {
 
 impl Module {
     pub async fn parse(source: Arc<Source>, host: Host) -> Module {
-        let (root_node, diagnostics) = Parser::new(source.clone()).parse().await;
+        let (root_node, mut diagnostics) = Parser::parse_lenient(source.clone()).await;
+
+        for range in source.invalid_ranges() {
+            diagnostics.push(InvalidEncoding(source.clone(), range));
+        }
+
+        if cfg!(debug_assertions) {
+            if let Some(module) = root_node.clone().as_module() {
+                module.validate_ranges();
+            }
+        }
+
+        let bindings = Self::resolve_bindings(&Navigator::new(root_node.clone()));
 
         Module {
             source,
             root_node,
-            diagnostics: Mutex::new(diagnostics),
+            diagnostics,
+            bindings,
             host,
 
             exported_declarations: MemoOut::of(analyzers::GetExportedDeclarations),
-            collect_diagnostics: Once::of(
+            collect_diagnostics: MemoOut::of(
                 (analyzers::CheckForDuplicateExports)
                     .and(analyzers::CheckAllReferencesAreDefined)
                     .and(analyzers::CheckForFailedExpressionTypeInference)
                     .and(analyzers::CheckForFailedTypeExpressionTypeInference)
-                    .and(analyzers::CheckForUnunderstandableMessages),
+                    .and(analyzers::CheckForUnunderstandableMessages)
+                    .and(analyzers::CheckForMismatchedReplyTypes)
+                    .and(analyzers::CheckForUnconditionalSelfRecursion)
+                    .and(analyzers::CheckForEmptyObjectBodies)
+                    .and(analyzers::CheckObjectNamingConvention),
             ),
             find_declaration: Memo::of(analyzers::FindDeclaration),
             find_type_declaration: Memo::of(analyzers::FindTypeDeclaration),
@@ -98,16 +136,50 @@ impl Module {
         analyzer.analyze(ctx).await
     }
 
-    pub async fn diagnostics(self: &Arc<Self>) -> Diagnostics {
-        let d = self.run_analyzer(&self.collect_diagnostics, ()).await;
+    /// Runs part of the compilation of the module, turning a panic
+    /// into an internal compiler error instead of aborting the process.
+    pub async fn catch_internal_errors<F: Future>(
+        &self,
+        f: F,
+    ) -> Result<F::Output, InternalCompilerError> {
+        AssertUnwindSafe(f)
+            .catch_unwind()
+            .await
+            .map_err(|payload| InternalCompilerError::from_panic(self.source.clone(), payload))
+    }
 
-        let mut diagnostics = self.diagnostics.lock().await;
+    pub async fn diagnostics(self: &Arc<Self>) -> Diagnostics {
+        // Inapplicable patterns depend on every other module, so they
+        // aren't memoized with the rest.
+        let collect = async {
+            let mut d = self.run_analyzer(&self.collect_diagnostics, ()).await;
+            d.push_all(
+                self.run_analyzer(&analyzers::CheckForInapplicablePatterns, ())
+                    .await,
+            );
+            d
+        };
+        self.diagnostics_collected_by(collect).await
+    }
 
-        if !d.is_empty() {
-            diagnostics.push_all(d);
-        }
+    /// The diagnostics of parsing the module, along with those collected
+    /// by the future, or an internal compiler error if it panics.
+    async fn diagnostics_collected_by<F: Future<Output = Diagnostics>>(
+        &self,
+        collect: F,
+    ) -> Diagnostics {
+        let d = match self.catch_internal_errors(collect).await {
+            Ok(d) => d,
+            Err(error) => {
+                let mut d = Diagnostics::new();
+                d.push(error);
+                d
+            }
+        };
 
-        diagnostics.clone()
+        let mut diagnostics = self.diagnostics.clone();
+        diagnostics.push_all(d);
+        diagnostics.capped(self.host.diagnostic_limit())
     }
 
     pub async fn exported_declarations(self: &Arc<Self>) -> Vec<(String, Arc<Declaration>)> {
@@ -123,6 +195,42 @@ impl Module {
             .ok()
     }
 
+    /// The statement in the same method that binds the name of the
+    /// reference, if the reference refers to a local binding. Only
+    /// statements before the reference are considered, and the latest
+    /// of those binding the name wins.
+    pub fn binding_referenced_by(
+        &self,
+        reference: &Arc<ReferenceExpression>,
+    ) -> Option<Arc<Statement>> {
+        self.bindings
+            .get(&(Arc::as_ptr(reference) as usize))
+            .cloned()
+    }
+
+    /// Resolves the references to local bindings in every method in
+    /// one pass, by walking the statements of each method in order.
+    fn resolve_bindings(root: &Arc<Navigator>) -> HashMap<usize, Arc<Statement>> {
+        let mut bindings = HashMap::new();
+        for method in root.all_methods() {
+            let mut bound: HashMap<&str, &Arc<Statement>> = HashMap::new();
+            for statement in method.statements.iter() {
+                for expression in Navigator::new(statement.expression.clone()).all_expressions() {
+                    if let Expression::Reference(reference) = expression.as_ref() {
+                        let name = reference.symbol.identifier.lexeme();
+                        if let Some(statement) = bound.get(name) {
+                            bindings.insert(Arc::as_ptr(reference) as usize, (*statement).clone());
+                        }
+                    }
+                }
+                if let Some(name) = statement.bound_name() {
+                    bound.insert(name, statement);
+                }
+            }
+        }
+        bindings
+    }
+
     pub async fn declaration_referenced_by_type(
         self: &Arc<Self>,
         reference: Arc<ReferenceTypeExpression>,
@@ -132,45 +240,124 @@ impl Module {
             .ok()
     }
 
+    /// The sources of the other modules that declare something that
+    /// this module refers to.
+    pub async fn dependencies(self: &Arc<Self>) -> Vec<Arc<Source>> {
+        let navigator = self.navigate();
+        let mut declarations = vec![];
+
+        for expression in navigator.all_expressions() {
+            if let Expression::Reference(reference) = expression.as_ref() {
+                if self.binding_referenced_by(reference).is_some() {
+                    continue;
+                }
+                declarations.extend(self.declaration_referenced_by(reference.clone()).await);
+            }
+        }
+
+        for type_expression in navigator.all_type_expressions() {
+            let TypeExpression::Reference(reference) = type_expression.as_ref();
+            declarations.extend(self.declaration_referenced_by_type(reference.clone()).await);
+        }
+
+        let mut dependencies: Vec<Arc<Source>> = vec![];
+        for declaration in declarations {
+            let source = declaration.source();
+            if source.uri() != self.uri() && !dependencies.iter().any(|s| s.uri() == source.uri()) {
+                dependencies.push(source.clone());
+            }
+        }
+        dependencies
+    }
+
     pub async fn get_type_of(self: &Arc<Self>, expression: Arc<Expression>) -> Type {
         self.run_analyzer(&self.get_type_of_expression, expression)
             .await
     }
 
+    /// The type of the expression entered into an inline source, like a
+    /// line in the REPL. Inline declarations have no result.
+    pub async fn inline_result_type(self: &Arc<Self>) -> Option<Type> {
+        match self.root_node.as_ref() {
+            Root::Inline(inline) => match inline.as_ref() {
+                Inline::Expression(expression, _) => {
+                    Some(self.get_type_of(expression.clone()).await)
+                }
+                Inline::Declaration(_) => None,
+            },
+            Root::Module(_) => None,
+        }
+    }
+
     pub async fn resolve_type(self: &Arc<Self>, expression: Arc<TypeExpression>) -> Type {
         self.run_analyzer(&self.get_type_of_type_expression, expression)
             .await
     }
 
+    /// The behaviours of an object. Objects declared in other modules
+    /// are analyzed by the module declaring them, and the result is
+    /// shared with every other module through the host's type cache.
     pub async fn get_behaviours_of_object(
         self: &Arc<Self>,
         object: Arc<ObjectDeclaration>,
     ) -> Vec<Behaviour> {
-        self.run_analyzer(&self.get_behaviours_of_object, object)
-            .await
+        let uri = object.source.uri().clone();
+        if &uri == self.uri() {
+            return self
+                .run_analyzer(&self.get_behaviours_of_object, object)
+                .await;
+        }
+
+        let cache = self.host.type_cache();
+        if let Some(behaviours) = cache.behaviours(&object).await {
+            return behaviours;
+        }
+
+        // Declarations of modules that have since been replaced are
+        // analyzed here, and never cached.
+        let generation = cache.generation().await;
+        let declaring = match self.host.get(&uri).await {
+            Some(declaring) if declaring.declares(&object) => declaring,
+            _ => {
+                return self
+                    .run_analyzer(&self.get_behaviours_of_object, object)
+                    .await
+            }
+        };
+
+        let behaviours = declaring
+            .run_analyzer(&declaring.get_behaviours_of_object, object.clone())
+            .await;
+        cache
+            .insert_behaviours(generation, object, behaviours.clone())
+            .await;
+        behaviours
+    }
+
+    fn declares(&self, object: &Arc<ObjectDeclaration>) -> bool {
+        self.navigate()
+            .all_declarations()
+            .any(|declaration| match declaration.as_ref() {
+                Declaration::Object(o) => Arc::ptr_eq(o, object),
+            })
     }
 
     pub async fn get_behaviours_of_type(self: &Arc<Self>, type_: Type) -> Vec<Behaviour> {
         match type_ {
             Type::Failed { .. } => vec![],
-            Type::Integer(Some(i)) => vec![
-                Behaviour {
-                    selector: Type::Atom(Some("increment!".into())),
-                    reply: Type::Integer(Some(i + 1)),
-                },
-                Behaviour {
-                    selector: Type::Integer(None),
-                    reply: Type::Integer(None),
-                },
-            ],
-            Type::Integer(None) => vec![
+            // Incrementing an integer shifts its bounds by one, so that
+            // e.g. a non-negative integer stays non-negative.
+            Type::Integer { lower, upper } => vec![
                 Behaviour {
                     selector: Type::Atom(Some("increment!".into())),
-                    reply: Type::Integer(None),
+                    reply: Type::Integer {
+                        lower: lower.and_then(|l| l.checked_add(1)),
+                        upper: upper.and_then(|u| u.checked_add(1)),
+                    },
                 },
                 Behaviour {
-                    selector: Type::Integer(None),
-                    reply: Type::Integer(None),
+                    selector: Type::any_integer(),
+                    reply: Type::any_integer(),
                 },
             ],
             Type::Float(_) => vec![],
@@ -180,16 +367,114 @@ impl Module {
         }
     }
 
+    /// The behaviours of the type of the receiver written right
+    /// before the given location, i.e. the messages that can be
+    /// sent from there.
+    pub async fn behaviours_at(self: &Arc<Self>, position: Location) -> Vec<Behaviour> {
+        match self.receiver_before(&position) {
+            None => vec![],
+            Some(expression) => {
+                let type_ = self.get_type_of(expression).await;
+                self.get_behaviours_of_type(type_).await
+            }
+        }
+    }
+
+    /// How the type of the innermost expression at the given location
+    /// was inferred.
+    pub async fn explain_type_at(self: &Arc<Self>, position: Location) -> Option<TypeExplanation> {
+        let expression = self
+            .navigate()
+            .smallest_node_at(&position)
+            .and_then(|nav| nav.up_to_cast(|n| n.as_expression()))?;
+
+        Some(TypeExplanation::of(self.clone(), expression).await)
+    }
+
+    /// The outermost expression that ends right before the given
+    /// location, ignoring whitespace. This is the expression that
+    /// a message written at the location would be sent to.
+    pub fn receiver_before(&self, position: &Location) -> Option<Arc<Expression>> {
+        if position.offset > self.source.len() {
+            return None;
+        }
+
+        let mut receiver: Option<Arc<Expression>> = None;
+        for expression in self.navigate().all_expressions() {
+            let range = expression.range();
+            if range.end.offset > position.offset
+                || !self
+                    .source
+                    .slice(range.end.offset..position.offset)
+                    .trim()
+                    .is_empty()
+            {
+                continue;
+            }
+
+            match &receiver {
+                Some(r) if r.range().start <= range.start => {}
+                _ => receiver = Some(expression),
+            }
+        }
+        receiver
+    }
+
+    /// The object declaration that the node is part of, which is what
+    /// `self` refers to within it.
+    pub fn enclosing_object(&self, node: Arc<dyn Node>) -> Option<Arc<ObjectDeclaration>> {
+        self.navigate()
+            .down_to(&node)?
+            .up_to_cast(|n| n.as_declaration())
+            .map(|d| match d.as_ref() {
+                Declaration::Object(o) => o.clone(),
+            })
+    }
+
+    /// Whether the sender of the message expects a reply. Commands are
+    /// conventionally fire-and-forget, while queries, like any other
+    /// message, expect one.
+    pub async fn expects_reply(self: &Arc<Self>, send: &Arc<MessageSend>) -> bool {
+        match self.get_type_of(send.message.clone()).await {
+            Type::Atom(Some(atom)) => AtomKind::of(&atom)
+                .map(AtomKind::expects_reply)
+                .unwrap_or(true),
+            _ => true,
+        }
+    }
+
     pub async fn get_type_of_pattern(self: &Arc<Self>, pattern: Arc<Pattern>) -> Type {
-        match pattern.as_ref() {
+        Self::type_of_pattern(pattern.as_ref())
+    }
+
+    /// Alternatives of the same value have that value's type, while
+    /// other alternatives of the same kind widen to the kind as a whole.
+    fn type_of_pattern(pattern: &Pattern) -> Type {
+        match pattern {
             Pattern::Integer(i) => match &i.literal.kind {
-                TokenKind::IntegerLiteral(i, _) => Type::Integer(Some(*i)),
-                _ => Type::Integer(None),
+                TokenKind::IntegerLiteral(i, _) => Type::integer(*i),
+                _ => Type::any_integer(),
             },
             Pattern::Nullary(a) => match &a.atom.kind {
-                TokenKind::NullaryAtom => Type::Atom(Some(a.atom.lexeme().into())),
-                _ => Type::Integer(None),
+                TokenKind::NullaryAtom(_) => Type::Atom(Some(a.atom.lexeme().into())),
+                _ => Type::any_integer(),
             },
+            Pattern::Or(alternatives) => {
+                let types: Vec<_> = alternatives
+                    .iter()
+                    .map(|p| Self::type_of_pattern(p))
+                    .collect();
+                if types.iter().all(|t| t == &types[0]) {
+                    return types[0].clone();
+                }
+                if types.iter().all(|t| matches!(t, Type::Integer { .. })) {
+                    Type::any_integer()
+                } else if types.iter().all(|t| matches!(t, Type::Atom(_))) {
+                    Type::Atom(None)
+                } else {
+                    Type::Failed { diagnosed: false }
+                }
+            }
         }
     }
 }
@@ -203,9 +488,52 @@ impl fmt::Debug for Module {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Context;
+    use crate::{Context, Diagnostic, Severity};
     use std::collections::HashMap;
 
+    #[tokio::test]
+    async fn internal_errors_become_diagnostics() {
+        let host = Host::new(Arc::new(Context::test()));
+        let broken = host.set(Source::new("test:a", "object A.")).await;
+        let healthy = host
+            .set(Source::new("test:b", "object B { 1 -> C. }"))
+            .await;
+
+        let error = broken
+            .catch_internal_errors(async { panic!("boom") })
+            .await
+            .unwrap_err();
+        assert_eq!(error.severity(), Severity::Error);
+        assert_eq!(
+            error.message(),
+            "Internal compiler error in module test:a: boom"
+        );
+
+        let diagnostics = healthy.diagnostics().await;
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.message() == "Undefined reference `C`"));
+    }
+
+    #[tokio::test]
+    async fn panicking_analyzers_report_internal_errors() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host.set(Source::new("test:a", "object A.")).await;
+
+        let diagnostics = module
+            .diagnostics_collected_by(async { panic!("boom") })
+            .await;
+        assert_eq!(diagnostics.len(), 1);
+        let error = diagnostics.iter().next().unwrap();
+        assert_eq!(error.severity(), Severity::Error);
+        assert_eq!(
+            error.message(),
+            "Internal compiler error in module test:a: boom"
+        );
+
+        assert!(module.diagnostics().await.is_empty());
+    }
+
     #[tokio::test]
     async fn single_declaration() {
         let host = Host::new(Arc::new(Context::test()));
@@ -217,6 +545,27 @@ mod tests {
         assert!(exported.get("X").is_some());
     }
 
+    #[tokio::test]
+    async fn float_words_are_only_floats_in_expressions() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host
+            .set(Source::new("test:x", "object NaN { 1 -> Infinity. }"))
+            .await;
+
+        assert!(module.diagnostics().await.is_empty());
+        let exported: HashMap<_, _> = module.exported_declarations().await.into_iter().collect();
+        assert!(exported.get("NaN").is_some());
+
+        let infinity = module
+            .navigate()
+            .down_to_cast(|n| n.as_expression())
+            .unwrap();
+        assert_eq!(
+            module.get_type_of(infinity).await,
+            Type::Float(Some(f64::INFINITY))
+        );
+    }
+
     #[tokio::test]
     async fn duplicated_export() {
         let host = Host::new(Arc::new(Context::test()));
@@ -226,4 +575,452 @@ mod tests {
         let diagnostics = module.diagnostics().await;
         assert_eq!(diagnostics.len(), 1);
     }
+
+    #[tokio::test]
+    async fn repl_line_sees_earlier_declarations() {
+        let host = Host::new(Arc::new(Context::test()));
+        host.set(Source::inline("repl:1", "object X.")).await;
+        let line = host.set(Source::inline("repl:2", "X.")).await;
+
+        assert!(line.diagnostics().await.is_empty());
+
+        let reference = line
+            .navigate()
+            .down_to_cast(|n| n.as_reference_expression())
+            .unwrap();
+        let declaration = line.declaration_referenced_by(reference).await.unwrap();
+        assert_eq!(declaration.symbol(), "X");
+        assert_eq!(declaration.source().uri(), &URI::from("repl:1"));
+    }
+
+    #[tokio::test]
+    async fn types_of_alternative_patterns() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host
+            .set(Source::new(
+                "test:x",
+                "object X { 1 | 2 -> 1. 3 | 3 -> 1. a! | b! -> 1. }",
+            ))
+            .await;
+
+        let mut types = vec![];
+        for method in module.navigate().all_methods() {
+            types.push(module.get_type_of_pattern(method.pattern.clone()).await);
+        }
+        let types: Vec<_> = types.iter().map(ToString::to_string).collect();
+        assert_eq!(types, vec!["Integer", "Integer (3)", "Atom"]);
+    }
+
+    #[tokio::test]
+    async fn references_to_bindings() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host
+            .set(Source::new("test:x", "object X { 1 -> y. x = 2. x 3. }"))
+            .await;
+
+        let references: Vec<_> = module
+            .navigate()
+            .traverse()
+            .filter_map(|n| n.node.clone().as_reference_expression())
+            .collect();
+        assert_eq!(references.len(), 2);
+
+        // `y` comes before any binding and isn't declared anywhere.
+        assert!(module.binding_referenced_by(&references[0]).is_none());
+
+        let statement = module.binding_referenced_by(&references[1]).unwrap();
+        assert_eq!(statement.bound_name(), Some("x"));
+        assert_eq!(
+            module
+                .get_type_of(Arc::new(Expression::Reference(references[1].clone())))
+                .await
+                .to_string(),
+            "Integer (2)"
+        );
+        assert_eq!(module.diagnostics().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn inline_result_type() {
+        let host = Host::new(Arc::new(Context::test()));
+        let line = host.set(Source::inline("repl:1", "2 3.")).await;
+        assert_eq!(
+            line.inline_result_type().await.unwrap().to_string(),
+            "Integer (6)"
+        );
+
+        let declaration = host.set(Source::inline("repl:2", "object X.")).await;
+        assert!(declaration.inline_result_type().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn self_reference() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host
+            .set(Source::new("test:x", "object X { 1 -> self 2. 2 -> 3. }"))
+            .await;
+
+        assert!(module.diagnostics().await.is_empty());
+
+        let self_expression = module
+            .navigate()
+            .all_expressions()
+            .find(|e| matches!(e.as_ref(), Expression::SelfReference(_)))
+            .unwrap();
+        match module.get_type_of(self_expression).await {
+            Type::Object(o) => assert_eq!(o.symbol(), "X"),
+            t => panic!("expected X, got {:?}", t),
+        }
+    }
+
+    #[tokio::test]
+    async fn self_outside_of_object() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host.set(Source::inline("test:x", "self.")).await;
+
+        let diagnostics: Vec<_> = module.diagnostics().await.into_iter().collect();
+        assert_eq!(
+            diagnostics[0].message(),
+            "`self` can only be used inside of an object"
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_encoding() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host
+            .set(Source::from_bytes("test:x", b"object X. \xFF"))
+            .await;
+
+        let warnings: Vec<_> = module
+            .diagnostics()
+            .await
+            .into_iter()
+            .filter(|d| d.severity() == Severity::Warning)
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].message(),
+            "Invalid UTF-8 was replaced with U+FFFD"
+        );
+        assert_eq!(warnings[0].range().start.offset, 10);
+    }
+
+    #[tokio::test]
+    async fn empty_object_body() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host.set(Source::new("test:x", "object X {}")).await;
+
+        let diagnostics: Vec<_> = module.diagnostics().await.into_iter().collect();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity(), Severity::Info);
+        assert_eq!(
+            diagnostics[0].message(),
+            "`X` has an empty body, write `object X.` if it has no methods"
+        );
+
+        let module = host.set(Source::new("test:y", "object Y.")).await;
+        assert!(module.diagnostics().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn diagnostics_are_the_same_every_time() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host
+            .set(Source::new("test:x", "object X { 1 -> Y. } object Z {}"))
+            .await;
+
+        let (first, second) =
+            futures::future::join(module.diagnostics(), module.diagnostics()).await;
+        let third = module.diagnostics().await;
+
+        let messages = |d: Diagnostics| -> Vec<String> { d.iter().map(|d| d.message()).collect() };
+        let first = messages(first);
+        assert_eq!(first.len(), 2);
+        assert_eq!(first, messages(second));
+        assert_eq!(first, messages(third));
+    }
+
+    #[tokio::test]
+    async fn lowercase_object_names() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host.set(Source::new("test:x", "object foo.")).await;
+
+        let diagnostics: Vec<_> = module.diagnostics().await.into_iter().collect();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity(), Severity::Warning);
+        assert_eq!(
+            diagnostics[0].message(),
+            "Object names should start with an uppercase letter, like `Foo`"
+        );
+
+        let module = host.set(Source::new("test:y", "object Foo.")).await;
+        assert!(module.diagnostics().await.is_empty());
+
+        let host = Host::new(Arc::new(
+            Context::test().with_naming_convention_severity(Some(Severity::Info)),
+        ));
+        let module = host.set(Source::new("test:x", "object foo.")).await;
+        let diagnostics: Vec<_> = module.diagnostics().await.into_iter().collect();
+        assert_eq!(diagnostics[0].severity(), Severity::Info);
+
+        let host = Host::new(Arc::new(
+            Context::test().with_naming_convention_severity(None),
+        ));
+        let module = host.set(Source::new("test:x", "object foo.")).await;
+        assert!(module.diagnostics().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unconditional_self_recursion() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host
+            .set(Source::new(
+                "test:x",
+                "object X { run! => self run!. } object Y { run! => self stop!. stop! => 1. }",
+            ))
+            .await;
+
+        let warnings: Vec<_> = module
+            .diagnostics()
+            .await
+            .into_iter()
+            .filter(|d| d.severity() == Severity::Warning)
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].message(),
+            "Sending `run!` to `self` from the method handling it will never stop"
+        );
+        assert_eq!(warnings[0].range().start.line, 1);
+        assert_eq!(warnings[0].range().start.character, 20);
+    }
+
+    #[tokio::test]
+    async fn inapplicable_patterns() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host
+            .set(Source::new(
+                "test:x",
+                "object Pinger { 1 -> 2. ping! -> 3. } object Main { run! -> Pinger ping!. }",
+            ))
+            .await;
+
+        let warnings: Vec<_> = module
+            .diagnostics()
+            .await
+            .into_iter()
+            .filter(|d| d.severity() == Severity::Warning)
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].message(),
+            "`1` never matches, since `Pinger` is only sent ping!"
+        );
+        assert_eq!(warnings[0].range().start.character, 17);
+
+        let module = host
+            .set(Source::new(
+                "test:x",
+                "object Pinger { 1 -> 2. ping! -> 3. } object Main { run! -> Pinger ping!. Pinger 1. }",
+            ))
+            .await;
+        assert!(module
+            .diagnostics()
+            .await
+            .iter()
+            .all(|d| d.severity() != Severity::Warning));
+    }
+
+    #[tokio::test]
+    async fn inapplicable_patterns_across_modules() {
+        let host = Host::new(Arc::new(Context::test()));
+        let pinger = host
+            .set(Source::new(
+                "test:pinger",
+                "object Pinger { 1 -> 2. ping! -> 3. }",
+            ))
+            .await;
+        host.set(Source::new(
+            "test:main",
+            "object Main { run! -> Pinger ping!. }",
+        ))
+        .await;
+
+        let warnings = |diagnostics: Diagnostics| -> Vec<String> {
+            diagnostics
+                .into_iter()
+                .filter(|d| d.severity() == Severity::Warning)
+                .map(|d| d.message())
+                .collect()
+        };
+        assert_eq!(
+            warnings(pinger.diagnostics().await),
+            vec!["`1` never matches, since `Pinger` is only sent ping!"]
+        );
+
+        // Changing the sending module is enough to update the warnings
+        // of the declaring one.
+        host.set(Source::new(
+            "test:main",
+            "object Main { run! -> Pinger ping!. Pinger 1. }",
+        ))
+        .await;
+        assert!(warnings(pinger.diagnostics().await).is_empty());
+
+        // An object that is passed around could be sent anything.
+        host.set(Source::new(
+            "test:main",
+            "object Main { run! -> Pinger ping!. Main Pinger. }",
+        ))
+        .await;
+        assert!(warnings(pinger.diagnostics().await).is_empty());
+    }
+
+    #[tokio::test]
+    async fn capped_diagnostics() {
+        let host = Host::new(Arc::new(Context::test()));
+        host.set_diagnostic_limit(3);
+        let module = host
+            .set(Source::new(
+                "test:x",
+                "object X. object X. object X. object X. object X. object X.",
+            ))
+            .await;
+
+        let diagnostics: Vec<_> = module.diagnostics().await.into_iter().collect();
+        assert_eq!(diagnostics.len(), 4);
+        assert_eq!(diagnostics[3].severity(), Severity::Info);
+        assert_eq!(diagnostics[3].message(), "2 more diagnostics suppressed");
+    }
+
+    #[tokio::test]
+    async fn behaviours_of_integer() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host.set(Source::inline("test:x", "1 increment!.")).await;
+
+        let behaviours = module.behaviours_at(module.source.location_at(1)).await;
+        let selectors: Vec<_> = behaviours.into_iter().map(|b| b.selector).collect();
+        assert_eq!(
+            selectors,
+            vec![Type::Atom(Some("increment!".into())), Type::any_integer()]
+        );
+
+        let behaviours = module.behaviours_at(module.source.location_at(20)).await;
+        assert!(behaviours.is_empty());
+    }
+
+    #[tokio::test]
+    async fn behaviours_of_receiver() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host.set(Source::inline("test:x", "2 3 ")).await;
+
+        let receiver = module
+            .receiver_before(&module.source.location_at(4))
+            .unwrap();
+        assert_eq!(module.get_type_of(receiver.clone()).await, Type::integer(6));
+
+        let behaviours = module.get_behaviours_of_type(Type::integer(6)).await;
+        assert!(behaviours
+            .iter()
+            .any(|b| b.selector == Type::Atom(Some("increment!".into()))));
+
+        assert!(module
+            .receiver_before(&module.source.location_at(0))
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn explain_integer_multiplication() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host.set(Source::inline("test:x", "2 3.")).await;
+
+        let explanation = module
+            .explain_type_at(module.source.location_at(1))
+            .await
+            .unwrap();
+        assert_eq!(explanation.type_, Type::integer(6));
+        assert_eq!(explanation.reason, "folded multiplication");
+        assert_eq!(explanation.children.len(), 2);
+        assert_eq!(explanation.children[0].type_, Type::integer(2));
+        assert_eq!(
+            explanation.to_string(),
+            "2 3 : Integer (6) (folded multiplication)\n  \
+             2 : Integer (2) (integer literal)\n  \
+             3 : Integer (3) (integer literal)\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn explain_reference_to_object() {
+        let host = Host::new(Arc::new(Context::test()));
+        host.set(Source::new("test:x", "object X.")).await;
+        let module = host.set(Source::inline("test:y", "X.")).await;
+
+        let explanation = module
+            .explain_type_at(module.source.location_at(0))
+            .await
+            .unwrap();
+        assert_eq!(explanation.reason, "refers to object X");
+        assert!(explanation.children.is_empty());
+    }
+
+    #[tokio::test]
+    async fn matching_reply_type() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host
+            .set(Source::new("test:x", "object X { new!: X -> ^X. }"))
+            .await;
+
+        assert!(module.diagnostics().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn mismatched_reply_type() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host
+            .set(Source::new(
+                "test:x",
+                "object X { new!: Y -> ^X. } object Y.",
+            ))
+            .await;
+
+        let diagnostics = module.diagnostics().await;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics.iter().next().unwrap().message(),
+            "Expected a reply of type Y, but got X"
+        );
+    }
+
+    #[tokio::test]
+    async fn behaviours_reply_with_annotated_type() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host
+            .set(Source::new("test:x", "object X { new!: X -> ^X. }"))
+            .await;
+        let object = match module.exported_declarations().await[0].1.as_ref() {
+            Declaration::Object(o) => o.clone(),
+        };
+
+        let behaviours = module.get_behaviours_of_object(object.clone()).await;
+        assert_eq!(behaviours.len(), 1);
+        assert_eq!(behaviours[0].reply, Type::Object(object));
+    }
+
+    #[tokio::test]
+    async fn queries_expect_replies() {
+        let host = Host::new(Arc::new(Context::test()));
+        let module = host
+            .set(Source::new(
+                "test:x",
+                "object X { run! -> 1. value? -> 2. a! -> self value?. b! -> self run!. }",
+            ))
+            .await;
+
+        let sends: Vec<_> = module.navigate().all_message_sends().collect();
+        assert_eq!(sends.len(), 2);
+        assert!(module.expects_reply(&sends[0]).await);
+        assert!(!module.expects_reply(&sends[1]).await);
+    }
 }
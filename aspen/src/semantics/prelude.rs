@@ -0,0 +1,18 @@
+use crate::{Source, URI};
+use std::sync::Arc;
+
+/// The URI of the prelude, whose declarations are visible to every
+/// module without being declared there.
+pub const PRELUDE_URI: &str = "aspen:prelude";
+
+const PRELUDE: &str = include_str!("prelude.aspen");
+
+/// The built-in prelude. A host can override it by setting a source
+/// with the prelude URI.
+pub fn prelude() -> Arc<Source> {
+    Source::new(PRELUDE_URI, PRELUDE)
+}
+
+pub fn is_prelude(uri: &URI) -> bool {
+    uri.uri() == PRELUDE_URI
+}
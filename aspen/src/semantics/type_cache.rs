@@ -0,0 +1,110 @@
+use crate::semantics::analyzers::SentMessages;
+use crate::semantics::types::Behaviour;
+use crate::syntax::ObjectDeclaration;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Analyses of declarations that other modules depend on, shared by
+/// every module in a host so that e.g. the behaviours of an imported
+/// object are only resolved once, rather than once per module that
+/// refers to it.
+///
+/// Entries are keyed by the identity of the declaration. The analysis
+/// of a declaration can depend on any other module, so the whole cache
+/// is invalidated whenever a module changes, and analyses that started
+/// before that are not inserted.
+#[derive(Clone, Default)]
+pub struct TypeCache {
+    inner: Arc<Mutex<Inner>>,
+    sent_messages: Arc<Mutex<Option<(usize, Arc<SentMessages>)>>>,
+    hits: Arc<AtomicUsize>,
+}
+
+#[derive(Default)]
+struct Inner {
+    generation: usize,
+    behaviours: HashMap<usize, (Arc<ObjectDeclaration>, Vec<Behaviour>)>,
+}
+
+impl TypeCache {
+    pub fn new() -> TypeCache {
+        TypeCache::default()
+    }
+
+    /// The number of times the cache has been invalidated, which is
+    /// passed back when inserting an analysis started at that point.
+    pub async fn generation(&self) -> usize {
+        self.inner.lock().await.generation
+    }
+
+    pub async fn behaviours(&self, object: &Arc<ObjectDeclaration>) -> Option<Vec<Behaviour>> {
+        let behaviours = self
+            .inner
+            .lock()
+            .await
+            .behaviours
+            .get(&Self::key(object))
+            .map(|(_, behaviours)| behaviours.clone());
+        if behaviours.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        behaviours
+    }
+
+    /// Caches the behaviours of the object, unless the cache has been
+    /// invalidated since the given generation.
+    pub async fn insert_behaviours(
+        &self,
+        generation: usize,
+        object: Arc<ObjectDeclaration>,
+        behaviours: Vec<Behaviour>,
+    ) {
+        let mut inner = self.inner.lock().await;
+        if inner.generation == generation {
+            // The declaration is kept in the entry, so that its address
+            // can't be reused by another declaration.
+            inner
+                .behaviours
+                .insert(Self::key(&object), (object, behaviours));
+        }
+    }
+
+    /// The messages sent to the objects of the host, gathered by
+    /// `gather` unless they have been since the cache was last
+    /// invalidated. Concurrent callers wait for a single gathering.
+    pub async fn sent_messages<F: Future<Output = SentMessages>>(
+        &self,
+        gather: F,
+    ) -> Arc<SentMessages> {
+        let mut sent_messages = self.sent_messages.lock().await;
+        let generation = self.generation().await;
+        if let Some((g, sent)) = sent_messages.as_ref() {
+            if *g == generation {
+                return sent.clone();
+            }
+        }
+
+        let sent = Arc::new(gather.await);
+        *sent_messages = Some((generation, sent.clone()));
+        sent
+    }
+
+    /// Forgets everything that has been cached.
+    pub async fn invalidate(&self) {
+        let mut inner = self.inner.lock().await;
+        inner.generation += 1;
+        inner.behaviours.clear();
+    }
+
+    /// The number of lookups that were answered from the cache.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    fn key(object: &Arc<ObjectDeclaration>) -> usize {
+        Arc::as_ptr(object) as usize
+    }
+}
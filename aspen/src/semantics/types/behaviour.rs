@@ -1,7 +1,14 @@
 use crate::semantics::types::Type;
+use std::fmt;
 
 #[derive(Debug, Clone)]
 pub struct Behaviour {
     pub selector: Type,
     pub reply: Type,
 }
+
+impl fmt::Display for Behaviour {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> {}", self.selector, self.reply)
+    }
+}
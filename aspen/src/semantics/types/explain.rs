@@ -0,0 +1,79 @@
+use crate::semantics::types::{Type, TypeSlot, TypeTracer};
+use crate::semantics::Module;
+use crate::syntax::{Expression, Node};
+use futures::future::{BoxFuture, FutureExt};
+use std::fmt;
+use std::sync::Arc;
+
+/// A record of how the type of an expression was inferred, with the
+/// reason recorded by the `TypeTracer` for each step.
+#[derive(Debug, Clone)]
+pub struct TypeExplanation {
+    pub expression: String,
+    pub type_: Type,
+    pub reason: String,
+    pub children: Vec<TypeExplanation>,
+}
+
+impl TypeExplanation {
+    pub fn of(module: Arc<Module>, expression: Arc<Expression>) -> BoxFuture<'static, Self> {
+        async move {
+            let range = expression.range();
+            let source = module
+                .source
+                .slice(range.start.offset..range.end.offset)
+                .to_string();
+            let tracer = TypeTracer::new(module.clone(), TypeSlot::covariant());
+            let type_ = tracer.trace_apparent_expression(&expression).await;
+            let reason = tracer.reason().map(|r| r.to_string()).unwrap_or_default();
+
+            let children = match expression.as_ref() {
+                Expression::Integer(_)
+                | Expression::Float(_)
+                | Expression::NullaryAtom(_)
+                | Expression::SelfReference(_)
+                | Expression::Sender(_) => vec![],
+                Expression::Reference(r) => match module.binding_referenced_by(r) {
+                    Some(statement) => {
+                        vec![Self::of(module.clone(), statement.expression.clone()).await]
+                    }
+                    None => vec![],
+                },
+                Expression::Answer(a) => vec![Self::of(module.clone(), a.expression.clone()).await],
+                Expression::MessageSend(s) => vec![
+                    Self::of(module.clone(), s.receiver.clone()).await,
+                    Self::of(module.clone(), s.message.clone()).await,
+                ],
+            };
+
+            TypeExplanation {
+                expression: source,
+                type_,
+                reason,
+                children,
+            }
+        }
+        .boxed()
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        writeln!(
+            f,
+            "{}{} : {} ({})",
+            "  ".repeat(depth),
+            self.expression,
+            self.type_,
+            self.reason
+        )?;
+        for child in self.children.iter() {
+            child.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TypeExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
@@ -1,21 +1,34 @@
 use crate::syntax::ObjectDeclaration;
+use futures::future::join;
 use std::cmp::Ordering;
 use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
+use tokio::task;
+use tokio::time::timeout;
 
 mod behaviour;
+mod explain;
 mod trace;
 
 pub use self::behaviour::*;
+pub use self::explain::*;
 pub use self::trace::*;
 
 #[derive(Clone, Debug)]
 pub enum Type {
-    Failed { diagnosed: bool },
+    Failed {
+        diagnosed: bool,
+    },
     Object(Arc<ObjectDeclaration>),
     Unbounded(String, usize),
-    Integer(Option<i128>),
+    /// An integer between two inclusive bounds. A missing bound means
+    /// that the integer is unbounded in that direction.
+    Integer {
+        lower: Option<i128>,
+        upper: Option<i128>,
+    },
     Float(Option<f64>),
     Atom(Option<String>),
 }
@@ -27,8 +40,25 @@ impl fmt::Display for Type {
             Failed { .. } => write!(f, "?"),
             Object(o) => write!(f, "{}", o.symbol()),
             Unbounded(s, _) => write!(f, "{}", s),
-            Integer(Some(i)) => write!(f, "Integer ({})", i),
-            Integer(None) => write!(f, "Integer"),
+            Integer {
+                lower: Some(l),
+                upper: Some(u),
+            } if l == u => write!(f, "Integer ({})", l),
+            Integer {
+                lower: None,
+                upper: None,
+            } => write!(f, "Integer"),
+            Integer { lower, upper } => {
+                write!(f, "Integer (")?;
+                if let Some(l) = lower {
+                    write!(f, "{}", l)?;
+                }
+                write!(f, "..")?;
+                if let Some(u) = upper {
+                    write!(f, "{}", u)?;
+                }
+                write!(f, ")")
+            }
             Float(Some(v)) => write!(f, "Float ({})", v),
             Float(None) => write!(f, "Float"),
             Atom(Some(a)) => write!(f, "{}", a),
@@ -38,6 +68,33 @@ impl fmt::Display for Type {
 }
 
 impl Type {
+    /// The type of exactly one integer.
+    pub fn integer(value: i128) -> Type {
+        Type::Integer {
+            lower: Some(value),
+            upper: Some(value),
+        }
+    }
+
+    /// The type of any integer at all.
+    pub fn any_integer() -> Type {
+        Type::Integer {
+            lower: None,
+            upper: None,
+        }
+    }
+
+    /// The value of an integer type that only contains a single value.
+    pub fn exact_integer(&self) -> Option<i128> {
+        match self {
+            Type::Integer {
+                lower: Some(l),
+                upper: Some(u),
+            } if l == u => Some(*l),
+            _ => None,
+        }
+    }
+
     fn check_equality(&self, other: &Type) -> TypeCheck {
         use Type::*;
         match (self, other) {
@@ -53,18 +110,27 @@ impl Type {
                     Err(TypeError::ObjectsAreNotEqual(a.clone(), b.clone()))
                 }
             }
-            (Integer(i), Integer(j)) => {
-                if i == j {
+            (
+                Integer {
+                    lower: a_lower,
+                    upper: a_upper,
+                },
+                Integer {
+                    lower: b_lower,
+                    upper: b_upper,
+                },
+            ) => {
+                if a_lower == b_lower && a_upper == b_upper {
                     Ok(())
                 } else {
                     Err(TypeError::TypesAreNotEqual(self.clone(), other.clone()))
                 }
             }
-            (Integer(_), _) | (_, Integer(_)) => {
+            (Integer { .. }, _) | (_, Integer { .. }) => {
                 Err(TypeError::TypesAreNotEqual(self.clone(), other.clone()))
             }
             (Float(i), Float(j)) => {
-                if i == j {
+                if same_float(i, j) {
                     Ok(())
                 } else {
                     Err(TypeError::TypesAreNotEqual(self.clone(), other.clone()))
@@ -97,20 +163,39 @@ impl Type {
                 object.clone(),
                 other.clone(),
             )),
-            (Integer(None), Integer(Some(_))) => Ok(()),
-            (Integer(i), Integer(j)) => {
-                if i == j {
+            // An interval is assignable to any interval that contains it.
+            (
+                Integer {
+                    lower: a_lower,
+                    upper: a_upper,
+                },
+                Integer {
+                    lower: b_lower,
+                    upper: b_upper,
+                },
+            ) => {
+                let lower_contained = match (a_lower, b_lower) {
+                    (None, _) => true,
+                    (Some(_), None) => false,
+                    (Some(a), Some(b)) => a <= b,
+                };
+                let upper_contained = match (a_upper, b_upper) {
+                    (None, _) => true,
+                    (Some(_), None) => false,
+                    (Some(a), Some(b)) => a >= b,
+                };
+                if lower_contained && upper_contained {
                     Ok(())
                 } else {
                     Err(TypeError::TypesAreNotEqual(self.clone(), other.clone()))
                 }
             }
-            (Integer(_), _) | (_, Integer(_)) => {
+            (Integer { .. }, _) | (_, Integer { .. }) => {
                 Err(TypeError::TypesAreNotEqual(self.clone(), other.clone()))
             }
             (Float(None), Float(Some(_))) => Ok(()),
             (Float(i), Float(j)) => {
-                if i == j {
+                if same_float(i, j) {
                     Ok(())
                 } else {
                     Err(TypeError::TypesAreNotEqual(self.clone(), other.clone()))
@@ -134,6 +219,12 @@ impl Type {
     }
 }
 
+/// Float types are compared bitwise, so that `NaN` equals itself and
+/// `-0.0` is distinct from `0.0`.
+fn same_float(a: &Option<f64>, b: &Option<f64>) -> bool {
+    a.map(f64::to_bits) == b.map(f64::to_bits)
+}
+
 impl PartialEq for Type {
     fn eq(&self, other: &Self) -> bool {
         self.check_equality(other).is_ok()
@@ -154,6 +245,8 @@ impl PartialOrd for Type {
     }
 }
 
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
 enum Variance {
     Invariant,
     Covariant,
@@ -210,6 +303,7 @@ impl TypeSlot {
             if let Some(t) = self.get_apparent().await {
                 return t;
             }
+            task::yield_now().await;
         }
     }
 
@@ -218,12 +312,24 @@ impl TypeSlot {
             if let Some(t) = self.get_required().await {
                 return t.clone();
             }
+            task::yield_now().await;
         }
     }
 
     pub async fn check(&self) -> TypeCheck {
-        let apparent = self.wait_for_apparent().await;
-        let required = self.wait_for_required().await;
+        self.check_with_timeout(CHECK_TIMEOUT).await
+    }
+
+    /// Checks the slot, giving up with `TypeError::Unresolved` if either
+    /// side isn't resolved within the timeout, so that a missing call to
+    /// `resolve_apparent` or `resolve_required` can't hang the analysis.
+    pub async fn check_with_timeout(&self, duration: Duration) -> TypeCheck {
+        let (apparent, required) = timeout(
+            duration,
+            join(self.wait_for_apparent(), self.wait_for_required()),
+        )
+        .await
+        .map_err(|_| TypeError::Unresolved)?;
 
         match self.variance {
             Variance::Invariant => required.check_equality(&apparent)?,
@@ -242,11 +348,11 @@ pub enum TypeError {
     ObjectsAreNotEqual(Arc<ObjectDeclaration>, Arc<ObjectDeclaration>),
     TypesAreNotEqual(Type, Type),
     ObjectsHaveNoSubTypes(Arc<ObjectDeclaration>, Type),
+    Unresolved,
 }
 
 #[cfg(test)]
 mod tests {
-    use tokio::task;
 
     use crate::syntax::{Declaration, Parser, Root};
     use crate::Source;
@@ -351,4 +457,70 @@ mod tests {
 
         assert!(slot.check().await.is_err());
     }
+
+    #[tokio::test]
+    async fn half_resolved_slot_times_out() {
+        let slot = TypeSlot::invariant();
+        slot.resolve_apparent(Type::any_integer()).await;
+
+        match slot.check_with_timeout(Duration::from_millis(10)).await {
+            Err(TypeError::Unresolved) => {}
+            other => panic!("expected the check to time out, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn floats_compare_bitwise() {
+        let float = |f: f64| Type::Float(Some(f));
+
+        assert_eq!(float(f64::INFINITY), float(f64::INFINITY));
+        assert_ne!(float(f64::INFINITY), float(f64::NEG_INFINITY));
+        assert_eq!(float(f64::NAN), float(f64::NAN));
+        assert_ne!(float(-0.0), float(0.0));
+        assert!(Type::Float(None) > float(-0.0));
+    }
+
+    #[test]
+    fn integer_intervals_are_assignable_to_enclosing_intervals() {
+        let interval = |lower, upper| Type::Integer { lower, upper };
+
+        assert!(interval(Some(0), Some(100)) > interval(Some(1), Some(10)));
+        assert!(interval(Some(1), Some(10)) < interval(Some(0), Some(100)));
+        assert!(Type::any_integer() > interval(Some(1), Some(10)));
+        assert!(interval(Some(0), None) > Type::integer(7));
+        assert!(interval(Some(0), None) > interval(Some(1), Some(10)));
+
+        assert!(interval(Some(0), None)
+            .partial_cmp(&interval(None, Some(10)))
+            .is_none());
+        assert!(interval(Some(0), Some(5))
+            .partial_cmp(&interval(Some(3), Some(10)))
+            .is_none());
+        assert!(Type::integer(-1) < interval(None, Some(0)));
+        assert!(Type::integer(-1)
+            .partial_cmp(&interval(Some(0), None))
+            .is_none());
+    }
+
+    #[test]
+    fn integer_intervals_display_their_bounds() {
+        assert_eq!(Type::integer(6).to_string(), "Integer (6)");
+        assert_eq!(Type::any_integer().to_string(), "Integer");
+        assert_eq!(
+            Type::Integer {
+                lower: Some(1),
+                upper: Some(10)
+            }
+            .to_string(),
+            "Integer (1..10)"
+        );
+        assert_eq!(
+            Type::Integer {
+                lower: Some(0),
+                upper: None
+            }
+            .to_string(),
+            "Integer (0..)"
+        );
+    }
 }
@@ -5,16 +5,58 @@ use crate::syntax::{
     TypeExpression,
 };
 use futures::future::join;
-use std::sync::Arc;
+use std::fmt;
+use std::sync::{Arc, Mutex};
 
 pub struct TypeTracer {
     module: Arc<Module>,
     slot: Arc<TypeSlot>,
+    reason: Mutex<Option<TypeReason>>,
+}
+
+/// The step the tracer took to arrive at the type of an expression.
+#[derive(Debug, Clone)]
+pub enum TypeReason {
+    IntegerLiteral,
+    FloatLiteral,
+    AtomLiteral,
+    Binding(String),
+    Object(String),
+    Undefined,
+    SelfReference,
+    Sender,
+    Answer,
+    FailedOperand,
+    FoldedMultiplication,
+    FoldedIncrement,
+    Behaviour {
+        receiver: Type,
+        selector: Type,
+        reply: Type,
+    },
+    NoBehaviour {
+        receiver: Type,
+        message: Type,
+    },
 }
 
 impl TypeTracer {
     pub fn new(module: Arc<Module>, slot: Arc<TypeSlot>) -> TypeTracer {
-        TypeTracer { module, slot }
+        TypeTracer {
+            module,
+            slot,
+            reason: Mutex::new(None),
+        }
+    }
+
+    /// Why the last traced expression has its type, or `None` if the
+    /// type was already resolved in the slot and so wasn't traced.
+    pub fn reason(&self) -> Option<TypeReason> {
+        self.reason.lock().unwrap().clone()
+    }
+
+    fn record(&self, reason: TypeReason) {
+        *self.reason.lock().unwrap() = Some(reason);
     }
 
     pub async fn trace_apparent_expression(&self, expression: &Arc<Expression>) -> Type {
@@ -24,17 +66,41 @@ impl TypeTracer {
 
         let t = match expression.as_ref() {
             Expression::Reference(reference) => self.trace_reference(reference).await,
-            Expression::Integer(i) => match i.literal.kind {
-                TokenKind::IntegerLiteral(i, true) => Type::Integer(Some(i)),
-                _ => Type::Failed { diagnosed: true },
-            },
-            Expression::Float(f) => match f.literal.kind {
-                TokenKind::FloatLiteral(f, true) => Type::Float(Some(f)),
-                _ => Type::Failed { diagnosed: true },
-            },
-            Expression::NullaryAtom(a) => Type::Atom(Some(a.atom.lexeme().into())),
+            Expression::Integer(i) => {
+                self.record(TypeReason::IntegerLiteral);
+                match i.literal.kind {
+                    TokenKind::IntegerLiteral(i, true) => Type::integer(i),
+                    _ => Type::Failed { diagnosed: true },
+                }
+            }
+            Expression::Float(f) => {
+                self.record(TypeReason::FloatLiteral);
+                match f.literal.kind {
+                    TokenKind::FloatLiteral(f, true) => Type::Float(Some(f)),
+                    _ => Type::Failed { diagnosed: true },
+                }
+            }
+            Expression::NullaryAtom(a) => {
+                self.record(TypeReason::AtomLiteral);
+                Type::Atom(Some(a.atom.lexeme().into()))
+            }
             Expression::MessageSend(m) => self.trace_message_send(m).await,
-            Expression::Answer(a) => self.module.get_type_of(a.expression.clone()).await,
+            Expression::Answer(a) => {
+                self.record(TypeReason::Answer);
+                self.module.get_type_of(a.expression.clone()).await
+            }
+            Expression::SelfReference(s) => {
+                self.record(TypeReason::SelfReference);
+                match self.module.enclosing_object(s.clone()) {
+                    Some(o) => Type::Object(o),
+                    None => Type::Failed { diagnosed: true },
+                }
+            }
+            // The sender is only known at runtime, and could be any object.
+            Expression::Sender(_) => {
+                self.record(TypeReason::Sender);
+                Type::Failed { diagnosed: true }
+            }
         };
 
         self.slot.resolve_apparent(t.clone()).await;
@@ -56,54 +122,120 @@ impl TypeTracer {
     }
 
     pub async fn trace_message_send<'a>(&'a self, send: &'a Arc<MessageSend>) -> Type {
-        match join(
+        let (receiver, message) = join(
             self.module.get_type_of(send.receiver.clone()),
             self.module.get_type_of(send.message.clone()),
         )
-        .await
-        {
-            (Type::Failed { .. }, _) | (_, Type::Failed { .. }) => Type::Failed { diagnosed: true },
+        .await;
 
-            (Type::Integer(Some(a)), Type::Integer(Some(b))) => Type::Integer(Some(a * b)),
-            (Type::Integer(Some(a)), Type::Atom(Some(s))) if s == "increment!" => {
-                Type::Integer(Some(a + 1))
+        match (receiver.exact_integer(), message.exact_integer(), &message) {
+            (Some(a), Some(b), _) => {
+                self.record(TypeReason::FoldedMultiplication);
+                return a
+                    .checked_mul(b)
+                    .map(Type::integer)
+                    .unwrap_or_else(Type::any_integer);
+            }
+            (Some(a), None, Type::Atom(Some(s))) if s == "increment!" => {
+                self.record(TypeReason::FoldedIncrement);
+                return a
+                    .checked_add(1)
+                    .map(Type::integer)
+                    .unwrap_or_else(Type::any_integer);
+            }
+            _ => {}
+        }
+
+        match (receiver, message) {
+            (Type::Failed { .. }, _) | (_, Type::Failed { .. }) => {
+                self.record(TypeReason::FailedOperand);
+                Type::Failed { diagnosed: true }
             }
 
             (receiver, message) => {
-                for behaviour in self.module.get_behaviours_of_type(receiver).await {
+                for behaviour in self.module.get_behaviours_of_type(receiver.clone()).await {
                     if message <= behaviour.selector {
+                        self.record(TypeReason::Behaviour {
+                            receiver,
+                            selector: behaviour.selector.clone(),
+                            reply: behaviour.reply.clone(),
+                        });
                         return behaviour.reply.clone();
                     }
                 }
 
+                self.record(TypeReason::NoBehaviour { receiver, message });
                 Type::Failed { diagnosed: true }
             }
         }
     }
 
     pub async fn trace_reference(&self, reference: &Arc<ReferenceExpression>) -> Type {
-        match self
+        if let Some(statement) = self.module.binding_referenced_by(reference) {
+            self.record(TypeReason::Binding(
+                reference.symbol.identifier.lexeme().into(),
+            ));
+            return self.module.get_type_of(statement.expression.clone()).await;
+        }
+
+        let declaration = self
             .module
             .declaration_referenced_by(reference.clone())
-            .await
-        {
-            None => Type::Failed { diagnosed: true },
-            Some(declaration) => match declaration.as_ref() {
-                Declaration::Object(o) => Type::Object(o.clone()),
-            },
-        }
+            .await;
+        self.trace_declaration(declaration)
     }
 
     pub async fn trace_type_reference(&self, reference: &Arc<ReferenceTypeExpression>) -> Type {
-        match self
+        let declaration = self
             .module
             .declaration_referenced_by_type(reference.clone())
-            .await
-        {
-            None => Type::Failed { diagnosed: true },
+            .await;
+        self.trace_declaration(declaration)
+    }
+
+    fn trace_declaration(&self, declaration: Option<Arc<Declaration>>) -> Type {
+        match declaration {
+            None => {
+                self.record(TypeReason::Undefined);
+                Type::Failed { diagnosed: true }
+            }
             Some(declaration) => match declaration.as_ref() {
-                Declaration::Object(o) => Type::Object(o.clone()),
+                Declaration::Object(o) => {
+                    self.record(TypeReason::Object(o.symbol().into()));
+                    Type::Object(o.clone())
+                }
             },
         }
     }
 }
+
+impl fmt::Display for TypeReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeReason::IntegerLiteral => write!(f, "integer literal"),
+            TypeReason::FloatLiteral => write!(f, "float literal"),
+            TypeReason::AtomLiteral => write!(f, "atom literal"),
+            TypeReason::Binding(name) => write!(f, "bound to {}", name),
+            TypeReason::Object(name) => write!(f, "refers to object {}", name),
+            TypeReason::Undefined => write!(f, "undefined reference"),
+            TypeReason::SelfReference => write!(f, "`self` in the enclosing object"),
+            TypeReason::Sender => write!(f, "`sender` is only known at runtime"),
+            TypeReason::Answer => write!(f, "answer"),
+            TypeReason::FailedOperand => write!(f, "operand could not be inferred"),
+            TypeReason::FoldedMultiplication => write!(f, "folded multiplication"),
+            TypeReason::FoldedIncrement => write!(f, "folded increment"),
+            TypeReason::Behaviour {
+                receiver,
+                selector,
+                reply,
+            } => write!(
+                f,
+                "matched behaviour {} -> {} of {}",
+                selector, reply, receiver
+            ),
+            TypeReason::NoBehaviour { receiver, message } => {
+                write!(f, "no behaviour of {} matches {}", receiver, message)
+            }
+        }
+    }
+}
@@ -32,6 +32,17 @@ impl Range {
         self.start <= other.start && other.end <= self.end
     }
 
+    /// Whether the location is within the range. The end of the range
+    /// is only included if `inclusive_end` is set, which is useful for
+    /// cursors placed right after the last character of a node.
+    pub fn contains_location(&self, location: &Location, inclusive_end: bool) -> bool {
+        if inclusive_end {
+            &self.start <= location && location <= &self.end
+        } else {
+            &self.start <= location && location < &self.end
+        }
+    }
+
     pub fn through(&self, mut other: Range) -> Range {
         other.start = min(&self.start, &other.start).clone();
         other.end = max(&self.end, &other.end).clone();
@@ -69,3 +80,25 @@ impl<'a> Into<std::ops::Range<usize>> for &'a Range {
         self.start.offset..self.end.offset
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_containment() {
+        let source = Source::new("test:x", "abcd");
+        let range = (1..3).into_range(&source);
+        let at = |offset: usize| offset.into_location(&source);
+
+        assert!(!range.contains_location(&at(0), false));
+        assert!(range.contains_location(&at(1), false));
+        assert!(range.contains_location(&at(2), false));
+        assert!(!range.contains_location(&at(3), false));
+
+        assert!(!range.contains_location(&at(0), true));
+        assert!(range.contains_location(&at(1), true));
+        assert!(range.contains_location(&at(3), true));
+        assert!(!range.contains_location(&at(4), true));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::source::{Location, URI};
 use crate::Range;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::io;
@@ -17,11 +17,12 @@ pub struct Source {
     len: usize,
     offset_byte_indices: HashMap<usize, usize>,
     line_breaks: Vec<usize>,
+    invalid_offsets: Vec<usize>,
     pub modified: SystemTime,
     pub kind: SourceKind,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum SourceKind {
     Module,
     Inline,
@@ -74,6 +75,13 @@ impl Source {
         )
     }
 
+    /// Creates a source from bytes that are expected to be UTF-8. Invalid
+    /// sequences are replaced with U+FFFD rather than failing, and their
+    /// positions are available from `invalid_ranges`.
+    pub fn from_bytes<U: Into<URI>>(uri: U, bytes: &[u8]) -> Arc<Source> {
+        Self::create_from_bytes(uri.into(), bytes, SystemTime::now())
+    }
+
     pub async fn read<U, R>(uri: U, read: R) -> io::Result<Arc<Source>>
     where
         U: Into<URI>,
@@ -92,17 +100,56 @@ impl Source {
     }
 
     pub async fn files<P: AsRef<str>>(pattern: P) -> Vec<Arc<Source>> {
+        Self::files_excluding::<P, &str>(pattern, &[]).await
+    }
+
+    /// Like `files`, but skips paths matching any of the `excluded` globs.
+    pub async fn files_excluding<P, E>(pattern: P, excluded: &[E]) -> Vec<Arc<Source>>
+    where
+        P: AsRef<str>,
+        E: AsRef<str>,
+    {
+        let excluded: Vec<_> = excluded
+            .iter()
+            .filter_map(|e| glob::Pattern::new(e.as_ref()).ok())
+            .collect();
+
         if let Ok(paths) = glob::glob(pattern.as_ref()) {
-            futures::future::join_all(paths.into_iter().filter_map(Result::ok).map(Self::file))
-                .await
-                .into_iter()
-                .filter_map(Result::ok)
-                .collect()
+            futures::future::join_all(
+                paths
+                    .into_iter()
+                    .filter_map(Result::ok)
+                    .filter(|path| !excluded.iter().any(|e| e.matches_path(path)))
+                    .map(Self::file),
+            )
+            .await
+            .into_iter()
+            .filter_map(Result::ok)
+            .collect()
         } else {
             vec![]
         }
     }
 
+    /// All files under the directory with any of the extensions, except
+    /// the ones matching the `excluded` globs.
+    pub async fn files_with_extensions<S, E>(
+        dir: &Path,
+        extensions: &[S],
+        excluded: &[E],
+    ) -> Vec<Arc<Source>>
+    where
+        S: AsRef<str>,
+        E: AsRef<str>,
+    {
+        let mut sources = vec![];
+        for extension in extensions {
+            let pattern = format!("{}/**/*.{}", dir.display(), extension.as_ref());
+            sources.extend(Self::files_excluding(pattern, excluded).await);
+        }
+        sources
+    }
+
     pub async fn stdin() -> io::Result<Arc<Source>> {
         Self::read(URI::stdin(), stdin()).await
     }
@@ -111,21 +158,59 @@ impl Source {
     where
         R: AsyncRead + Unpin,
     {
-        let mut code = String::new();
-        read.read_to_string(&mut code).await?;
-        Ok(Self::create(uri, code, modified, SourceKind::Module))
+        let mut bytes = vec![];
+        read.read_to_end(&mut bytes).await?;
+        Ok(Self::create_from_bytes(uri, bytes.as_slice(), modified))
+    }
+
+    fn create_from_bytes(uri: URI, bytes: &[u8], modified: SystemTime) -> Arc<Source> {
+        let mut code = String::with_capacity(bytes.len());
+        let mut invalid_bytes = HashSet::new();
+        let mut rest = bytes;
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    code.push_str(valid);
+                    break;
+                }
+                Err(error) => {
+                    let (valid, invalid) = rest.split_at(error.valid_up_to());
+                    code.push_str(std::str::from_utf8(valid).unwrap());
+                    invalid_bytes.insert(code.len());
+                    code.push(std::char::REPLACEMENT_CHARACTER);
+                    rest = &invalid[error.error_len().unwrap_or(invalid.len())..];
+                }
+            }
+        }
+
+        Self::create_with_invalid_bytes(uri, code, modified, SourceKind::Module, invalid_bytes)
     }
 
     fn create(uri: URI, code: String, modified: SystemTime, kind: SourceKind) -> Arc<Source> {
+        Self::create_with_invalid_bytes(uri, code, modified, kind, HashSet::new())
+    }
+
+    fn create_with_invalid_bytes(
+        uri: URI,
+        code: String,
+        modified: SystemTime,
+        kind: SourceKind,
+        invalid_bytes: HashSet<usize>,
+    ) -> Arc<Source> {
         let mut offset = 0;
         let mut offset_byte_indices = HashMap::new();
         let mut line_breaks = vec![];
+        let mut invalid_offsets = vec![];
 
         for (byte_offset, grapheme) in code.grapheme_indices(true) {
             if grapheme == "\n" {
                 line_breaks.push(offset);
             }
 
+            if invalid_bytes.contains(&byte_offset) {
+                invalid_offsets.push(offset);
+            }
+
             offset_byte_indices.insert(offset, byte_offset);
             offset += 1
         }
@@ -138,6 +223,7 @@ impl Source {
             len: offset,
             offset_byte_indices,
             line_breaks,
+            invalid_offsets,
             modified,
             kind,
         })
@@ -147,6 +233,18 @@ impl Source {
         Graphemes::new(&self.code[..], &self.offset_byte_indices)
     }
 
+    /// The ranges where invalid UTF-8 was replaced when the source was
+    /// decoded.
+    pub fn invalid_ranges(&self) -> Vec<Range> {
+        self.invalid_offsets
+            .iter()
+            .map(|offset| Range {
+                start: self.location_at(*offset),
+                end: self.location_at(offset + 1),
+            })
+            .collect()
+    }
+
     pub fn uri(&self) -> &URI {
         &self.uri
     }
@@ -159,6 +257,13 @@ impl Source {
         self.len
     }
 
+    /// A hash of the source code, independent of the URI and
+    /// modification time of the source. It's stable across builds of
+    /// the compiler, so it can be persisted.
+    pub fn content_hash(&self) -> u64 {
+        stable_hash(self.code.as_bytes())
+    }
+
     pub fn location_at(&self, offset: usize) -> Location {
         let mut line = 1;
         let mut character = offset + 1;
@@ -191,6 +296,20 @@ impl Source {
         }
     }
 
+    /// The code on a line, counting from 1, without its line break.
+    pub fn line(&self, line: usize) -> Option<&str> {
+        if line == 0 || line > self.line_breaks.len() + 1 {
+            return None;
+        }
+        let start = if line == 1 {
+            0
+        } else {
+            self.line_breaks[line - 2] + 1
+        };
+        let end = self.line_breaks.get(line - 1).cloned().unwrap_or(self.len);
+        Some(self.slice(start..end))
+    }
+
     pub fn slice<R: Into<std::ops::Range<usize>>>(&self, range: R) -> &str {
         let range = range.into();
         if range.end > self.len {
@@ -258,7 +377,26 @@ impl Source {
         }
         new_code.push_str(self.slice(offset..self.len));
 
-        Self::new(self.uri.clone(), new_code)
+        Self::create(
+            self.uri.clone(),
+            new_code,
+            SystemTime::now(),
+            self.kind.clone(),
+        )
+    }
+
+    /// The same code under another URI, as if the file was moved.
+    pub fn with_uri(&self, uri: URI) -> Arc<Source> {
+        Arc::new(Source {
+            uri,
+            code: self.code.clone(),
+            len: self.len,
+            offset_byte_indices: self.offset_byte_indices.clone(),
+            line_breaks: self.line_breaks.clone(),
+            invalid_offsets: self.invalid_offsets.clone(),
+            modified: self.modified,
+            kind: self.kind.clone(),
+        })
     }
 }
 
@@ -300,3 +438,118 @@ impl<'a> Iterator for Graphemes<'a> {
         Some(item)
     }
 }
+
+/// The 64-bit FNV-1a hash of the bytes. Unlike the hashers of the
+/// standard library, it doesn't change between releases of Rust.
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::{Lexer, TokenKind};
+
+    #[test]
+    fn content_hashes_are_stable() {
+        assert_eq!(stable_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(stable_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(
+            Source::new("test:x", "a").content_hash(),
+            Source::new("test:y", "a").content_hash()
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced() {
+        let source = Source::from_bytes("test:x", b"object X\xFF\xFE.");
+
+        assert_eq!(source.len(), 11);
+        assert_eq!(source.slice(8..10), "\u{FFFD}\u{FFFD}");
+
+        let invalid: Vec<_> = source
+            .invalid_ranges()
+            .into_iter()
+            .map(|r| r.start.offset)
+            .collect();
+        assert_eq!(invalid, vec![8, 9]);
+
+        let tokens = Lexer::tokenize(&source);
+        assert_eq!(tokens[0].kind, TokenKind::ObjectKeyword);
+        assert_eq!(tokens[2].lexeme(), "X");
+    }
+
+    #[test]
+    fn lines_exclude_their_line_breaks() {
+        let source = Source::new("test:x", "object X {\n  a! -> 1.\n}");
+
+        assert_eq!(source.line(1), Some("object X {"));
+        assert_eq!(source.line(2), Some("  a! -> 1."));
+        assert_eq!(source.line(3), Some("}"));
+        assert_eq!(source.line(0), None);
+        assert_eq!(source.line(4), None);
+    }
+
+    #[tokio::test]
+    async fn excluded_files_are_skipped() {
+        let temp = mktemp::Temp::new_dir().unwrap();
+        let dir = temp.to_path_buf();
+        tokio::fs::create_dir_all(dir.join("excluded"))
+            .await
+            .unwrap();
+        tokio::fs::write(dir.join("a.aspen"), "object A.")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.join("excluded").join("b.aspen"), "object B.")
+            .await
+            .unwrap();
+
+        let all = Source::files(format!("{}/**/*.aspen", dir.display())).await;
+        assert_eq!(all.len(), 2);
+
+        let included = Source::files_excluding(
+            format!("{}/**/*.aspen", dir.display()),
+            &[format!("{}/excluded/**", dir.display())],
+        )
+        .await;
+        assert_eq!(included.len(), 1);
+        assert_eq!(included[0].short_name(), "a.aspen");
+    }
+
+    #[tokio::test]
+    async fn configured_extensions() {
+        let temp = mktemp::Temp::new_dir().unwrap();
+        let dir = temp.to_path_buf();
+        tokio::fs::write(dir.join("a.aspen"), "object A.")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.join("b.asp"), "object B.")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.join("c.txt"), "object C.")
+            .await
+            .unwrap();
+
+        let context = crate::Context::directory(None, dir.clone());
+        let sources = context.sources().await.unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].short_name(), "a.aspen");
+
+        let context =
+            crate::Context::directory(None, dir.clone()).with_source_extensions(vec!["asp"]);
+        let sources = context.sources().await.unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].short_name(), "b.asp");
+
+        let mut names: Vec<_> =
+            Source::files_with_extensions(&dir, &["aspen", "asp"], &[] as &[&str])
+                .await
+                .iter()
+                .map(|s| s.short_name().to_string())
+                .collect();
+        names.sort();
+        assert_eq!(names, vec!["a.aspen", "b.asp"]);
+    }
+}
@@ -57,6 +57,16 @@ impl URI {
     pub fn uri(&self) -> &str {
         self.uri.as_str()
     }
+
+    /// The path of a `file:` URI, as written, without resolving it
+    /// against the file system.
+    pub fn file_path(&self) -> Option<PathBuf> {
+        if self.scheme() == "file" {
+            Some(PathBuf::from(self.path().trim_start_matches("//")))
+        } else {
+            None
+        }
+    }
 }
 
 impl fmt::Debug for URI {
@@ -0,0 +1,65 @@
+use crate::source::Source;
+use crate::syntax::{Lexer, TokenKind};
+use std::sync::Arc;
+
+/// Checks whether the source looks like the beginning of
+/// something that continues on a following line, like an
+/// object body that hasn't been closed yet, or a method
+/// arrow that isn't followed by any statements.
+///
+/// This is used by interactive environments to decide whether
+/// to keep reading input before trying to evaluate it.
+pub fn is_incomplete(source: &Arc<Source>) -> bool {
+    let tokens = Lexer::tokenize(source);
+
+    let mut depth = 0isize;
+    let mut last_significant = None;
+
+    for token in tokens.iter() {
+        match token.kind {
+            TokenKind::OpenCurly => depth += 1,
+            TokenKind::CloseCurly => depth -= 1,
+            TokenKind::EOF => continue,
+            ref kind if kind.is_trivia() => continue,
+            _ => {}
+        }
+        last_significant = Some(&token.kind);
+    }
+
+    depth > 0 || last_significant == Some(&TokenKind::Arrow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn incomplete(code: &str) -> bool {
+        is_incomplete(&Source::inline("test:x", code))
+    }
+
+    #[test]
+    fn complete_fragments() {
+        assert!(!incomplete(""));
+        assert!(!incomplete("object X."));
+        assert!(!incomplete("object X {}"));
+        assert!(!incomplete("object X {\n  1 -> 2.\n}"));
+        assert!(!incomplete("1 increment!."));
+    }
+
+    #[test]
+    fn unclosed_object_body() {
+        assert!(incomplete("object X {"));
+        assert!(incomplete("object X {\n  1 -> 2."));
+    }
+
+    #[test]
+    fn trailing_arrow() {
+        assert!(incomplete("object X {\n  1 ->"));
+        assert!(incomplete("1 ->\n"));
+    }
+
+    #[test]
+    fn extra_close_curly_is_not_incomplete() {
+        assert!(!incomplete("object X {}}"));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::source::Source;
-use crate::syntax::{Token, TokenKind, TokenKind::*};
+use crate::syntax::{AtomKind, Token, TokenKind, TokenKind::*};
 use crate::Graphemes;
 use peekmore::{PeekMore, PeekMoreIterator};
 use std::sync::Arc;
@@ -19,24 +19,121 @@ fn is_valid_digit(c: char, radix: u32) -> bool {
     valid_digits.contains(&c.to_ascii_uppercase())
 }
 
+/// Parses a float in a radix other than 10, which the standard
+/// library doesn't support.
+///
+/// All digits are assembled into a single integer mantissa, which is
+/// then scaled by the radix raised to minus the number of fraction
+/// digits. For power-of-two radices this rounds only once, so literals
+/// like `16#1.8` are exact. Literals whose exponent is out of range
+/// yield `None` rather than silently becoming infinite or zero.
+fn parse_float_radix(integer: &str, fraction: &str, radix: u32) -> Option<f64> {
+    let (negative, integer) = if integer.starts_with('-') {
+        (true, &integer[1..])
+    } else {
+        (false, integer)
+    };
+
+    let mut mantissa = 0u128;
+    let mut exponent = 0i64;
+    for c in integer.chars().chain(fraction.chars()) {
+        let digit = u128::from(c.to_digit(radix)?);
+        match mantissa
+            .checked_mul(u128::from(radix))
+            .and_then(|m| m.checked_add(digit))
+        {
+            Some(m) => mantissa = m,
+            // Digits beyond the precision of the mantissa only scale it.
+            None => exponent += 1,
+        }
+    }
+    exponent -= fraction.chars().count() as i64;
+
+    let value = scale(mantissa as f64, f64::from(radix), exponent)?;
+    Some(if negative { -value } else { value })
+}
+
+/// Multiplies the value by the base raised to the exponent, in steps
+/// small enough that no intermediate power overflows on its own.
+fn scale(mut value: f64, base: f64, mut exponent: i64) -> Option<f64> {
+    const STEP: i64 = 64;
+
+    let nonzero = value != 0.0;
+    while exponent > 0 && value.is_finite() {
+        let step = exponent.min(STEP);
+        value *= base.powi(step as i32);
+        exponent -= step;
+    }
+    while exponent < 0 && value != 0.0 {
+        let step = (-exponent).min(STEP);
+        value /= base.powi(step as i32);
+        exponent += step;
+    }
+
+    if !value.is_finite() || (nonzero && value == 0.0) {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// What the lexer found at the start of a token, before taking it.
+enum TokenStart {
+    Period,
+    OpenCurly,
+    CloseCurly,
+    Hat,
+    Colon,
+    Pipe,
+    Arrow,
+    Equals,
+    Shebang,
+    Newline,
+    Whitespace,
+    Number,
+    NegativeInfinity,
+    Symbol,
+}
+
+/// A lazy stream of tokens, lexed as they are requested.
+///
+/// The stream always ends with a single EOF token.
+pub struct Tokens<'a> {
+    lexer: Lexer<'a>,
+    done: bool,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Arc<Token>;
+
+    fn next(&mut self) -> Option<Arc<Token>> {
+        if self.done {
+            return None;
+        }
+
+        if let Some(_) = self.lexer.chars.peek() {
+            return Some(self.lexer.next_token());
+        }
+
+        self.done = true;
+        let end_offset = self.lexer.offset();
+        Some(Token::new(EOF, &self.lexer.source, end_offset..end_offset))
+    }
+}
+
 impl<'a> Lexer<'a> {
     pub fn tokenize(source: &Arc<Source>) -> Arc<Vec<Arc<Token>>> {
-        let chars = source.graphemes().peekmore();
-        let lexer = Lexer {
-            source: &source,
-            chars,
-        };
-        lexer.get_tokens()
+        Arc::new(Lexer::iter(source).collect())
     }
 
-    fn get_tokens(mut self) -> Arc<Vec<Arc<Token>>> {
-        let mut tokens = vec![];
-        while let Some(_) = self.chars.peek() {
-            tokens.push(self.next_token());
+    pub fn iter(source: &'a Arc<Source>) -> Tokens<'a> {
+        Tokens {
+            lexer: Lexer {
+                source,
+                chars: source.graphemes().peekmore(),
+            },
+            done: false,
         }
-        let end_offset = self.offset();
-        tokens.push(Token::new(EOF, &self.source, end_offset..end_offset));
-        Arc::new(tokens)
     }
 
     fn offset(&mut self) -> usize {
@@ -74,89 +171,169 @@ impl<'a> Lexer<'a> {
 
     fn next_token(&mut self) -> Arc<Token> {
         let start_offset = self.offset();
-        let kind: TokenKind;
 
-        match self.peek_char() {
-            '.' => {
+        let kind = match self.token_start(start_offset) {
+            Some(TokenStart::Period) => {
                 self.skip();
-                kind = Period;
+                Period
             }
 
-            '{' => {
+            Some(TokenStart::OpenCurly) => {
                 self.skip();
-                kind = OpenCurly;
+                OpenCurly
             }
 
-            '}' => {
+            Some(TokenStart::CloseCurly) => {
                 self.skip();
-                kind = CloseCurly;
+                CloseCurly
             }
 
-            '^' => {
+            Some(TokenStart::Hat) => {
                 self.skip();
-                kind = Hat;
+                Hat
             }
 
-            '-' if self.peek_next_char() == '>' => {
+            Some(TokenStart::Colon) => {
                 self.skip();
+                Colon
+            }
+
+            Some(TokenStart::Pipe) => {
                 self.skip();
-                kind = Arrow;
+                Pipe
             }
 
-            c if c == '\n' => {
+            Some(TokenStart::Arrow) => {
+                self.skip();
                 self.skip();
-                kind = Whitespace;
+                Arrow
             }
 
-            c if c.is_whitespace() => {
-                self.skip_whitespace();
-                kind = Whitespace;
+            Some(TokenStart::Equals) => {
+                self.skip();
+                Equals
             }
 
-            c if c.is_numeric() || (c == '-' && self.peek_next_char().is_numeric()) => {
-                kind = self.take_number();
+            Some(TokenStart::Shebang) => {
+                while !self.peek().is_empty() && self.peek_char() != '\n' && self.peek() != "\r\n" {
+                    self.skip();
+                }
+                Shebang
             }
 
-            c if c.is_alphabetic() => {
-                kind = self.take_symbol_or_keyword();
+            Some(TokenStart::Newline) => {
+                self.skip();
+                Whitespace
+            }
+
+            Some(TokenStart::Whitespace) => {
+                self.skip_whitespace();
+                Whitespace
             }
 
-            _ => {
+            Some(TokenStart::Number) => self.take_number(),
+
+            Some(TokenStart::NegativeInfinity) => {
                 self.skip();
-                kind = Unknown;
+                self.take_symbol();
+                FloatLiteral(f64::NEG_INFINITY, true)
             }
-        }
+
+            Some(TokenStart::Symbol) => self.take_symbol_or_keyword(),
+
+            None => {
+                self.skip();
+                while self.is_at_unknown() {
+                    self.skip();
+                }
+                Unknown
+            }
+        };
 
         let end_offset = self.offset();
 
         if start_offset == end_offset {
-            panic!("next_token didn't move forward");
+            self.skip();
+            return Token::new(Unknown, &self.source, start_offset..self.offset());
         }
 
         Token::new(kind, &self.source, start_offset..end_offset)
     }
 
+    /// The kind of token that starts at the next grapheme, if any.
+    fn token_start(&mut self, offset: usize) -> Option<TokenStart> {
+        let start = match self.peek_char() {
+            '.' => TokenStart::Period,
+            '{' => TokenStart::OpenCurly,
+            '}' => TokenStart::CloseCurly,
+            '^' => TokenStart::Hat,
+            ':' => TokenStart::Colon,
+            '|' => TokenStart::Pipe,
+            '-' | '=' if self.peek_next_char() == '>' => TokenStart::Arrow,
+            '=' => TokenStart::Equals,
+            '#' if offset == 0 && self.peek_next_char() == '!' => TokenStart::Shebang,
+            '\n' => TokenStart::Newline,
+            c if c.is_whitespace() => TokenStart::Whitespace,
+            c if c.is_numeric() || (c == '-' && self.peek_next_char().is_numeric()) => {
+                TokenStart::Number
+            }
+            '-' if self.is_at_word("-Infinity") => TokenStart::NegativeInfinity,
+            c if c.is_alphabetic() && is_symbol_grapheme(self.peek()) => TokenStart::Symbol,
+            _ => return None,
+        };
+        Some(start)
+    }
+
+    /// Whether the next grapheme can't start any token, so that it
+    /// belongs in the same `Unknown` token as the one before it.
+    fn is_at_unknown(&mut self) -> bool {
+        let offset = self.offset();
+        !self.peek().is_empty() && self.token_start(offset).is_none()
+    }
+
     fn take_symbol_or_keyword(&mut self) -> TokenKind {
         let symbol = self.take_symbol();
 
         let mut kind = match symbol {
             "object" => ObjectKeyword,
+            "self" => SelfKeyword,
+            "sender" => SenderKeyword,
             _ => Identifier,
         };
 
-        if let '!' | '?' = self.peek_char() {
-            self.skip();
-            kind = NullaryAtom;
+        match self.peek_char() {
+            '!' => {
+                self.skip();
+                kind = NullaryAtom(AtomKind::Command);
+            }
+            '?' => {
+                self.skip();
+                kind = NullaryAtom(AtomKind::Query);
+            }
+            _ => {}
         }
 
         kind
     }
 
+    /// Whether the remaining source starts with the word, not followed
+    /// by anything that would make it part of a longer symbol or atom.
+    fn is_at_word(&mut self, word: &str) -> bool {
+        let offset = self.offset();
+        let rest = self.source.slice(offset..self.source.len());
+        rest.starts_with(word)
+            && !rest[word.len()..]
+                .chars()
+                .next()
+                .map(|c| c.is_alphanumeric() || c == '\'' || c == '!' || c == '?')
+                .unwrap_or(false)
+    }
+
     fn take_symbol(&mut self) -> &str {
         let start = self.peek().as_ptr();
         let mut length = 0;
 
-        while self.peek_char().is_alphanumeric() {
+        while is_symbol_grapheme(self.peek()) {
             length += self.take().len();
         }
 
@@ -217,15 +394,17 @@ impl<'a> Lexer<'a> {
         }
         self.take();
         let fraction = self.take_digits(radix);
-        let precision = fraction.len();
-        number.push_str(fraction.as_str());
 
-        return match i64::from_str_radix(&number, radix) {
-            Ok(n) => {
-                TokenKind::FloatLiteral((n as f64) / f64::from(radix).powi(precision as i32), true)
-            }
-            Err(_) => TokenKind::FloatLiteral(f64::NAN, false),
+        let value = if radix == 10 {
+            format!("{}.{}", number, fraction).parse().ok()
+        } else {
+            parse_float_radix(&number, &fraction, radix)
         };
+
+        match value {
+            Some(f) if f64::is_finite(f) => TokenKind::FloatLiteral(f, true),
+            _ => TokenKind::FloatLiteral(f64::NAN, false),
+        }
     }
 
     fn take_digits(&mut self, radix: u32) -> String {
@@ -239,9 +418,35 @@ impl<'a> Lexer<'a> {
     }
 }
 
+/// The value of a word that is read as a float literal where an
+/// expression is expected. Anywhere else, like in the name of an object,
+/// the word is an ordinary identifier, so that it isn't reserved.
+pub fn float_word(word: &str) -> Option<f64> {
+    match word {
+        "Infinity" => Some(f64::INFINITY),
+        "NaN" => Some(f64::NAN),
+        _ => None,
+    }
+}
+
+/// Whether a grapheme cluster can be part of a symbol. The cluster is
+/// judged as a whole, so that a letter followed by combining marks
+/// stays in one identifier, while a cluster that merely starts with a
+/// letter but carries whitespace or control characters does not.
+fn is_symbol_grapheme(grapheme: &str) -> bool {
+    let mut chars = grapheme.chars();
+    match chars.next() {
+        Some(c) if c.is_alphanumeric() => {
+            chars.all(|c| !c.is_whitespace() && !c.is_control() && !c.is_ascii_punctuation())
+        }
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::syntax::Parser;
 
     #[tokio::test]
     async fn empty_source() {
@@ -265,6 +470,22 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn symbol_with_combining_marks() {
+        let source = Source::new("test:x", "cafe\u{301} e\u{301}\u{302}x");
+        let tokens = Lexer::tokenize(&source);
+
+        assert_eq!(
+            tokens,
+            Arc::new(vec![
+                Token::new(Identifier, &source, 0..4),
+                Token::new(Whitespace, &source, 4..5),
+                Token::new(Identifier, &source, 5..7),
+                Token::new(EOF, &source, 7..7),
+            ])
+        );
+    }
+
     #[tokio::test]
     async fn symbol() {
         let source = Source::new("test:x", "åäöकि''");
@@ -280,20 +501,115 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn two_unknown_tokens() {
+    async fn adjacent_unknowns_are_one_token() {
         let source = Source::new("test:x", "¥•");
         let tokens = Lexer::tokenize(&source);
 
         assert_eq!(
             tokens,
             Arc::new(vec![
-                Token::new(Unknown, &source, 0..1),
-                Token::new(Unknown, &source, 1..2),
+                Token::new(Unknown, &source, 0..2),
                 Token::new(EOF, &source, 2..2),
             ])
         );
     }
 
+    #[tokio::test]
+    async fn unknowns_run_until_a_token_starts() {
+        let source = Source::new("test:x", "¥-\0•->");
+        let tokens = Lexer::tokenize(&source);
+
+        assert_eq!(
+            tokens,
+            Arc::new(vec![
+                Token::new(Unknown, &source, 0..4),
+                Token::new(Arrow, &source, 4..6),
+                Token::new(EOF, &source, 6..6),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn separated_unknowns_are_separate_tokens() {
+        let source = Source::new("test:x", "¥ •x");
+        let tokens = Lexer::tokenize(&source);
+
+        assert_eq!(
+            tokens,
+            Arc::new(vec![
+                Token::new(Unknown, &source, 0..1),
+                Token::new(Whitespace, &source, 1..2),
+                Token::new(Unknown, &source, 2..3),
+                Token::new(Identifier, &source, 3..4),
+                Token::new(EOF, &source, 4..4),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn lazy_iteration() {
+        let source = Source::new("test:x", "object X. object Y.");
+        let mut tokens = Lexer::iter(&source);
+
+        assert_eq!(
+            tokens.by_ref().take(2).collect::<Vec<_>>(),
+            vec![
+                Token::new(ObjectKeyword, &source, 0..6),
+                Token::new(Whitespace, &source, 6..7),
+            ]
+        );
+        assert_eq!(tokens.lexer.offset(), 7);
+
+        assert_eq!(tokens.last(), Some(Token::new(EOF, &source, 19..19)));
+    }
+
+    #[tokio::test]
+    async fn arrows() {
+        let source = Source::new("test:x", "=>->");
+        let tokens = Lexer::tokenize(&source);
+
+        assert_eq!(
+            tokens,
+            Arc::new(vec![
+                Token::new(Arrow, &source, 0..2),
+                Token::new(Arrow, &source, 2..4),
+                Token::new(EOF, &source, 4..4),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn separated_arrow() {
+        let source = Source::new("test:x", "= >");
+        let tokens = Lexer::tokenize(&source);
+
+        assert_eq!(
+            tokens,
+            Arc::new(vec![
+                Token::new(Equals, &source, 0..1),
+                Token::new(Whitespace, &source, 1..2),
+                Token::new(Unknown, &source, 2..3),
+                Token::new(EOF, &source, 3..3),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn self_keyword() {
+        let source = Source::new("test:x", "self selfish");
+        let tokens = Lexer::tokenize(&source);
+
+        assert_eq!(
+            tokens,
+            Arc::new(vec![
+                Token::new(SelfKeyword, &source, 0..4),
+                Token::new(Whitespace, &source, 4..5),
+                Token::new(Identifier, &source, 5..12),
+                Token::new(EOF, &source, 12..12),
+            ])
+        );
+    }
+
     #[tokio::test]
     async fn import_keyword() {
         let source = Source::new("test:x", "object");
@@ -307,4 +623,129 @@ mod tests {
             ])
         );
     }
+
+    #[tokio::test]
+    async fn long_decimal_float() {
+        let source = Source::new("test:x", "3.14159265358979323846264338327950288");
+        let tokens = Lexer::tokenize(&source);
+
+        assert_eq!(
+            tokens,
+            Arc::new(vec![
+                Token::new(FloatLiteral(std::f64::consts::PI, true), &source, 0..37),
+                Token::new(EOF, &source, 37..37),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn commands_and_queries() {
+        let source = Source::new("test:x", "run! value?");
+        let tokens = Lexer::tokenize(&source);
+
+        assert_eq!(
+            tokens,
+            Arc::new(vec![
+                Token::new(NullaryAtom(AtomKind::Command), &source, 0..4),
+                Token::new(Whitespace, &source, 4..5),
+                Token::new(NullaryAtom(AtomKind::Query), &source, 5..11),
+                Token::new(EOF, &source, 11..11),
+            ])
+        );
+        assert_eq!(tokens[0].atom_kind(), Some(AtomKind::Command));
+        assert_eq!(tokens[2].atom_kind(), Some(AtomKind::Query));
+        assert_eq!(tokens[1].atom_kind(), None);
+    }
+
+    #[tokio::test]
+    async fn special_floats() {
+        let source = Source::new("test:x", "Infinity -Infinity NaN -0.0 -Infinityx");
+        let tokens = Lexer::tokenize(&source);
+        let floats: Vec<_> = tokens
+            .iter()
+            .filter_map(|t| match t.kind {
+                FloatLiteral(f, true) => Some(f),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(floats.len(), 2);
+        assert_eq!(floats[0], f64::NEG_INFINITY);
+        assert_eq!(floats[1], 0.0);
+        assert!(floats[1].is_sign_negative());
+
+        assert_eq!(tokens[0].kind, Identifier);
+        assert_eq!(float_word(tokens[0].lexeme()), Some(f64::INFINITY));
+        assert_eq!(tokens[4].kind, Identifier);
+        assert!(float_word(tokens[4].lexeme()).unwrap().is_nan());
+
+        assert_eq!(tokens[8].kind, Unknown);
+        assert_eq!(tokens[9].kind, Identifier);
+    }
+
+    #[tokio::test]
+    async fn hex_and_binary_floats() {
+        let source = Source::new("test:x", "16#1.8 2#0.1");
+        let tokens = Lexer::tokenize(&source);
+
+        assert_eq!(
+            tokens,
+            Arc::new(vec![
+                Token::new(FloatLiteral(1.5, true), &source, 0..6),
+                Token::new(Whitespace, &source, 6..7),
+                Token::new(FloatLiteral(0.5, true), &source, 7..12),
+                Token::new(EOF, &source, 12..12),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn out_of_range_radix_float() {
+        let code = format!("16#0.{}1", "0".repeat(300));
+        let source = Source::new("test:x", code.as_str());
+        let tokens = Lexer::tokenize(&source);
+
+        match tokens[0].kind {
+            FloatLiteral(_, valid) => assert!(!valid),
+            ref kind => panic!("expected a float literal, got {:?}", kind),
+        }
+    }
+
+    #[tokio::test]
+    async fn high_radix_float() {
+        let source = Source::new("test:x", "36#Z.I -16#0.08");
+        let tokens = Lexer::tokenize(&source);
+
+        assert_eq!(
+            tokens,
+            Arc::new(vec![
+                Token::new(FloatLiteral(35.5, true), &source, 0..6),
+                Token::new(Whitespace, &source, 6..7),
+                Token::new(FloatLiteral(-0.03125, true), &source, 7..15),
+                Token::new(EOF, &source, 15..15),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn leading_shebang_line() {
+        let source = Source::new("test:x", "#!/usr/bin/env aspen\nobject X.");
+        let tokens = Lexer::tokenize(&source);
+
+        assert_eq!(tokens[0], Token::new(Shebang, &source, 0..20));
+        assert_eq!(tokens[1], Token::new(Whitespace, &source, 20..21));
+        assert_eq!(tokens[2], Token::new(ObjectKeyword, &source, 21..27));
+
+        let (root, diagnostics) = Parser::new(source).parse().await;
+        assert!(diagnostics.is_ok());
+        assert_eq!(root.as_module().unwrap().declarations.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn shebang_only_at_the_start() {
+        let source = Source::new("test:x", " #!");
+        let tokens = Lexer::tokenize(&source);
+
+        assert!(tokens.iter().all(|t| t.kind != Shebang));
+    }
 }
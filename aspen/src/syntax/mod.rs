@@ -3,6 +3,7 @@
 //! the Aspen language, as well as implementing the parsing of that
 //! grammar.
 
+mod incomplete;
 mod lexer;
 mod navigator;
 mod node;
@@ -12,6 +13,7 @@ mod parser;
 mod token;
 mod token_cursor;
 
+pub use self::incomplete::*;
 pub use self::lexer::*;
 pub use self::navigator::*;
 pub use self::node::*;
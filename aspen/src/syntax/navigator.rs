@@ -1,4 +1,4 @@
-use crate::syntax::{Expression, MessageSend, Node, TypeExpression};
+use crate::syntax::{Declaration, Expression, MessageSend, Method, Node, TypeExpression};
 use crate::Location;
 use std::sync::Arc;
 
@@ -40,7 +40,7 @@ impl Navigator {
         for nav in self.traverse() {
             let range = nav.node.range();
 
-            if &range.start <= location && &range.end > location {
+            if range.contains_location(location, false) {
                 result = Some(nav.clone())
             }
 
@@ -51,6 +51,32 @@ impl Navigator {
         result
     }
 
+    /// The innermost node containing the location, i.e. the one with
+    /// the shortest range. When ranges are equal, the deepest node wins.
+    pub fn smallest_node_at(self: &Arc<Self>, location: &Location) -> Option<Arc<Navigator>> {
+        let mut result: Option<Arc<Navigator>> = None;
+        for nav in self.traverse() {
+            let range = nav.node.range();
+
+            if !range.contains_location(location, false) {
+                continue;
+            }
+
+            let is_smaller = match &result {
+                None => true,
+                Some(current) => {
+                    let current = current.node.range();
+                    range.end.offset - range.start.offset
+                        <= current.end.offset - current.start.offset
+                }
+            };
+            if is_smaller {
+                result = Some(nav);
+            }
+        }
+        result
+    }
+
     pub fn down_to(self: &Arc<Self>, node: &Arc<dyn Node>) -> Option<Arc<Navigator>> {
         for nav in self.traverse() {
             if nav.node.range() == node.range() {
@@ -113,6 +139,15 @@ impl Navigator {
         self.traverse()
             .filter_map(|n| n.node.clone().as_message_send())
     }
+
+    pub fn all_methods(self: &Arc<Self>) -> impl Iterator<Item = Arc<Method>> {
+        self.traverse().filter_map(|n| n.node.clone().as_method())
+    }
+
+    pub fn all_declarations(self: &Arc<Self>) -> impl Iterator<Item = Arc<Declaration>> {
+        self.traverse()
+            .filter_map(|n| n.node.clone().as_declaration())
+    }
 }
 
 #[derive(Debug)]
@@ -133,3 +168,28 @@ impl Iterator for Traverse {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::Parser;
+    use crate::Source;
+
+    #[tokio::test]
+    async fn smallest_node_inside_message_send() {
+        let source = Source::new("test:x", "object X { 1 -> 22 33. }");
+        let (root, _) = Parser::new(source.clone()).parse().await;
+        let location = source.location_at(20);
+
+        let nav = Navigator::new(root).smallest_node_at(&location).unwrap();
+
+        match nav
+            .up_to_cast(|n| n.as_expression())
+            .as_ref()
+            .map(AsRef::as_ref)
+        {
+            Some(Expression::Integer(i)) => assert_eq!(i.literal.lexeme(), "33"),
+            e => panic!("expected an integer, got {:?}", e),
+        }
+    }
+}
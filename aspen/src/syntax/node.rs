@@ -35,6 +35,14 @@ pub trait Node: fmt::Debug + Send + Sync {
     fn as_message_send(self: Arc<Self>) -> Option<Arc<MessageSend>> {
         None
     }
+
+    fn as_method(self: Arc<Self>) -> Option<Arc<Method>> {
+        None
+    }
+
+    fn as_self_expression(self: Arc<Self>) -> Option<Arc<SelfExpression>> {
+        None
+    }
 }
 
 pub trait IntoNode {
@@ -156,6 +164,29 @@ impl fmt::Debug for Module {
     }
 }
 
+impl Module {
+    /// Checks that the declarations appear in source order without
+    /// overlapping, which navigation relies on. This can only be broken
+    /// by a bug in the parser, so rather than producing a diagnostic,
+    /// offending declarations are logged to stderr.
+    pub fn validate_ranges(&self) -> bool {
+        let mut valid = true;
+        for pair in self.declarations.windows(2) {
+            let (previous, next) = (pair[0].range(), pair[1].range());
+            if next.start < previous.end {
+                eprintln!(
+                    "warning: declaration at {:?} overlaps or precedes declaration at {:?} in {:?}",
+                    next,
+                    previous,
+                    self.source.uri()
+                );
+                valid = false;
+            }
+        }
+        valid
+    }
+}
+
 impl Node for Module {
     fn source(&self) -> &Arc<Source> {
         &self.source
@@ -318,6 +349,14 @@ impl ObjectDeclaration {
             ObjectMember::Method(m) => Some(m),
         })
     }
+
+    /// Whether the object has braces with nothing between them.
+    pub fn has_empty_body(&self) -> bool {
+        match &self.body {
+            None => false,
+            Some(body) => body.members.is_empty(),
+        }
+    }
 }
 
 impl Node for ObjectDeclaration {
@@ -427,12 +466,14 @@ impl Node for ObjectMember {
 /// ```bnf
 /// Method :=
 ///   Pattern
+///   (COLON TypeExpression)?
 ///   ARROW
 ///   Statement+
 /// ```
 pub struct Method {
     pub source: Arc<Source>,
     pub pattern: Arc<Pattern>,
+    pub reply_type: Option<Arc<TypeExpression>>,
     pub arrow: Arc<Token>,
     pub statements: Vec<Arc<Statement>>,
 }
@@ -441,6 +482,7 @@ impl fmt::Debug for Method {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Method")
             .field("pattern", &self.pattern)
+            .field("reply_type", &self.reply_type)
             .field("statements", &self.statements)
             .finish()
     }
@@ -464,6 +506,12 @@ impl Node for Method {
         Children::Iter(Box::new(
             vec![self.pattern.clone() as Arc<dyn Node>]
                 .into_iter()
+                .chain(
+                    self.reply_type
+                        .clone()
+                        .into_iter()
+                        .map(|t| t as Arc<dyn Node>),
+                )
                 .chain(
                     self.statements
                         .clone()
@@ -472,15 +520,26 @@ impl Node for Method {
                 ),
         ))
     }
+
+    fn as_method(self: Arc<Self>) -> Option<Arc<Method>> {
+        Some(self)
+    }
 }
 
 /// ```bnf
 /// Pattern :=
-///   Integer
+///   SinglePattern
+///   (PIPE SinglePattern)*
+///
+/// SinglePattern :=
+///   Integer |
+///   NullaryAtom
 /// ```
 pub enum Pattern {
     Integer(Arc<Integer>),
     Nullary(Arc<NullaryAtomExpression>),
+    /// Matches if any of the alternatives match.
+    Or(Vec<Arc<Pattern>>),
 }
 
 impl fmt::Debug for Pattern {
@@ -488,6 +547,7 @@ impl fmt::Debug for Pattern {
         match self {
             Pattern::Integer(n) => f.debug_tuple("Pattern::Integer").field(n).finish(),
             Pattern::Nullary(n) => f.debug_tuple("Pattern::Nullary").field(n).finish(),
+            Pattern::Or(n) => f.debug_tuple("Pattern::Or").field(n).finish(),
         }
     }
 }
@@ -497,6 +557,7 @@ impl Node for Pattern {
         match self {
             Pattern::Integer(n) => n.source(),
             Pattern::Nullary(n) => n.source(),
+            Pattern::Or(n) => n[0].source(),
         }
     }
 
@@ -504,6 +565,7 @@ impl Node for Pattern {
         match self {
             Pattern::Integer(n) => n.range(),
             Pattern::Nullary(n) => n.range(),
+            Pattern::Or(n) => n[0].range().through(n[n.len() - 1].range()),
         }
     }
 
@@ -511,17 +573,22 @@ impl Node for Pattern {
         match self {
             Pattern::Integer(n) => Children::Single(Some(n.clone())),
             Pattern::Nullary(n) => Children::Single(Some(n.clone())),
+            Pattern::Or(n) => {
+                Children::Iter(Box::new(n.clone().into_iter().map(|p| p as Arc<dyn Node>)))
+            }
         }
     }
 }
 
 /// ```bnf
 /// Statement :=
+///   Binding?
 ///   Expression
 ///   PERIOD
 /// ```
 pub struct Statement {
     pub source: Arc<Source>,
+    pub binding: Option<Arc<Binding>>,
     pub expression: Arc<Expression>,
     pub period: Option<Arc<Token>>,
 }
@@ -529,18 +596,29 @@ pub struct Statement {
 impl fmt::Debug for Statement {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Statement")
+            .field("binding", &self.binding)
             .field("expression", &self.expression)
             .finish()
     }
 }
 
+impl Statement {
+    /// The name that the value of the statement is bound to, if any.
+    pub fn bound_name(&self) -> Option<&str> {
+        self.binding.as_ref().map(|b| b.symbol.identifier.lexeme())
+    }
+}
+
 impl Node for Statement {
     fn source(&self) -> &Arc<Source> {
         &self.source
     }
 
     fn range(&self) -> Range {
-        let r = self.expression.range();
+        let mut r = self.expression.range();
+        if let Some(b) = self.binding.as_ref() {
+            r = b.range().through(r);
+        }
         match self.period.as_ref() {
             None => r,
             Some(p) => r.through(p.range.clone()),
@@ -548,7 +626,43 @@ impl Node for Statement {
     }
 
     fn children(&self) -> Children {
-        Children::Single(Some(self.expression.clone()))
+        match self.binding.as_ref() {
+            None => Children::Single(Some(self.expression.clone())),
+            Some(b) => Children::Iter(Box::new(
+                vec![b.clone() as Arc<dyn Node>, self.expression.clone()].into_iter(),
+            )),
+        }
+    }
+}
+
+/// ```bnf
+/// Binding :=
+///   Symbol
+///   EQUALS
+/// ```
+pub struct Binding {
+    pub source: Arc<Source>,
+    pub symbol: Arc<Symbol>,
+    pub equals: Arc<Token>,
+}
+
+impl fmt::Debug for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Binding").field(&self.symbol).finish()
+    }
+}
+
+impl Node for Binding {
+    fn source(&self) -> &Arc<Source> {
+        &self.source
+    }
+
+    fn range(&self) -> Range {
+        self.symbol.range().through(self.equals.range.clone())
+    }
+
+    fn children(&self) -> Children {
+        Children::Single(Some(self.symbol.clone()))
     }
 }
 
@@ -670,7 +784,10 @@ impl AsRef<str> for Symbol {
 ///   Float |
 ///   ReferenceExpression |
 ///   MessageSend |
-///   NullaryAtomExpression
+///   NullaryAtomExpression |
+///   AnswerExpression |
+///   SelfExpression |
+///   SenderExpression
 /// ```
 pub enum Expression {
     Integer(Arc<Integer>),
@@ -679,6 +796,8 @@ pub enum Expression {
     MessageSend(Arc<MessageSend>),
     NullaryAtom(Arc<NullaryAtomExpression>),
     Answer(Arc<AnswerExpression>),
+    SelfReference(Arc<SelfExpression>),
+    Sender(Arc<SenderExpression>),
 }
 
 impl fmt::Debug for Expression {
@@ -692,6 +811,10 @@ impl fmt::Debug for Expression {
             }
             Expression::NullaryAtom(n) => f.debug_tuple("Expression::Atom").field(n).finish(),
             Expression::Answer(n) => f.debug_tuple("Expression::Answer").field(n).finish(),
+            Expression::SelfReference(n) => {
+                f.debug_tuple("Expression::SelfReference").field(n).finish()
+            }
+            Expression::Sender(n) => f.debug_tuple("Expression::Sender").field(n).finish(),
         }
     }
 }
@@ -705,6 +828,8 @@ impl Node for Expression {
             Expression::MessageSend(n) => n.source(),
             Expression::NullaryAtom(n) => n.source(),
             Expression::Answer(n) => n.source(),
+            Expression::SelfReference(n) => n.source(),
+            Expression::Sender(n) => n.source(),
         }
     }
 
@@ -716,6 +841,8 @@ impl Node for Expression {
             Expression::MessageSend(n) => n.range(),
             Expression::NullaryAtom(n) => n.range(),
             Expression::Answer(n) => n.range(),
+            Expression::SelfReference(n) => n.range(),
+            Expression::Sender(n) => n.range(),
         }
     }
 
@@ -727,6 +854,8 @@ impl Node for Expression {
             Expression::MessageSend(n) => Children::Single(Some(n.clone())),
             Expression::NullaryAtom(n) => Children::Single(Some(n.clone())),
             Expression::Answer(n) => Children::Single(Some(n.clone())),
+            Expression::SelfReference(n) => Children::Single(Some(n.clone())),
+            Expression::Sender(n) => Children::Single(Some(n.clone())),
         }
     }
 
@@ -903,6 +1032,68 @@ impl Node for ReferenceExpression {
     }
 }
 
+/// ```bnf
+/// SelfExpression :=
+///   SELF_KEYWORD
+/// ```
+pub struct SelfExpression {
+    pub source: Arc<Source>,
+    pub keyword: Arc<Token>,
+}
+
+impl fmt::Debug for SelfExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SelfExpression").finish()
+    }
+}
+
+impl Node for SelfExpression {
+    fn source(&self) -> &Arc<Source> {
+        &self.source
+    }
+
+    fn range(&self) -> Range {
+        self.keyword.range.clone()
+    }
+
+    fn children(&self) -> Children {
+        Children::None
+    }
+
+    fn as_self_expression(self: Arc<Self>) -> Option<Arc<SelfExpression>> {
+        Some(self)
+    }
+}
+
+/// ```bnf
+/// SenderExpression :=
+///   SENDER_KEYWORD
+/// ```
+pub struct SenderExpression {
+    pub source: Arc<Source>,
+    pub keyword: Arc<Token>,
+}
+
+impl fmt::Debug for SenderExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SenderExpression").finish()
+    }
+}
+
+impl Node for SenderExpression {
+    fn source(&self) -> &Arc<Source> {
+        &self.source
+    }
+
+    fn range(&self) -> Range {
+        self.keyword.range.clone()
+    }
+
+    fn children(&self) -> Children {
+        Children::None
+    }
+}
+
 /// ```bnf
 /// NullaryAtomExpression :=
 ///   NULLARY_ATOM
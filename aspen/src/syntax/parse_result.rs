@@ -1,3 +1,4 @@
+use crate::syntax::{Method, Node, Token};
 use crate::{Diagnostic, Diagnostics, Range, Severity, Source};
 use std::cmp::Ordering;
 use std::future::Future;
@@ -120,3 +121,53 @@ impl Diagnostic for Expected {
         format!("Expected {}", self.0)
     }
 }
+
+/// A method written at the top level of a module, where only
+/// declarations are allowed.
+#[derive(Debug, Clone)]
+pub struct MethodOutsideObject(pub Arc<Method>);
+
+impl Diagnostic for MethodOutsideObject {
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn source(&self) -> &Arc<Source> {
+        &self.0.source
+    }
+
+    fn range(&self) -> Range {
+        self.0.range()
+    }
+
+    fn message(&self) -> String {
+        "Methods must be declared inside an object body, like `object X { 1 -> 2. }`".into()
+    }
+}
+
+/// Periods between the members of an object body that don't end any
+/// statement, like the second one in `object X { 1 -> 2.. }`.
+#[derive(Debug, Clone)]
+pub struct StrayPeriods(pub Vec<Arc<Token>>);
+
+impl Diagnostic for StrayPeriods {
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn source(&self) -> &Arc<Source> {
+        &self.0[0].source
+    }
+
+    fn range(&self) -> Range {
+        Range::over(self.0.iter().map(|t| t.range.clone()))
+    }
+
+    fn message(&self) -> String {
+        if self.0.len() == 1 {
+            "Unexpected period".into()
+        } else {
+            "Unexpected periods".into()
+        }
+    }
+}
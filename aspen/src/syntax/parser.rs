@@ -1,6 +1,8 @@
 use crate::syntax::ParseResult::*;
 use crate::syntax::*;
-use crate::{Diagnostics, Source, SourceKind};
+use crate::{Diagnostics, InternalCompilerError, Source, SourceKind};
+use futures::FutureExt;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
 
 pub struct Parser {
@@ -46,6 +48,34 @@ impl Parser {
         }
     }
 
+    /// Lexes and parses any source without panicking, so that untrusted
+    /// input can be parsed safely. Should the parser still panic, the
+    /// panic is reported as an internal compiler error on an empty
+    /// module.
+    pub async fn parse_lenient(source: Arc<Source>) -> (Arc<Root>, Diagnostics) {
+        let result = AssertUnwindSafe(async {
+            let mut parser = Parser::new(source.clone());
+            parser.parse().await
+        })
+        .catch_unwind()
+        .await;
+
+        match result {
+            Ok(parsed) => parsed,
+            Err(payload) => {
+                let mut diagnostics = Diagnostics::new();
+                diagnostics.push(InternalCompilerError::from_panic(source.clone(), payload));
+                (
+                    Arc::new(Root::Module(Arc::new(Module {
+                        source,
+                        declarations: vec![],
+                    }))),
+                    diagnostics,
+                )
+            }
+        }
+    }
+
     pub fn fail_expecting<S: Into<String>, T>(&mut self, message: S) -> ParseResult<T> {
         ParseResult::fail(self.expected(message))
     }
@@ -79,6 +109,20 @@ impl Parser {
     pub fn offset(&self) -> usize {
         self.tokens.offset()
     }
+
+    /// Whether the upcoming tokens start a method, like `a! ->`. Arrows
+    /// never appear in statements, so this is where the next member of
+    /// an object body starts even if the period before it is missing.
+    pub async fn sees_method_head(&self) -> bool {
+        let mut lookahead = self.split();
+        if let Failed(_) = ParsePattern.parse(&mut lookahead).await {
+            return false;
+        }
+        if let Failed(_) = ParseReplyType.parse(&mut lookahead).await {
+            return false;
+        }
+        lookahead.tokens.sees(TokenKind::Arrow)
+    }
 }
 
 struct ParseRoot;
@@ -152,6 +196,14 @@ impl ParseStrategy<Arc<Module>> for ParseModule {
                     encountered_error = false;
                 }
                 None => {
+                    let mut method_parser = parser.split();
+                    if let Succeeded(_, method) = ParseMethod.parse(&mut method_parser).await {
+                        diagnostics.push(MethodOutsideObject(method));
+                        *parser = method_parser;
+                        encountered_error = false;
+                        continue;
+                    }
+
                     if !encountered_error {
                         diagnostics.push(parser.expected(format!("a declaration")));
                     }
@@ -251,11 +303,24 @@ impl ParseStrategy<Arc<ObjectBody>> for ParseObjectBody {
             .and_then(async move |open_curly| {
                 let mut diagnostics = Diagnostics::new();
 
-                let members = ParseMany::of(ParseObjectMember)
-                    .parse(parser)
-                    .await
-                    .collect_diagnostics(&mut diagnostics)
-                    .unwrap_or(vec![]);
+                let mut members = vec![];
+                loop {
+                    let mut stray_periods = vec![];
+                    while parser.tokens.sees(TokenKind::Period) {
+                        stray_periods.push(parser.tokens.take());
+                    }
+                    if !stray_periods.is_empty() {
+                        diagnostics.push(StrayPeriods(stray_periods));
+                    }
+
+                    match ParseObjectMember.parse(parser).await {
+                        Succeeded(d, member) => {
+                            diagnostics.push_all(d);
+                            members.push(member);
+                        }
+                        Failed(_) => break,
+                    }
+                }
 
                 let close_curly = parser
                     .expect(TokenKind::CloseCurly, "end of object body")
@@ -306,25 +371,32 @@ impl ParseStrategy<Arc<Method>> for ParseMethod {
             .parse(parser)
             .await
             .and_then(async move |pattern| {
-                parser
-                    .expect(TokenKind::Arrow, "method body")
-                    .and_then(async move |arrow| {
-                        ParseMany::of(ParseStatement)
-                            .parse(parser)
-                            .await
-                            .and_then(async move |statements| {
-                                if statements.len() == 0 {
-                                    return parser.fail_expecting("statement");
-                                }
-                                Succeeded(
-                                    Diagnostics::new(),
-                                    Arc::new(Method {
-                                        source: parser.source.clone(),
-                                        pattern,
-                                        arrow,
-                                        statements,
-                                    }),
-                                )
+                ParseReplyType
+                    .parse(parser)
+                    .await
+                    .and_then(async move |reply_type| {
+                        parser
+                            .expect(TokenKind::Arrow, "method body")
+                            .and_then(async move |arrow| {
+                                ParseMany::of(ParseStatement)
+                                    .parse(parser)
+                                    .await
+                                    .and_then(async move |statements| {
+                                        if statements.len() == 0 {
+                                            return parser.fail_expecting("statement");
+                                        }
+                                        Succeeded(
+                                            Diagnostics::new(),
+                                            Arc::new(Method {
+                                                source: parser.source.clone(),
+                                                pattern,
+                                                reply_type,
+                                                arrow,
+                                                statements,
+                                            }),
+                                        )
+                                    })
+                                    .await
                             })
                             .await
                     })
@@ -334,6 +406,23 @@ impl ParseStrategy<Arc<Method>> for ParseMethod {
     }
 }
 
+struct ParseReplyType;
+
+#[async_trait]
+impl ParseStrategy<Option<Arc<TypeExpression>>> for ParseReplyType {
+    fn describe(&self) -> String {
+        "reply type".into()
+    }
+
+    async fn parse(self, parser: &mut Parser) -> ParseResult<Option<Arc<TypeExpression>>> {
+        if !parser.tokens.sees(TokenKind::Colon) {
+            return Succeeded(Diagnostics::new(), None);
+        }
+        parser.tokens.skip();
+        ParseTypeExpression.parse(parser).await.map(Some)
+    }
+}
+
 #[derive(Clone)]
 struct ParseStatement;
 
@@ -344,21 +433,55 @@ impl ParseStrategy<Arc<Statement>> for ParseStatement {
     }
 
     async fn parse(self, parser: &mut Parser) -> ParseResult<Arc<Statement>> {
-        ParseExpression
-            .parse(parser)
+        if parser.sees_method_head().await {
+            return parser.fail_expecting("statement");
+        }
+        let binding = ParseBinding.maybe().parse(parser).await;
+        binding
+            .and_then(async move |binding| {
+                ParseExpression
+                    .parse(parser)
+                    .await
+                    .and_then(async move |expression| {
+                        let mut diagnostics = Diagnostics::new();
+                        let period = parser.expect_optional_period(&mut diagnostics);
+
+                        Succeeded(
+                            diagnostics,
+                            Arc::new(Statement {
+                                source: parser.source.clone(),
+                                binding,
+                                expression,
+                                period,
+                            }),
+                        )
+                    })
+                    .await
+            })
             .await
-            .and_then(async move |expression| {
-                let mut diagnostics = Diagnostics::new();
-                let period = parser.expect_optional_period(&mut diagnostics);
+    }
+}
 
-                Succeeded(
-                    diagnostics,
-                    Arc::new(Statement {
+struct ParseBinding;
+
+#[async_trait]
+impl ParseStrategy<Arc<Binding>> for ParseBinding {
+    fn describe(&self) -> String {
+        "binding".into()
+    }
+
+    async fn parse(self, parser: &mut Parser) -> ParseResult<Arc<Binding>> {
+        ParseSymbol
+            .parse(parser)
+            .await
+            .and_then(async move |symbol| {
+                parser.expect(TokenKind::Equals, "=").map(|equals| {
+                    Arc::new(Binding {
                         source: parser.source.clone(),
-                        expression,
-                        period,
-                    }),
-                )
+                        symbol,
+                        equals,
+                    })
+                })
             })
             .await
     }
@@ -372,6 +495,42 @@ impl ParseStrategy<Arc<Pattern>> for ParsePattern {
         "pattern".into()
     }
 
+    async fn parse(self, parser: &mut Parser) -> ParseResult<Arc<Pattern>> {
+        ParseSinglePattern
+            .parse(parser)
+            .await
+            .and_then(async move |first| {
+                if !parser.tokens.sees(TokenKind::Pipe) {
+                    return first.into();
+                }
+
+                let mut diagnostics = Diagnostics::new();
+                let mut alternatives = vec![first];
+                while parser.tokens.sees(TokenKind::Pipe) {
+                    parser.tokens.skip();
+                    match ParseSinglePattern
+                        .parse(parser)
+                        .await
+                        .collect_diagnostics(&mut diagnostics)
+                    {
+                        Some(pattern) => alternatives.push(pattern),
+                        None => return Failed(diagnostics),
+                    }
+                }
+                Succeeded(diagnostics, Arc::new(Pattern::Or(alternatives)))
+            })
+            .await
+    }
+}
+
+struct ParseSinglePattern;
+
+#[async_trait]
+impl ParseStrategy<Arc<Pattern>> for ParseSinglePattern {
+    fn describe(&self) -> String {
+        "pattern".into()
+    }
+
     async fn parse(self, parser: &mut Parser) -> ParseResult<Arc<Pattern>> {
         match parser.tokens.peek().kind {
             TokenKind::IntegerLiteral(_, _) => Succeeded(
@@ -381,7 +540,7 @@ impl ParseStrategy<Arc<Pattern>> for ParsePattern {
                     literal: parser.tokens.take(),
                 }))),
             ),
-            TokenKind::NullaryAtom => Succeeded(
+            TokenKind::NullaryAtom(_) => Succeeded(
                 Diagnostics::new(),
                 Arc::new(Pattern::Nullary(Arc::new(NullaryAtomExpression {
                     source: parser.source.clone(),
@@ -465,8 +624,14 @@ impl ParseStrategy<Arc<Expression>> for ParseExpression {
             .await
             .and_then(async move |mut expression| {
                 let mut diagnostics = Diagnostics::new();
-                while let Succeeded(d, message) = ParseTerm.parse(parser).await {
-                    diagnostics.push_all(d);
+                while !parser.sees_method_head().await {
+                    let message = match ParseTerm.parse(parser).await {
+                        Succeeded(d, message) => {
+                            diagnostics.push_all(d);
+                            message
+                        }
+                        Failed(_) => break,
+                    };
                     expression = Arc::new(Expression::MessageSend(Arc::new(MessageSend {
                         source: parser.source.clone(),
                         receiver: expression,
@@ -504,18 +669,48 @@ impl ParseStrategy<Arc<Expression>> for ParseTerm {
                     literal: parser.tokens.take(),
                 }))),
             ),
-            TokenKind::NullaryAtom => Succeeded(
+            TokenKind::NullaryAtom(_) => Succeeded(
                 Diagnostics::new(),
                 Arc::new(Expression::NullaryAtom(Arc::new(NullaryAtomExpression {
                     source: parser.source.clone(),
                     atom: parser.tokens.take(),
                 }))),
             ),
+            TokenKind::SelfKeyword => Succeeded(
+                Diagnostics::new(),
+                Arc::new(Expression::SelfReference(Arc::new(SelfExpression {
+                    source: parser.source.clone(),
+                    keyword: parser.tokens.take(),
+                }))),
+            ),
+            TokenKind::SenderKeyword => Succeeded(
+                Diagnostics::new(),
+                Arc::new(Expression::Sender(Arc::new(SenderExpression {
+                    source: parser.source.clone(),
+                    keyword: parser.tokens.take(),
+                }))),
+            ),
             TokenKind::Hat => ParseAnswerExpression
                 .map(Expression::Answer)
                 .parse(parser)
                 .await
                 .map(Arc::new),
+            TokenKind::Identifier if float_word(parser.tokens.peek().lexeme()).is_some() => {
+                let word = parser.tokens.take();
+                let value = float_word(word.lexeme()).unwrap();
+                let range = word.range.start.offset..word.range.end.offset;
+                Succeeded(
+                    Diagnostics::new(),
+                    Arc::new(Expression::Float(Arc::new(Float {
+                        source: parser.source.clone(),
+                        literal: Token::new(
+                            TokenKind::FloatLiteral(value, true),
+                            &word.source,
+                            range,
+                        ),
+                    }))),
+                )
+            }
             TokenKind::Identifier => ParseReferenceExpression
                 .map(Expression::Reference)
                 .parse(parser)
@@ -588,4 +783,250 @@ mod tests {
 
         assert_eq!(module.as_module().unwrap().declarations.len(), 1)
     }
+
+    #[tokio::test]
+    async fn declaration_ranges() {
+        let source = Source::new("test:x", "object X. object Y.");
+        let (root, _) = Parser::new(source.clone()).parse().await;
+        let module = root.as_module().unwrap();
+        assert!(module.validate_ranges());
+
+        let mut declarations = module.declarations.clone();
+        declarations.reverse();
+        let overlapping = Module {
+            source,
+            declarations,
+        };
+        assert!(!overlapping.validate_ranges());
+    }
+
+    async fn single_method(code: &str) -> (Arc<Method>, Diagnostics) {
+        let source = Source::new("test:x", code);
+        let (root, diagnostics) = Parser::new(source).parse().await;
+        let method = Navigator::new(root).all_methods().next().unwrap();
+        (method, diagnostics)
+    }
+
+    #[tokio::test]
+    async fn method_without_reply_type() {
+        let (method, diagnostics) = single_method("object X { 1 -> 2. }").await;
+
+        assert!(diagnostics.is_empty());
+        assert!(method.reply_type.is_none());
+    }
+
+    #[tokio::test]
+    async fn method_nodes() {
+        let (method, diagnostics) = single_method("object X { ping! -> ^pong!. }").await;
+
+        assert!(diagnostics.is_empty());
+        match method.pattern.as_ref() {
+            Pattern::Nullary(n) => assert_eq!(n.atom.lexeme(), "ping!"),
+            p => panic!("expected a nullary pattern, got {:?}", p),
+        }
+        assert_eq!(method.arrow.lexeme(), "->");
+        assert_eq!(method.statements.len(), 1);
+
+        let statement = &method.statements[0];
+        assert_eq!(statement.period.as_ref().map(|p| p.lexeme()), Some("."));
+        assert_eq!(statement.children().count(), 1);
+        match statement.expression.as_ref() {
+            Expression::Answer(answer) => {
+                assert_eq!(answer.hat.lexeme(), "^");
+                assert!(statement.range().contains(&answer.range()));
+            }
+            e => panic!("expected an answer, got {:?}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn self_send() {
+        let (method, diagnostics) = single_method("object X { 1 -> self 2. }").await;
+
+        assert!(diagnostics.is_empty());
+        match method.statements[0].expression.as_ref() {
+            Expression::MessageSend(send) => match send.receiver.as_ref() {
+                Expression::SelfReference(s) => assert_eq!(s.keyword.lexeme(), "self"),
+                e => panic!("expected self, got {:?}", e),
+            },
+            e => panic!("expected a message send, got {:?}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn sender() {
+        let (method, diagnostics) = single_method("object X { 1 -> sender 2. }").await;
+
+        assert!(diagnostics.is_empty());
+        match method.statements[0].expression.as_ref() {
+            Expression::MessageSend(send) => match send.receiver.as_ref() {
+                Expression::Sender(s) => assert_eq!(s.keyword.lexeme(), "sender"),
+                e => panic!("expected sender, got {:?}", e),
+            },
+            e => panic!("expected a message send, got {:?}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn method_with_fat_arrow() {
+        let (method, diagnostics) = single_method("object X { 1 => 2. }").await;
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(method.statements.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn method_with_reply_type() {
+        let (method, diagnostics) = single_method("object X { new!: X -> ^X. }").await;
+
+        assert!(diagnostics.is_empty());
+        match method.reply_type.as_ref().map(AsRef::as_ref) {
+            Some(TypeExpression::Reference(r)) => assert_eq!(r.symbol.identifier.lexeme(), "X"),
+            None => panic!("expected a reply type"),
+        }
+        assert_eq!(method.statements.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn method_with_missing_reply_type() {
+        let (_, diagnostics) = Parser::new(Source::new("test:x", "object X { 1: -> 2. }"))
+            .parse()
+            .await;
+
+        assert!(!diagnostics.is_empty());
+    }
+
+    #[tokio::test]
+    async fn method_outside_object() {
+        let source = Source::new("test:x", "1 => 2. object X.");
+        let (root, diagnostics) = Parser::new(source).parse().await;
+
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message()).collect();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("inside an object body"));
+        assert_eq!(root.as_module().unwrap().declarations.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn arbitrary_bytes_never_panic() {
+        const INTERESTING: &[u8] = b"object{}^:.->=>!?#'-0123456789abcXYZ \n\t\r\xFF\xC3\xA5";
+
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        for _ in 0..500 {
+            let length = next() % 64;
+            let bytes: Vec<u8> = (0..length)
+                .map(|_| {
+                    let n = next();
+                    if n % 4 == 0 {
+                        n as u8
+                    } else {
+                        INTERESTING[(n >> 8) as usize % INTERESTING.len()]
+                    }
+                })
+                .collect();
+
+            Parser::new(Source::from_bytes("test:x", &bytes))
+                .parse()
+                .await;
+        }
+
+        let (root, _) =
+            Parser::parse_lenient(Source::from_bytes("test:x", b"object X { \xFF")).await;
+        assert!(root.as_module().is_some());
+    }
+
+    #[tokio::test]
+    async fn alternative_patterns() {
+        let (method, diagnostics) = single_method("object X { 1 | 2 | 3 => 4. }").await;
+
+        assert!(diagnostics.is_empty());
+        match method.pattern.as_ref() {
+            Pattern::Or(alternatives) => {
+                let literals: Vec<_> = alternatives
+                    .iter()
+                    .map(|p| match p.as_ref() {
+                        Pattern::Integer(i) => i.literal.lexeme().to_string(),
+                        p => panic!("expected an integer pattern, got {:?}", p),
+                    })
+                    .collect();
+                assert_eq!(literals, vec!["1", "2", "3"]);
+            }
+            p => panic!("expected alternatives, got {:?}", p),
+        }
+        assert_eq!(method.pattern.range().start.character, 12);
+
+        let (_, diagnostics) = Parser::new(Source::new("test:x", "object X { 1 | -> 2. }"))
+            .parse()
+            .await;
+        assert!(!diagnostics.is_empty());
+    }
+
+    #[tokio::test]
+    async fn statement_with_binding() {
+        let (method, diagnostics) = single_method("object X { 1 -> x = 2. x 3. }").await;
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(method.statements.len(), 2);
+        assert_eq!(method.statements[0].bound_name(), Some("x"));
+        assert_eq!(method.statements[1].bound_name(), None);
+        match method.statements[1].expression.as_ref() {
+            Expression::MessageSend(send) => match send.receiver.as_ref() {
+                Expression::Reference(r) => assert_eq!(r.symbol.identifier.lexeme(), "x"),
+                e => panic!("expected a reference, got {:?}", e),
+            },
+            e => panic!("expected a message send, got {:?}", e),
+        }
+    }
+
+    async fn object_members(code: &str) -> (Vec<Arc<Method>>, Vec<String>) {
+        let (root, diagnostics) = Parser::new(Source::new("test:x", code)).parse().await;
+        let methods = Navigator::new(root).all_methods().collect();
+        let messages = diagnostics.iter().map(|d| d.message()).collect();
+        (methods, messages)
+    }
+
+    #[tokio::test]
+    async fn members_separated_by_periods() {
+        let (methods, messages) = object_members("object X { a! -> 1. b! -> 2. 3 -> 4. }").await;
+
+        assert!(messages.is_empty());
+        assert_eq!(methods.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn member_missing_its_period_is_recovered() {
+        let (methods, messages) = object_members("object X { a! -> 1 b! -> 2. }").await;
+
+        assert_eq!(messages, vec!["Expected period"]);
+        assert_eq!(methods.len(), 2);
+        assert!(methods[0].statements[0].period.is_none());
+        match methods[0].statements[0].expression.as_ref() {
+            Expression::Integer(i) => assert_eq!(i.literal.lexeme(), "1"),
+            e => panic!("expected an integer, got {:?}", e),
+        }
+        match methods[1].pattern.as_ref() {
+            Pattern::Nullary(n) => assert_eq!(n.atom.lexeme(), "b!"),
+            p => panic!("expected a nullary pattern, got {:?}", p),
+        }
+    }
+
+    #[tokio::test]
+    async fn stray_periods_between_members() {
+        let (methods, messages) = object_members("object X { a! -> 1.. . b! -> 2. }").await;
+
+        assert_eq!(messages, vec!["Unexpected periods"]);
+        assert_eq!(methods.len(), 2);
+
+        let (methods, messages) = object_members("object X { . a! -> 1. }").await;
+
+        assert_eq!(messages, vec!["Unexpected period"]);
+        assert_eq!(methods.len(), 1);
+    }
 }
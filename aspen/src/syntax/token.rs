@@ -27,6 +27,14 @@ impl Token {
     pub fn lexeme(&self) -> &str {
         self.source.slice(&self.range)
     }
+
+    /// Whether the token is a command or a query atom.
+    pub fn atom_kind(&self) -> Option<AtomKind> {
+        match self.kind {
+            NullaryAtom(kind) => Some(kind),
+            _ => None,
+        }
+    }
 }
 
 impl PartialEq for Token {
@@ -52,19 +60,63 @@ pub enum TokenKind {
     Unknown,
     EOF,
     Whitespace,
+    /// A `#!` line at the very start of a source, so that scripts can be
+    /// made executable.
+    Shebang,
 
     Period,
     Arrow,
     Hat,
+    Colon,
+    Pipe,
+    Equals,
 
     IntegerLiteral(i128, bool),
     FloatLiteral(f64, bool),
-    NullaryAtom,
+    NullaryAtom(AtomKind),
 
     Identifier,
 
     ObjectKeyword,
+    SelfKeyword,
+    SenderKeyword,
 
     OpenCurly,
     CloseCurly,
 }
+
+impl TokenKind {
+    /// Whether tokens of the kind are ignored by the parser.
+    pub fn is_trivia(&self) -> bool {
+        match self {
+            Whitespace | Shebang => true,
+            _ => false,
+        }
+    }
+}
+
+/// Atoms ending in `!` are commands, which are conventionally sent
+/// without waiting for a reply, while atoms ending in `?` are queries,
+/// which expect one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AtomKind {
+    Command,
+    Query,
+}
+
+impl AtomKind {
+    /// The kind of an atom, given its lexeme.
+    pub fn of(atom: &str) -> Option<AtomKind> {
+        if atom.ends_with('!') {
+            Some(AtomKind::Command)
+        } else if atom.ends_with('?') {
+            Some(AtomKind::Query)
+        } else {
+            None
+        }
+    }
+
+    pub fn expects_reply(self) -> bool {
+        self == AtomKind::Query
+    }
+}
@@ -31,7 +31,7 @@ impl TokenCursor {
     }
 
     fn move_past_whitespace(&mut self) {
-        while self.sees(Whitespace) {
+        while self.peek().kind.is_trivia() {
             self.offset += 1;
         }
     }